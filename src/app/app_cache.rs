@@ -0,0 +1,509 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use actix_web::web;
+use crate::constants::*;
+use super::date_conv::current_timestamp;
+
+tokio::task_local! {
+  static CACHE_TRACE: RefCell<CacheTrace>;
+}
+
+/*
+* Application cache lookups made while serving one request
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheTrace {
+  pub hits: u32,
+  pub misses: u32,
+}
+
+impl CacheTrace {
+  /*
+  * X-Cache header value, HIT if all lookups were served from the cache, MISS if none and PARTIAL otherwise
+  */
+  pub fn status(&self) -> Option<&'static str> {
+    match (self.hits, self.misses) {
+      (0, 0) => None,
+      (_, 0) => Some("HIT"),
+      (0, _) => Some("MISS"),
+      _ => Some("PARTIAL")
+    }
+  }
+}
+
+/*
+* Run a request future while counting cache hits and misses
+*/
+pub async fn with_cache_trace<F: Future>(future: F) -> (F::Output, CacheTrace) {
+  CACHE_TRACE.scope(RefCell::new(CacheTrace::default()), async move {
+    let output = future.await;
+    let trace = CACHE_TRACE.with(|trace| *trace.borrow());
+    (output, trace)
+  }).await
+}
+
+fn record_cache_lookup(hit: bool) {
+  let _ = CACHE_TRACE.try_with(|trace| {
+    let mut trace = trace.borrow_mut();
+    if hit { trace.hits += 1; } else { trace.misses += 1; }
+  });
+}
+
+/*
+* Stable 64-bit FNV-1a hash, so file names remain valid across builds
+*/
+fn fnv_hash(text: &str) -> u64 {
+  text.bytes().fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/*
+* Persistent key-value store for cached responses. Its methods block on file or network I/O,
+* so async handlers go through cache_get, cache_set, cache_count and cache_purge, which run them on the blocking pool.
+*/
+#[derive(Debug, Clone)]
+pub enum CacheBackend {
+  // one file per entry with the expiry timestamp on the first line
+  Directory { dir: PathBuf, max_entries: usize },
+  // any server speaking the Redis protocol, e.g. Redis, Valkey or KeyDB
+  Redis { address: String, password: Option<String>, db: u32, prefix: String },
+}
+
+impl CacheBackend {
+  pub fn name(&self) -> &'static str {
+    match self {
+      CacheBackend::Directory { .. } => "directory",
+      CacheBackend::Redis { .. } => "redis",
+    }
+  }
+
+  fn entry_path(dir: &Path, key: &str) -> PathBuf {
+    let clean: String = key.chars().take(120).map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' { c } else { '~' }).collect();
+    dir.join(format!("{}-{:016x}", clean, fnv_hash(key)))
+  }
+
+  pub fn get(&self, key: &str) -> Option<String> {
+    match self {
+      CacheBackend::Directory { dir, .. } => {
+        let path = CacheBackend::entry_path(dir, key);
+        let text = std::fs::read_to_string(&path).ok()?;
+        let (expiry, value) = text.split_once('\n')?;
+        if expiry.parse::<i64>().unwrap_or(0) > current_timestamp() {
+          Some(value.to_owned())
+        } else {
+          let _ = std::fs::remove_file(path);
+          None
+        }
+      },
+      CacheBackend::Redis { prefix, .. } => {
+        match self.redis_command(&["GET", &format!("{}{}", prefix, key)]) {
+          Ok(RespValue::Bulk(Some(value))) => Some(value),
+          _ => None
+        }
+      }
+    }
+  }
+
+  pub fn set(&self, key: &str, value: &str, ttl: u64) {
+    match self {
+      CacheBackend::Directory { dir, .. } => {
+        if std::fs::create_dir_all(dir).is_err() {
+          return;
+        }
+        let expiry = current_timestamp() + ttl as i64;
+        if let Err(e) = std::fs::write(CacheBackend::entry_path(dir, key), format!("{}\n{}", expiry, value)) {
+          println!("{:?}", e);
+        }
+      },
+      CacheBackend::Redis { prefix, .. } => {
+        if let Err(e) = self.redis_command(&["SET", &format!("{}{}", prefix, key), value, "EX", &ttl.to_string()]) {
+          println!("{:?}", e);
+        }
+      }
+    }
+  }
+
+  /*
+  * Remove expired entries and then the oldest tenth of the rest once the directory exceeds its size limit.
+  * Run periodically rather than on each write, as it reads every entry. Redis expires entries itself.
+  */
+  pub fn sweep(&self) -> usize {
+    let (dir, max_entries) = match self {
+      CacheBackend::Directory { dir, max_entries } => (dir, *max_entries),
+      CacheBackend::Redis { .. } => return 0
+    };
+    let now = current_timestamp();
+    let mut removed = 0;
+    let mut entries: Vec<(std::time::SystemTime, PathBuf)> = vec![];
+    if let Ok(read_dir) = std::fs::read_dir(dir) {
+      for entry in read_dir.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let expiry = std::fs::File::open(&path).ok().and_then(|file| {
+          let mut line = String::new();
+          BufReader::new(file).read_line(&mut line).ok()?;
+          line.trim().parse::<i64>().ok()
+        });
+        if expiry.map(|ts| ts <= now).unwrap_or(false) {
+          if std::fs::remove_file(&path).is_ok() {
+            removed += 1;
+          }
+        } else if let Some(modified) = entry.metadata().ok().and_then(|m| m.modified().ok()) {
+          entries.push((modified, path));
+        }
+      }
+    }
+    if entries.len() > max_entries {
+      entries.sort();
+      let num_remove = entries.len() - max_entries + max_entries / 10;
+      for (_, path) in entries.into_iter().take(num_remove) {
+        if std::fs::remove_file(path).is_ok() {
+          removed += 1;
+        }
+      }
+    }
+    removed
+  }
+
+  fn matching_redis_keys(&self, prefix: &str) -> Vec<String> {
+    let pattern = format!("{}*", prefix);
+    let mut keys: Vec<String> = Vec::new();
+    let mut cursor = "0".to_owned();
+    loop {
+      match self.redis_command(&["SCAN", &cursor, "MATCH", &pattern, "COUNT", "1000"]) {
+        Ok(RespValue::Array(items)) if items.len() == 2 => {
+          if let (RespValue::Bulk(Some(next)), RespValue::Array(batch)) = (&items[0], &items[1]) {
+            keys.extend(batch.iter().filter_map(|item| match item {
+              RespValue::Bulk(Some(key)) => Some(key.clone()),
+              _ => None
+            }));
+            cursor = next.clone();
+          }
+          if cursor == "0" {
+            break;
+          }
+        },
+        _ => break
+      }
+    }
+    keys
+  }
+
+  /*
+  * Delete all entries and return the number removed
+  */
+  pub fn purge(&self) -> usize {
+    match self {
+      CacheBackend::Directory { dir, .. } => {
+        let mut count = 0;
+        if let Ok(read_dir) = std::fs::read_dir(dir) {
+          for entry in read_dir.filter_map(|e| e.ok()) {
+            if std::fs::remove_file(entry.path()).is_ok() {
+              count += 1;
+            }
+          }
+        }
+        count
+      },
+      CacheBackend::Redis { prefix, .. } => {
+        let keys = self.matching_redis_keys(prefix);
+        let mut count = 0;
+        for chunk in keys.chunks(500) {
+          let mut args: Vec<&str> = vec!["DEL"];
+          args.extend(chunk.iter().map(|k| k.as_str()));
+          if let Ok(RespValue::Integer(num)) = self.redis_command(&args) {
+            count += num as usize;
+          }
+        }
+        count
+      }
+    }
+  }
+
+  pub fn count(&self) -> usize {
+    match self {
+      CacheBackend::Directory { dir, .. } => std::fs::read_dir(dir).map(|rd| rd.count()).unwrap_or(0),
+      CacheBackend::Redis { prefix, .. } => self.matching_redis_keys(prefix).len(),
+    }
+  }
+
+  /*
+  * Send one command on a new connection. Connections are short-lived like the MySQL pools in this service.
+  */
+  fn redis_command(&self, args: &[&str]) -> std::io::Result<RespValue> {
+    if let CacheBackend::Redis { address, password, db, .. } = self {
+      let stream = TcpStream::connect(address)?;
+      stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+      stream.set_write_timeout(Some(Duration::from_secs(2)))?;
+      let mut reader = BufReader::new(stream.try_clone()?);
+      let mut writer = stream;
+      if let Some(pass) = password {
+        write_resp_command(&mut writer, &["AUTH", pass])?;
+        read_resp_value(&mut reader)?;
+      }
+      if *db > 0 {
+        write_resp_command(&mut writer, &["SELECT", &db.to_string()])?;
+        read_resp_value(&mut reader)?;
+      }
+      write_resp_command(&mut writer, args)?;
+      read_resp_value(&mut reader)
+    } else {
+      Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "not a Redis backend"))
+    }
+  }
+}
+
+/*
+* Reply in the Redis serialization protocol (RESP2)
+*/
+#[derive(Debug, Clone, PartialEq)]
+enum RespValue {
+  Simple,
+  Integer(i64),
+  Bulk(Option<String>),
+  Array(Vec<RespValue>),
+}
+
+fn write_resp_command<W: Write>(writer: &mut W, args: &[&str]) -> std::io::Result<()> {
+  let mut buffer = format!("*{}\r\n", args.len());
+  for arg in args {
+    buffer.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+  }
+  writer.write_all(buffer.as_bytes())
+}
+
+fn read_resp_value<R: BufRead>(reader: &mut R) -> std::io::Result<RespValue> {
+  let mut line = String::new();
+  reader.read_line(&mut line)?;
+  let line = line.trim_end_matches("\r\n");
+  let invalid = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_owned());
+  if line.is_empty() {
+    return Err(invalid("empty reply"));
+  }
+  let (kind, rest) = line.split_at(1);
+  match kind {
+    "+" => Ok(RespValue::Simple),
+    "-" => Err(invalid(rest)),
+    ":" => rest.parse::<i64>().map(RespValue::Integer).map_err(|_| invalid("invalid integer reply")),
+    "$" => {
+      let len = rest.parse::<i64>().unwrap_or(-1);
+      if len < 0 {
+        Ok(RespValue::Bulk(None))
+      } else {
+        let mut buf = vec![0u8; len as usize + 2];
+        reader.read_exact(&mut buf)?;
+        buf.truncate(len as usize);
+        Ok(RespValue::Bulk(Some(String::from_utf8_lossy(&buf).to_string())))
+      }
+    },
+    "*" => {
+      let len = rest.parse::<i64>().unwrap_or(-1);
+      let mut items: Vec<RespValue> = Vec::new();
+      for _ in 0..len.max(0) {
+        items.push(read_resp_value(reader)?);
+      }
+      Ok(RespValue::Array(items))
+    },
+    _ => Err(invalid("unknown reply type"))
+  }
+}
+
+/*
+* Parse redis://[:password@]host[:port][/db]
+*/
+fn parse_redis_url(url: &str) -> (String, Option<String>, u32) {
+  let rest = url.trim().trim_start_matches("redis://");
+  let (auth, host_part) = match rest.rsplit_once('@') {
+    Some((auth, host)) => (Some(auth), host),
+    None => (None, rest)
+  };
+  let password = auth.map(|a| a.rsplit_once(':').map(|(_, p)| p).unwrap_or(a).to_owned()).filter(|p| !p.is_empty());
+  let (host, db) = match host_part.split_once('/') {
+    Some((host, db)) => (host, db.parse::<u32>().unwrap_or(0)),
+    None => (host_part, 0)
+  };
+  let address = if host.contains(':') { host.to_owned() } else { format!("{}:6379", host) };
+  (address, password, db)
+}
+
+fn match_env_u64(key: &str, default_value: u64) -> u64 {
+  dotenv::var(key).ok().and_then(|v| v.parse::<u64>().ok()).unwrap_or(default_value)
+}
+
+/*
+* Backend configured via cache_backend: directory, redis or none (default), so nothing is written unless a cache is chosen
+*/
+pub fn app_cache_backend() -> Option<CacheBackend> {
+  match dotenv::var("cache_backend").unwrap_or_default().trim().to_lowercase().as_str() {
+    "redis" => {
+      let (address, password, db) = parse_redis_url(&dotenv::var("cache_redis_url").unwrap_or(CACHE_REDIS_URL_DEFAULT.to_owned()));
+      let prefix = dotenv::var("cache_prefix").unwrap_or(CACHE_KEY_PREFIX_DEFAULT.to_owned());
+      Some(CacheBackend::Redis { address, password, db, prefix })
+    },
+    "directory" => {
+      let dir = PathBuf::from(dotenv::var("cache_dir").unwrap_or(CACHE_DIR_DEFAULT.to_owned()));
+      let max_entries = match_env_u64("cache_max_entries", CACHE_MAX_ENTRIES_DEFAULT) as usize;
+      Some(CacheBackend::Directory { dir, max_entries })
+    },
+    _ => None
+  }
+}
+
+/*
+* TTL in seconds for coordinate lookups (cache_ttl) or place name searches (cache_search_ttl)
+*/
+pub fn match_cache_ttl(is_search: bool) -> u64 {
+  if is_search {
+    match_env_u64("cache_search_ttl", CACHE_SEARCH_TTL_DEFAULT)
+  } else {
+    match_env_u64("cache_ttl", CACHE_TTL_DEFAULT)
+  }
+}
+
+/*
+* Decimal places to which coordinates are rounded in cache keys and upstream requests
+*/
+pub fn match_cache_coord_precision() -> i32 {
+  match_env_u64("cache_coord_precision", CACHE_COORD_PRECISION_DEFAULT as u64).min(8) as i32
+}
+
+pub async fn cache_get(backend: &CacheBackend, key: &str) -> Option<String> {
+  let (backend, key) = (backend.clone(), key.to_owned());
+  let value = web::block(move || backend.get(&key)).await.ok().flatten();
+  record_cache_lookup(value.is_some());
+  value
+}
+
+pub async fn cache_set(backend: &CacheBackend, key: &str, value: String, ttl: u64) {
+  let (backend, key) = (backend.clone(), key.to_owned());
+  let _ = web::block(move || backend.set(&key, &value, ttl)).await;
+}
+
+pub async fn cache_count(backend: &CacheBackend) -> usize {
+  let backend = backend.clone();
+  web::block(move || backend.count()).await.unwrap_or(0)
+}
+
+pub async fn cache_purge(backend: &CacheBackend) -> usize {
+  let backend = backend.clone();
+  web::block(move || backend.purge()).await.unwrap_or(0)
+}
+
+/*
+* Sweep the configured cache at the interval set by cache_sweep_interval in seconds, on a background thread
+*/
+pub fn run_cache_sweeper() {
+  let interval = match_env_u64("cache_sweep_interval", CACHE_SWEEP_INTERVAL_DEFAULT).max(1);
+  loop {
+    std::thread::sleep(Duration::from_secs(interval));
+    if let Some(backend) = app_cache_backend() {
+      backend.sweep();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  fn read_reply(bytes: &str) -> std::io::Result<RespValue> {
+    read_resp_value(&mut Cursor::new(bytes.as_bytes().to_vec()))
+  }
+
+  #[test]
+  fn commands_are_sent_as_arrays_of_bulk_strings() {
+    let mut buffer: Vec<u8> = vec![];
+    write_resp_command(&mut buffer, &["SET", "geotz:k", "{\"a\":1}", "EX", "60"]).unwrap();
+    assert_eq!(String::from_utf8(buffer).unwrap(), "*5\r\n$3\r\nSET\r\n$7\r\ngeotz:k\r\n$7\r\n{\"a\":1}\r\n$2\r\nEX\r\n$2\r\n60\r\n");
+  }
+
+  #[test]
+  fn bulk_replies_are_read_by_length() {
+    assert_eq!(read_reply("$5\r\nhello\r\n").unwrap(), RespValue::Bulk(Some("hello".to_owned())));
+    assert_eq!(read_reply("$7\r\na\r\nb:cd\r\n").unwrap(), RespValue::Bulk(Some("a\r\nb:cd".to_owned())));
+    assert_eq!(read_reply("$0\r\n\r\n").unwrap(), RespValue::Bulk(Some("".to_owned())));
+    assert_eq!(read_reply("$6\r\nMünch\r\n").unwrap(), RespValue::Bulk(Some("Münch".to_owned())));
+    assert!(read_reply("$10\r\nshort\r\n").is_err());
+  }
+
+  #[test]
+  fn nil_replies_are_empty_bulk_strings() {
+    assert_eq!(read_reply("$-1\r\n").unwrap(), RespValue::Bulk(None));
+  }
+
+  #[test]
+  fn error_replies_become_errors_with_the_message() {
+    let err = read_reply("-NOAUTH Authentication required.\r\n").unwrap_err();
+    assert_eq!(err.to_string(), "NOAUTH Authentication required.");
+    assert!(read_reply("").is_err());
+    assert!(read_reply("?what\r\n").is_err());
+  }
+
+  #[test]
+  fn integer_and_status_replies() {
+    assert_eq!(read_reply(":42\r\n").unwrap(), RespValue::Integer(42));
+    assert_eq!(read_reply(":-3\r\n").unwrap(), RespValue::Integer(-3));
+    assert!(read_reply(":x\r\n").is_err());
+    assert_eq!(read_reply("+OK\r\n").unwrap(), RespValue::Simple);
+  }
+
+  #[test]
+  fn scan_replies_are_nested_arrays() {
+    let reply = read_reply("*2\r\n$1\r\n0\r\n*2\r\n$7\r\ngeotz:a\r\n$7\r\ngeotz:b\r\n").unwrap();
+    assert_eq!(reply, RespValue::Array(vec![
+      RespValue::Bulk(Some("0".to_owned())),
+      RespValue::Array(vec![RespValue::Bulk(Some("geotz:a".to_owned())), RespValue::Bulk(Some("geotz:b".to_owned()))]),
+    ]));
+    assert_eq!(read_reply("*-1\r\n").unwrap(), RespValue::Array(vec![]));
+  }
+
+  #[test]
+  fn redis_urls_with_password_port_and_database() {
+    assert_eq!(parse_redis_url("redis://:secret@10.0.0.2:6380/2"), ("10.0.0.2:6380".to_owned(), Some("secret".to_owned()), 2));
+    assert_eq!(parse_redis_url("redis://127.0.0.1"), ("127.0.0.1:6379".to_owned(), None, 0));
+    assert_eq!(parse_redis_url("redis://user:pw@cache/1"), ("cache:6379".to_owned(), Some("pw".to_owned()), 1));
+  }
+
+  fn temp_directory(name: &str) -> CacheBackend {
+    let dir = std::env::temp_dir().join(format!("geotz-cache-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    CacheBackend::Directory { dir, max_entries: 2 }
+  }
+
+  #[test]
+  fn directory_entries_are_read_back_until_they_expire() {
+    let backend = temp_directory("expiry");
+    backend.set("timezoneJSON:lat=51.5&lng=-0.1", "{\"timezoneId\":\"Europe/London\"}", 60);
+    assert_eq!(backend.get("timezoneJSON:lat=51.5&lng=-0.1").as_deref(), Some("{\"timezoneId\":\"Europe/London\"}"));
+    assert_eq!(backend.get("timezoneJSON:lat=48.9&lng=2.4"), None);
+    backend.set("searchJSON:q=paris", "{}", 0);
+    assert_eq!(backend.count(), 2);
+    assert_eq!(backend.get("searchJSON:q=paris"), None);
+    assert_eq!(backend.count(), 1);
+    assert_eq!(backend.purge(), 1);
+  }
+
+  #[test]
+  fn sweeps_remove_expired_and_surplus_entries() {
+    let backend = temp_directory("sweep");
+    for key in ["a", "b", "c"] {
+      backend.set(key, "{}", 60);
+    }
+    backend.set("expired", "{}", 0);
+    assert_eq!(backend.sweep(), 2);
+    assert_eq!(backend.count(), 2);
+    backend.purge();
+  }
+
+  #[test]
+  fn entry_file_names_are_safe_and_distinct() {
+    let dir = Path::new("/tmp");
+    let path = CacheBackend::entry_path(dir, "searchJSON:q=são paulo");
+    let file_name = path.file_name().unwrap().to_str().unwrap();
+    assert!(file_name.starts_with("searchJSON~q~s~o~paulo-"));
+    assert_ne!(path, CacheBackend::entry_path(dir, "searchJSON:q=sào paulo"));
+  }
+}
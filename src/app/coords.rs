@@ -1,5 +1,7 @@
 use serde::{Serialize, Deserialize};
 
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
 pub struct Coords {
   pub lat: f64,
@@ -24,6 +26,38 @@ impl Coords {
     }
   }
 
+  /*
+  * Great-circle distance in kilometres via the haversine formula
+  */
+  pub fn distance_km(&self, other: &Coords) -> f64 {
+    let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lng = (other.lng - self.lng).to_radians();
+    let a = (d_lat / 2f64).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2f64).sin().powi(2);
+    2f64 * EARTH_RADIUS_KM * a.sqrt().asin()
+  }
+
+  /*
+  * Initial compass bearing in degrees from this point towards another, 0 = north, 90 = east
+  */
+  pub fn bearing_to(&self, other: &Coords) -> f64 {
+    let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+    let d_lng = (other.lng - self.lng).to_radians();
+    let y = d_lng.sin() * lat2.cos();
+    let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lng.cos();
+    (y.atan2(x).to_degrees() + 360f64) % 360f64
+  }
+
+  /*
+  * Latitude and longitude ranges enclosing a circle of the given radius, for prefiltering with BETWEEN
+  */
+  pub fn bounding_box(&self, radius_km: f64) -> ((f64, f64), (f64, f64)) {
+    let d_lat = radius_km / 111.2f64;
+    let cos_lat = self.lat.to_radians().cos().abs().max(0.01);
+    let d_lng = (radius_km / (111.2f64 * cos_lat)).min(180f64);
+    ((self.lat - d_lat, self.lat + d_lat), (self.lng - d_lng, self.lng + d_lng))
+  }
+
 }
 
 pub fn loc_string_to_coords(loc: &str) -> Coords {
@@ -34,3 +68,77 @@ pub fn loc_string_to_coords(loc: &str) -> Coords {
     Coords::zero()
   }
 }
+
+/*
+* Ray-casting test for a closed ring of (lng, lat) points
+*/
+pub fn ring_contains_coords(points: &[(f64, f64)], coords: &Coords) -> bool {
+  let mut inside = false;
+  let num_points = points.len();
+  for i in 0..num_points {
+    let (x1, y1) = points[i];
+    let (x2, y2) = points[(i + num_points - 1) % num_points];
+    if (y1 > coords.lat) != (y2 > coords.lat) && coords.lng < (x2 - x1) * (coords.lat - y1) / (y2 - y1) + x1 {
+      inside = !inside;
+    }
+  }
+  inside
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn london() -> Coords {
+    Coords::new(51.5074, -0.1278)
+  }
+
+  #[test]
+  fn great_circle_distances() {
+    let paris = Coords::new(48.8566, 2.3522);
+    assert!((london().distance_km(&paris) - 343.5).abs() < 1.0);
+    assert!((paris.distance_km(&london()) - london().distance_km(&paris)).abs() < 1e-9);
+    assert!((Coords::zero().distance_km(&Coords::new(0.0, 90.0)) - EARTH_RADIUS_KM * std::f64::consts::FRAC_PI_2).abs() < 1e-6);
+    assert!(london().distance_km(&london()) < 1e-9);
+    // across the antimeridian
+    assert!((Coords::new(0.0, 179.5).distance_km(&Coords::new(0.0, -179.5)) - 111.2).abs() < 0.5);
+  }
+
+  #[test]
+  fn initial_bearings() {
+    let origin = Coords::zero();
+    assert!((origin.bearing_to(&Coords::new(1.0, 0.0)) - 0.0).abs() < 1e-9);
+    assert!((origin.bearing_to(&Coords::new(0.0, 1.0)) - 90.0).abs() < 1e-9);
+    assert!((origin.bearing_to(&Coords::new(-1.0, 0.0)) - 180.0).abs() < 1e-9);
+    assert!((origin.bearing_to(&Coords::new(0.0, -1.0)) - 270.0).abs() < 1e-9);
+    assert!((london().bearing_to(&Coords::new(48.8566, 2.3522)) - 148.1).abs() < 0.5);
+  }
+
+  #[test]
+  fn bounding_boxes_enclose_the_radius() {
+    let ((min_lat, max_lat), (min_lng, max_lng)) = london().bounding_box(100.0);
+    assert!((max_lat - min_lat - 200.0 / 111.2).abs() < 1e-9);
+    for corner in [Coords::new(min_lat, london().lng), Coords::new(london().lat, max_lng), Coords::new(london().lat, min_lng)] {
+      assert!(london().distance_km(&corner) >= 99.0);
+    }
+    let (_, (min_lng, max_lng)) = Coords::new(89.0, 0.0).bounding_box(2000.0);
+    assert_eq!((min_lng, max_lng), (-180.0, 180.0));
+  }
+
+  #[test]
+  fn rings_contain_points_by_ray_casting() {
+    // rough outline of Alsace as (lng, lat), open or closed
+    let mut ring = vec![(7.5, 49.1), (8.2, 49.0), (7.6, 47.6), (6.8, 47.5), (7.0, 48.3)];
+    for _ in 0..2 {
+      assert!(ring_contains_coords(&ring, &Coords::new(48.58, 7.75)));
+      assert!(!ring_contains_coords(&ring, &Coords::new(48.86, 2.35)));
+      assert!(!ring_contains_coords(&ring, &Coords::new(49.5, 7.6)));
+      ring.push(ring[0]);
+    }
+    // concave ring: the notch between the two arms is outside
+    let u_shape = [(0.0, 0.0), (3.0, 0.0), (3.0, 3.0), (2.0, 3.0), (2.0, 1.0), (1.0, 1.0), (1.0, 3.0), (0.0, 3.0)];
+    assert!(ring_contains_coords(&u_shape, &Coords::new(2.0, 0.5)));
+    assert!(!ring_contains_coords(&u_shape, &Coords::new(2.0, 1.5)));
+    assert!(!ring_contains_coords(&[], &london()));
+  }
+}
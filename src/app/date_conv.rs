@@ -97,4 +97,223 @@ pub fn natural_hours_offset_from_utc(lng: f64) -> i32 {
   let zone_deg_offset = if lng < 7.5f64 { -7.5f64 } else { 7.5f64 };
   let secs = if lng >= 172.5f64 { 12i32 * 3600i32 } else { natural_tz_offset_from_utc(lng + zone_deg_offset) };
   secs / 3600
-}
\ No newline at end of file
+}
+/*
+* Reference epochs of other time scales expressed in relation to the unix epoch
+*/
+pub const GPS_EPOCH_UNIX: i64 = 315_964_800; // 1980-01-06 00:00:00 UTC
+pub const NTP_EPOCH_OFFSET: i64 = 2_208_988_800; // seconds from 1900-01-01 to 1970-01-01
+pub const MAC_EPOCH_UNIX: i64 = 978_307_200; // 2001-01-01 00:00:00 UTC
+pub const FILETIME_EPOCH_OFFSET: i64 = 11_644_473_600; // seconds from 1601-01-01 to 1970-01-01
+pub const FILETIME_TICKS_PER_SECOND: i64 = 10_000_000; // 100-nanosecond intervals
+pub const MJD_UNIX_EPOCH: f64 = 40_587f64; // Modified Julian Date of 1970-01-01
+pub const EXCEL_UNIX_EPOCH: f64 = 25_569f64; // Excel 1900 date system serial of 1970-01-01
+const SECONDS_PER_WEEK: i64 = 604_800;
+
+/*
+* Months (year, month) on whose first day GPS time moved one further second ahead of UTC
+*/
+const GPS_LEAP_SECOND_MONTHS: [(i32, u32); 18] = [
+  (1981, 7), (1982, 7), (1983, 7), (1985, 7), (1988, 1), (1990, 1),
+  (1991, 1), (1992, 7), (1993, 7), (1994, 7), (1996, 1), (1997, 7),
+  (1999, 1), (2006, 1), (2009, 1), (2012, 7), (2015, 7), (2017, 1),
+];
+
+/*
+* Seconds by which GPS time leads UTC at the given unix timestamp
+*/
+pub fn gps_leap_seconds(ts: i64) -> i64 {
+  GPS_LEAP_SECOND_MONTHS.iter().filter(|(year, month)| {
+    match chrono::NaiveDate::from_ymd_opt(*year, *month, 1).and_then(|d| d.and_hms_opt(0, 0, 0)) {
+      Some(dt) => dt.timestamp() <= ts,
+      None => false,
+    }
+  }).count() as i64
+}
+
+/*
+* Convert GPS week number and seconds of the week to a unix timestamp, removing leap seconds
+*/
+pub fn gps_time_to_unixtime(week: i64, secs: f64) -> i64 {
+  let gps_ts = GPS_EPOCH_UNIX + week * SECONDS_PER_WEEK + secs.round() as i64;
+  let approx_ts = gps_ts - gps_leap_seconds(gps_ts);
+  gps_ts - gps_leap_seconds(approx_ts)
+}
+
+/*
+* Convert a unix timestamp to a GPS week number and seconds of the week
+*/
+pub fn unixtime_to_gps_time(ts: i64) -> (i64, i64) {
+  let gps_secs = ts - GPS_EPOCH_UNIX + gps_leap_seconds(ts);
+  (gps_secs.div_euclid(SECONDS_PER_WEEK), gps_secs.rem_euclid(SECONDS_PER_WEEK))
+}
+
+pub fn mjd_to_unixtime(mjd: f64) -> i64 {
+  ((mjd - MJD_UNIX_EPOCH) * 86_400f64).round() as i64
+}
+
+pub fn unixtime_to_mjd(ts: i64) -> f64 {
+  ts as f64 / 86_400f64 + MJD_UNIX_EPOCH
+}
+
+/*
+* Excel's 1900 date system treats 1900 as a leap year, so serials before 1 March 1900 are one day out
+*/
+pub fn excel_serial_to_unixtime(serial: f64) -> i64 {
+  let days = if serial < 61f64 { serial + 1f64 } else { serial };
+  ((days - EXCEL_UNIX_EPOCH) * 86_400f64).round() as i64
+}
+
+pub fn unixtime_to_excel_serial(ts: i64) -> f64 {
+  let serial = ts as f64 / 86_400f64 + EXCEL_UNIX_EPOCH;
+  if serial < 61f64 { serial - 1f64 } else { serial }
+}
+
+/*
+* NTP timestamps are seconds since 1900-01-01 00:00:00 UTC, with optional fractions
+*/
+pub fn ntp_to_unixtime(ntp: f64) -> i64 {
+  ntp.round() as i64 - NTP_EPOCH_OFFSET
+}
+
+pub fn unixtime_to_ntp(ts: i64) -> i64 {
+  ts + NTP_EPOCH_OFFSET
+}
+
+/*
+* Windows FILETIME values count 100-nanosecond intervals since 1601-01-01 00:00:00 UTC
+*/
+pub fn filetime_to_unixtime(ft: i64) -> i64 {
+  ft.div_euclid(FILETIME_TICKS_PER_SECOND) - FILETIME_EPOCH_OFFSET
+}
+
+pub fn unixtime_to_filetime(ts: i64) -> i64 {
+  (ts + FILETIME_EPOCH_OFFSET) * FILETIME_TICKS_PER_SECOND
+}
+
+/*
+* Mac absolute time (Core Foundation / Cocoa) counts seconds since 2001-01-01 00:00:00 UTC
+*/
+pub fn mac_absolute_to_unixtime(secs: f64) -> i64 {
+  secs.round() as i64 + MAC_EPOCH_UNIX
+}
+
+pub fn unixtime_to_mac_absolute(ts: i64) -> i64 {
+  ts - MAC_EPOCH_UNIX
+}
+
+/*
+* UTC offset in seconds from +05:45, -0330, +5, UTC+5:45 or GMT-3 style strings.
+* A missing sign is read as positive, as an unencoded + in a query string arrives as a space.
+*/
+pub fn parse_utc_offset(text: &str) -> Option<i32> {
+  let mut rest = text.trim().to_uppercase().replace('\u{2212}', "-");
+  for prefix in ["UTC", "GMT"] {
+    if let Some(stripped) = rest.strip_prefix(prefix) {
+      rest = stripped.trim().to_owned();
+    }
+  }
+  if rest.is_empty() || rest == "Z" {
+    return Some(0);
+  }
+  let (sign, body) = match rest.chars().next() {
+    Some('-') => (-1, &rest[1..]),
+    Some('+') => (1, &rest[1..]),
+    _ => (1, rest.as_str())
+  };
+  let parts: Vec<&str> = if body.contains(':') {
+    body.split(':').collect()
+  } else {
+    match body.len() {
+      1 | 2 => vec![body],
+      3 => vec![&body[0..1], &body[1..3]],
+      4 => vec![&body[0..2], &body[2..4]],
+      6 => vec![&body[0..2], &body[2..4], &body[4..6]],
+      _ => return None
+    }
+  };
+  if parts.len() > 3 || parts.iter().any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_digit())) {
+    return None;
+  }
+  let values: Vec<i32> = parts.iter().filter_map(|p| p.parse::<i32>().ok()).collect();
+  let (hours, minutes, seconds) = (values[0], values.get(1).copied().unwrap_or(0), values.get(2).copied().unwrap_or(0));
+  if hours > 15 || minutes > 59 || seconds > 59 {
+    return None;
+  }
+  Some(sign * (hours * 3600 + minutes * 60 + seconds))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ts(utc: &str) -> i64 {
+    NaiveDateTime::parse_from_str(utc, "%Y-%m-%d %H:%M:%S").unwrap().timestamp()
+  }
+
+  #[test]
+  fn leap_seconds_apply_from_the_first_of_the_month() {
+    assert_eq!(gps_leap_seconds(GPS_EPOCH_UNIX), 0);
+    assert_eq!(gps_leap_seconds(ts("1981-06-30 23:59:59")), 0);
+    assert_eq!(gps_leap_seconds(ts("1981-07-01 00:00:00")), 1);
+    assert_eq!(gps_leap_seconds(ts("1999-08-21 23:59:47")), 13);
+    assert_eq!(gps_leap_seconds(ts("2016-12-31 23:59:59")), 17);
+    assert_eq!(gps_leap_seconds(ts("2017-01-01 00:00:00")), 18);
+  }
+
+  #[test]
+  fn gps_weeks_roll_over_at_known_utc_times() {
+    assert_eq!(unixtime_to_gps_time(GPS_EPOCH_UNIX), (0, 0));
+    // the 10-bit week counter rolled over while GPS time led UTC by 13 and then 18 seconds
+    let rollovers = [(1024, "1999-08-21 23:59:47"), (2048, "2019-04-06 23:59:42")];
+    for (week, utc) in rollovers {
+      assert_eq!(unixtime_to_gps_time(ts(utc)), (week, 0));
+      assert_eq!(gps_time_to_unixtime(week, 0f64), ts(utc));
+    }
+    assert_eq!(unixtime_to_gps_time(ts("2000-01-01 00:00:00")), (1042, 518413));
+    assert_eq!(gps_time_to_unixtime(1042, 518413f64), ts("2000-01-01 00:00:00"));
+  }
+
+  #[test]
+  fn modified_julian_dates() {
+    assert_eq!(mjd_to_unixtime(51544f64), ts("2000-01-01 00:00:00"));
+    assert_eq!(unixtime_to_mjd(ts("2000-01-01 12:00:00")), 51544.5);
+    assert_eq!(unixtime_to_mjd(0), MJD_UNIX_EPOCH);
+  }
+
+  #[test]
+  fn excel_serials_skip_the_phantom_leap_day() {
+    assert_eq!(unixtime_to_utc(excel_serial_to_unixtime(1f64)), "1900-01-01T00:00:00");
+    assert_eq!(unixtime_to_utc(excel_serial_to_unixtime(59f64)), "1900-02-28T00:00:00");
+    // serial 60 is 29 February 1900, which did not exist, so it is read as 1 March like serial 61
+    assert_eq!(unixtime_to_utc(excel_serial_to_unixtime(60f64)), "1900-03-01T00:00:00");
+    assert_eq!(unixtime_to_utc(excel_serial_to_unixtime(61f64)), "1900-03-01T00:00:00");
+    assert_eq!(unixtime_to_excel_serial(ts("1900-02-28 00:00:00")), 59f64);
+    assert_eq!(unixtime_to_excel_serial(ts("1900-03-01 00:00:00")), 61f64);
+    assert_eq!(unixtime_to_excel_serial(ts("2024-01-01 18:00:00")), 45292.75);
+    assert_eq!(excel_serial_to_unixtime(EXCEL_UNIX_EPOCH), 0);
+  }
+
+  #[test]
+  fn ntp_filetime_and_mac_epochs() {
+    assert_eq!(unixtime_to_ntp(0), 2_208_988_800);
+    // end of the first NTP era
+    assert_eq!(unixtime_to_utc(ntp_to_unixtime(4_294_967_296f64)), "2036-02-07T06:28:16");
+    assert_eq!(unixtime_to_filetime(ts("2024-01-01 00:00:00")), 133_485_408_000_000_000);
+    assert_eq!(filetime_to_unixtime(133_485_408_009_999_999), ts("2024-01-01 00:00:00"));
+    assert_eq!(unixtime_to_utc(filetime_to_unixtime(0)), "1601-01-01T00:00:00");
+    assert_eq!(unixtime_to_utc(mac_absolute_to_unixtime(0f64)), "2001-01-01T00:00:00");
+    assert_eq!(unixtime_to_mac_absolute(ts("2024-01-01 00:00:00")), 725_760_000);
+  }
+
+  #[test]
+  fn utc_offsets_in_common_notations() {
+    assert_eq!(parse_utc_offset("+05:45"), Some(20700));
+    assert_eq!(parse_utc_offset("-0330"), Some(-12600));
+    assert_eq!(parse_utc_offset("UTC+5:45"), Some(20700));
+    assert_eq!(parse_utc_offset("GMT-3"), Some(-10800));
+    assert_eq!(parse_utc_offset(" 530"), Some(19800));
+    assert_eq!(parse_utc_offset("Z"), Some(0));
+    assert_eq!(parse_utc_offset("+16"), None);
+  }
+}
@@ -0,0 +1,49 @@
+use serde::{Serialize, Deserialize};
+use super::date_conv::*;
+
+/*
+* The same UTC instant expressed in alternative time scales
+*/
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EpochTimes {
+  #[serde(rename="gpsWeek")]
+  pub gps_week: i64,
+  #[serde(rename="gpsSeconds")]
+  pub gps_seconds: i64,
+  pub mjd: f64,
+  pub excel: f64,
+  pub ntp: i64,
+  pub filetime: i64,
+  pub mac: i64,
+}
+
+impl EpochTimes {
+  pub fn new(ts: i64) -> Self {
+    let (gps_week, gps_seconds) = unixtime_to_gps_time(ts);
+    EpochTimes {
+      gps_week,
+      gps_seconds,
+      mjd: unixtime_to_mjd(ts),
+      excel: unixtime_to_excel_serial(ts),
+      ntp: unixtime_to_ntp(ts),
+      filetime: unixtime_to_filetime(ts),
+      mac: unixtime_to_mac_absolute(ts),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn start_of_2000_in_all_scales() {
+    let epochs = EpochTimes::new(946_684_800);
+    assert_eq!((epochs.gps_week, epochs.gps_seconds), (1042, 518413));
+    assert_eq!(epochs.mjd, 51544f64);
+    assert_eq!(epochs.excel, 36526f64);
+    assert_eq!(epochs.ntp, 3_155_673_600);
+    assert_eq!(epochs.filetime, 125_911_584_000_000_000);
+    assert_eq!(epochs.mac, -31_622_400);
+  }
+}
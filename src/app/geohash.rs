@@ -0,0 +1,55 @@
+const GEOHASH_BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/*
+* Standard geohash of a point. Each extra character narrows the cell, e.g. about 1.2 x 0.6 km at precision 6.
+*/
+pub fn encode_geohash(lat: f64, lng: f64, precision: usize) -> String {
+  let (mut lat_range, mut lng_range) = ((-90f64, 90f64), (-180f64, 180f64));
+  let mut hash = String::with_capacity(precision);
+  let mut even = true;
+  let mut bits = 0u8;
+  let mut bit_count = 0;
+  while hash.len() < precision {
+    let (range, value) = if even { (&mut lng_range, lng) } else { (&mut lat_range, lat) };
+    let mid = (range.0 + range.1) / 2f64;
+    bits <<= 1;
+    if value >= mid {
+      bits |= 1;
+      range.0 = mid;
+    } else {
+      range.1 = mid;
+    }
+    even = !even;
+    bit_count += 1;
+    if bit_count == 5 {
+      hash.push(GEOHASH_BASE32[bits as usize] as char);
+      bits = 0;
+      bit_count = 0;
+    }
+  }
+  hash
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn known_geohashes() {
+    assert_eq!(encode_geohash(57.64911, 10.40744, 11), "u4pruydqqvj");
+    assert_eq!(encode_geohash(51.5074, -0.1278, 6), "gcpvj0");
+    assert_eq!(encode_geohash(-33.8688, 151.2093, 5), "r3gx2");
+    assert_eq!(encode_geohash(0.0, 0.0, 4), "s000");
+    assert_eq!(encode_geohash(-90.0, -180.0, 3), "000");
+  }
+
+  #[test]
+  fn longer_hashes_extend_shorter_ones() {
+    let hash = encode_geohash(40.7128, -74.0060, 12);
+    assert_eq!(hash.len(), 12);
+    for precision in 1..12 {
+      assert_eq!(encode_geohash(40.7128, -74.0060, precision), hash[..precision]);
+    }
+    assert_eq!(encode_geohash(40.7128, -74.0060, 0), "");
+  }
+}
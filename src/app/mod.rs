@@ -2,4 +2,13 @@ pub mod date_conv;
 pub mod json_extract;
 pub mod coords;
 pub mod cached_http_client;
-pub mod weekday_code;
\ No newline at end of file
+pub mod weekday_code;
+pub mod epoch_times;
+pub mod relative_dates;
+pub mod recurrence;
+pub mod transition_rules;
+pub mod vtimezone;
+pub mod posix_tz;
+pub mod tzif;
+pub mod app_cache;
+pub mod geohash;
@@ -0,0 +1,110 @@
+use super::transition_rules::*;
+
+/*
+* POSIX abbreviations must be 3 or more letters, otherwise they are quoted in angle brackets, e.g. <+0545>
+*/
+fn posix_abbreviation(abbreviation: &str, offset: i64) -> String {
+  let abbr = display_abbreviation(abbreviation, offset);
+  if abbr.len() >= 3 && abbr.chars().all(|c| c.is_ascii_alphabetic()) {
+    abbr
+  } else {
+    format!("<{}>", abbr)
+  }
+}
+
+/*
+* POSIX offsets are west of Greenwich, so the sign is inverted, e.g. -1 for UTC+01:00
+*/
+fn posix_offset(offset: i64) -> String {
+  let sign = if offset > 0 { "-" } else { "" };
+  format_posix_time(offset.abs(), sign)
+}
+
+fn format_posix_time(secs: i64, sign: &str) -> String {
+  let (hours, minutes, seconds) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+  if seconds > 0 {
+    format!("{}{}:{:02}:{:02}", sign, hours, minutes, seconds)
+  } else if minutes > 0 {
+    format!("{}{}:{:02}", sign, hours, minutes)
+  } else {
+    format!("{}{}", sign, hours)
+  }
+}
+
+fn posix_rule(rule: &TransitionRule) -> String {
+  let time = if rule.seconds != 7200 { format!("/{}", format_posix_time(rule.seconds, "")) } else { "".to_string() };
+  format!("M{}.{}.{}{}", rule.month, rule.posix_week(), rule.weekday.num_days_from_sunday(), time)
+}
+
+/*
+* Build a POSIX TZ string describing the current rules of a zone, e.g. CET-1CEST,M3.5.0,M10.5.0/3.
+* Zones without an annual daylight saving rule at the horizon of the data are described by their last offset only.
+*/
+pub fn build_posix_tz(transitions: &[ZoneTransition], horizon_ts: i64) -> Option<String> {
+  let last = transitions.last()?;
+  let runs = group_transition_runs(transitions, horizon_ts);
+  let open_runs: Vec<&TransitionRun> = runs.iter().filter(|run| run.open_ended).collect();
+  let dst_run = open_runs.iter().find(|run| run.transitions.first().map(|t| t.dst).unwrap_or(false));
+  let std_run = open_runs.iter().find(|run| run.transitions.first().map(|t| !t.dst).unwrap_or(false));
+  if let (Some(dst_run), Some(std_run)) = (dst_run, std_run) {
+    if let (Some(dst), Some(std), Some(dst_rule), Some(std_rule)) = (dst_run.transitions.first(), std_run.transitions.first(), dst_run.rule, std_run.rule) {
+      let dst_offset = if dst.offset_to - std.offset_to != 3600 { posix_offset(dst.offset_to) } else { "".to_string() };
+      return Some(format!("{}{}{}{},{},{}",
+        posix_abbreviation(&std.abbreviation, std.offset_to),
+        posix_offset(std.offset_to),
+        posix_abbreviation(&dst.abbreviation, dst.offset_to),
+        dst_offset,
+        posix_rule(&dst_rule),
+        posix_rule(&std_rule)
+      ));
+    }
+  }
+  Some(format!("{}{}", posix_abbreviation(&last.abbreviation, last.offset_to), posix_offset(last.offset_to)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::transition_rules::tests::{transition, horizon, london_transitions, mexico_city_transitions};
+
+  #[test]
+  fn default_dst_offset_and_time_are_omitted() {
+    assert_eq!(build_posix_tz(&london_transitions(), horizon()), Some("GMT0BST,M3.5.0/1,M10.5.0".to_string()));
+  }
+
+  #[test]
+  fn non_default_times_and_dst_offsets_are_written() {
+    let years = [("2022-03-27", "2022-10-30"), ("2023-03-26", "2023-10-29")];
+    let berlin: Vec<ZoneTransition> = years.iter().flat_map(|(start, end)| vec![
+      transition(&format!("{} 01:00", start), 3600, 7200, "CEST", true),
+      transition(&format!("{} 01:00", end), 7200, 3600, "CET", false),
+    ]).collect();
+    assert_eq!(build_posix_tz(&berlin, horizon()), Some("CET-1CEST,M3.5.0,M10.5.0/3".to_string()));
+    // Lord Howe Island moves by 30 minutes, on the first Sundays of October and April
+    let lord_howe = vec![
+      transition("2022-04-02 15:00", 39600, 37800, "", false),
+      transition("2022-10-01 15:30", 37800, 39600, "", true),
+      transition("2023-04-01 15:00", 39600, 37800, "", false),
+      transition("2023-09-30 15:30", 37800, 39600, "", true),
+    ];
+    assert_eq!(build_posix_tz(&lord_howe, horizon()), Some("<+1030>-10:30<+11>-11,M10.1.0,M4.1.0".to_string()));
+  }
+
+  #[test]
+  fn zones_without_current_dst_use_the_last_offset() {
+    let kathmandu = vec![transition("1985-12-31 18:30", 19800, 20700, "", false)];
+    assert_eq!(build_posix_tz(&kathmandu, horizon()), Some("<+0545>-5:45".to_string()));
+    let moscow = vec![
+      transition("2010-10-30 23:00", 14400, 10800, "MSK", false),
+      transition("2011-03-26 23:00", 10800, 14400, "MSK", false),
+      transition("2014-10-25 22:00", 14400, 10800, "MSK", false),
+    ];
+    assert_eq!(build_posix_tz(&moscow, horizon()), Some("MSK-3".to_string()));
+    assert_eq!(build_posix_tz(&[], horizon()), None);
+  }
+
+  #[test]
+  fn abolished_dst_rules_are_not_carried_forward() {
+    assert_eq!(build_posix_tz(&mexico_city_transitions(), horizon()), Some("CST6".to_string()));
+  }
+}
@@ -0,0 +1,269 @@
+use chrono::{NaiveDate, NaiveDateTime, Datelike, Duration, Weekday};
+use julian_day_converter::iso_fuzzy_string_to_datetime;
+
+pub const MAX_RECURRENCE_OCCURRENCES: usize = 1000;
+
+// largest UTC offset in seconds, bounding local times before a UTC end time
+const MAX_UTC_OFFSET_SECS: i64 = 14 * 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecurrenceFrequency {
+  Daily,
+  Weekly,
+  Monthly,
+  Yearly,
+}
+
+/*
+* Subset of the iCalendar RRULE syntax, e.g. FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE;COUNT=10
+* or FREQ=MONTHLY;BYDAY=-1SU;UNTIL=2025-12-31. Occurrences are expanded in local wall-clock time.
+*/
+#[derive(Debug, Clone)]
+pub struct RecurrenceRule {
+  pub frequency: RecurrenceFrequency,
+  pub interval: u32,
+  pub count: Option<usize>,
+  pub until: Option<NaiveDateTime>,
+  // UNTIL with the Z suffix is a UTC time as in RFC 5545, otherwise local wall-clock time
+  pub until_utc: bool,
+  // weekday with an optional ordinal within the month, e.g. -1SU for the last Sunday
+  pub by_day: Vec<(Option<i32>, Weekday)>,
+  pub by_month_day: Vec<i32>,
+}
+
+fn match_weekday_code(code: &str) -> Option<Weekday> {
+  match code {
+    "MO" => Some(Weekday::Mon),
+    "TU" => Some(Weekday::Tue),
+    "WE" => Some(Weekday::Wed),
+    "TH" => Some(Weekday::Thu),
+    "FR" => Some(Weekday::Fri),
+    "SA" => Some(Weekday::Sat),
+    "SU" => Some(Weekday::Sun),
+    _ => None
+  }
+}
+
+fn parse_by_day(value: &str) -> Option<(Option<i32>, Weekday)> {
+  let value = value.trim();
+  if value.len() < 2 {
+    return None;
+  }
+  let (ordinal_str, code) = value.split_at(value.len() - 2);
+  let weekday = match_weekday_code(code)?;
+  let ordinal = if ordinal_str.is_empty() { None } else { Some(ordinal_str.trim_start_matches('+').parse::<i32>().ok()?) };
+  Some((ordinal, weekday))
+}
+
+/*
+* RRULE UNTIL values may be in basic (20251231T090000Z) or extended ISO format, flagged as UTC with a Z suffix
+*/
+fn parse_until(value: &str) -> Option<(NaiveDateTime, bool)> {
+  let is_utc = value.ends_with('Z');
+  let clean = value.trim_end_matches('Z');
+  let dt = if clean.len() >= 8 && !clean.contains('-') {
+    let date = NaiveDate::parse_from_str(&clean[0..8], "%Y%m%d").ok()?;
+    let time = if clean.len() >= 15 { chrono::NaiveTime::parse_from_str(&clean[9..15], "%H%M%S").ok()? } else { chrono::NaiveTime::from_hms_opt(23, 59, 59)? };
+    date.and_time(time)
+  } else {
+    iso_fuzzy_string_to_datetime(clean).ok()?
+  };
+  Some((dt, is_utc))
+}
+
+/*
+* Nth weekday of a month, where negative ordinals count back from the end of the month
+*/
+fn nth_weekday_of_month(year: i32, month: u32, weekday: Weekday, ordinal: i32) -> Option<NaiveDate> {
+  let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+  let days_in_month = days_in_month(year, month);
+  let matches: Vec<NaiveDate> = (0..days_in_month).map(|d| first + Duration::days(d as i64)).filter(|d| d.weekday() == weekday).collect();
+  let index = if ordinal > 0 { ordinal - 1 } else { matches.len() as i32 + ordinal };
+  if index >= 0 { matches.get(index as usize).copied() } else { None }
+}
+
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+  let (next_year, next_month) = if month >= 12 { (year + 1, 1) } else { (year, month + 1) };
+  match (NaiveDate::from_ymd_opt(next_year, next_month, 1), NaiveDate::from_ymd_opt(year, month, 1)) {
+    (Some(next), Some(curr)) => (next - curr).num_days() as u32,
+    _ => 30
+  }
+}
+
+impl RecurrenceRule {
+  pub fn parse(rule: &str) -> Option<RecurrenceRule> {
+    let mut frequency: Option<RecurrenceFrequency> = None;
+    let mut interval = 1u32;
+    let mut count: Option<usize> = None;
+    let mut until: Option<NaiveDateTime> = None;
+    let mut until_utc = false;
+    let mut by_day: Vec<(Option<i32>, Weekday)> = Vec::new();
+    let mut by_month_day: Vec<i32> = Vec::new();
+    let clean = rule.trim().trim_start_matches("RRULE:").to_uppercase();
+    for part in clean.split(';') {
+      let mut pair = part.splitn(2, '=');
+      let key = pair.next().unwrap_or("").trim();
+      let value = pair.next().unwrap_or("").trim();
+      match key {
+        "FREQ" => {
+          frequency = match value {
+            "DAILY" => Some(RecurrenceFrequency::Daily),
+            "WEEKLY" => Some(RecurrenceFrequency::Weekly),
+            "MONTHLY" => Some(RecurrenceFrequency::Monthly),
+            "YEARLY" => Some(RecurrenceFrequency::Yearly),
+            _ => None
+          };
+        },
+        "INTERVAL" => interval = value.parse::<u32>().unwrap_or(1).max(1),
+        "COUNT" => count = value.parse::<usize>().ok(),
+        "UNTIL" => if let Some((dt, is_utc)) = parse_until(value) {
+          until = Some(dt);
+          until_utc = is_utc;
+        },
+        "BYDAY" => by_day = value.split(',').filter_map(parse_by_day).collect(),
+        "BYMONTHDAY" => by_month_day = value.split(',').filter_map(|v| v.trim().parse::<i32>().ok()).filter(|d| *d != 0 && d.abs() <= 31).collect(),
+        _ => ()
+      }
+    }
+    frequency.map(|frequency| RecurrenceRule { frequency, interval, count, until, until_utc, by_day, by_month_day })
+  }
+
+  fn dates_in_month(&self, year: i32, month: u32, start: &NaiveDateTime) -> Vec<NaiveDate> {
+    let num_days = days_in_month(year, month) as i32;
+    let mut dates: Vec<NaiveDate> = if !self.by_day.is_empty() {
+      let mut items: Vec<NaiveDate> = Vec::new();
+      for (ordinal, weekday) in self.by_day.iter() {
+        match ordinal {
+          Some(ord) => {
+            if let Some(d) = nth_weekday_of_month(year, month, *weekday, *ord) {
+              items.push(d);
+            }
+          },
+          None => {
+            for ord in 1..=5 {
+              if let Some(d) = nth_weekday_of_month(year, month, *weekday, ord) {
+                items.push(d);
+              }
+            }
+          }
+        }
+      }
+      items
+    } else {
+      let month_days = if self.by_month_day.is_empty() { vec![start.day() as i32] } else { self.by_month_day.clone() };
+      month_days.into_iter().filter_map(|md| {
+        let day = if md < 0 { num_days + md + 1 } else { md };
+        // invalid dates such as 30 February are skipped as in RFC 5545
+        if day > 0 && day <= num_days { NaiveDate::from_ymd_opt(year, month, day as u32) } else { None }
+      }).collect()
+    };
+    dates.sort();
+    dates.dedup();
+    dates
+  }
+
+  fn dates_in_period(&self, index: i64, start: &NaiveDateTime) -> Vec<NaiveDate> {
+    let step = index * self.interval as i64;
+    match self.frequency {
+      RecurrenceFrequency::Daily => vec![start.date() + Duration::days(step)],
+      RecurrenceFrequency::Weekly => {
+        let week_start = start.date() - Duration::days(start.weekday().num_days_from_monday() as i64) + Duration::days(step * 7);
+        let mut weekdays: Vec<Weekday> = self.by_day.iter().map(|(_, wd)| *wd).collect();
+        if weekdays.is_empty() {
+          weekdays.push(start.weekday());
+        }
+        let mut dates: Vec<NaiveDate> = weekdays.into_iter().map(|wd| week_start + Duration::days(wd.num_days_from_monday() as i64)).collect();
+        dates.sort();
+        dates.dedup();
+        dates
+      },
+      RecurrenceFrequency::Monthly => {
+        let total = start.year() as i64 * 12 + start.month0() as i64 + step;
+        self.dates_in_month(total.div_euclid(12) as i32, total.rem_euclid(12) as u32 + 1, start)
+      },
+      RecurrenceFrequency::Yearly => {
+        let year = start.year() + step as i32;
+        if self.by_day.is_empty() && self.by_month_day.is_empty() {
+          NaiveDate::from_ymd_opt(year, start.month(), start.day()).into_iter().collect()
+        } else {
+          self.dates_in_month(year, start.month(), start)
+        }
+      },
+    }
+  }
+
+  /*
+  * Whether the UTC instant of an occurrence is after a UTC end time. Local end times are applied when expanding.
+  */
+  pub fn is_after_utc_until(&self, utc_ts: i64) -> bool {
+    match self.until {
+      Some(until) if self.until_utc => utc_ts > until.timestamp(),
+      _ => false
+    }
+  }
+
+  /*
+  * Expand local wall-clock occurrences from the start date-time, which counts as the first occurrence if it matches the rule.
+  * With a UTC end time, local times up to the largest UTC offset later are kept for the caller to check via is_after_utc_until.
+  */
+  pub fn expand(&self, start: NaiveDateTime, max: usize) -> Vec<NaiveDateTime> {
+    let limit = self.count.unwrap_or(max).min(max).min(MAX_RECURRENCE_OCCURRENCES);
+    let mut occurrences: Vec<NaiveDateTime> = Vec::new();
+    let max_periods = (MAX_RECURRENCE_OCCURRENCES * 12) as i64;
+    let mut index = 0i64;
+    while occurrences.len() < limit && index < max_periods {
+      for date in self.dates_in_period(index, &start) {
+        let dt = date.and_time(start.time());
+        if dt < start {
+          continue;
+        }
+        if let Some(until) = self.until {
+          let local_until = if self.until_utc { until + Duration::seconds(MAX_UTC_OFFSET_SECS) } else { until };
+          if dt > local_until {
+            return occurrences;
+          }
+        }
+        if occurrences.len() < limit {
+          occurrences.push(dt);
+        }
+      }
+      index += 1;
+    }
+    occurrences
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn local(s: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+  }
+
+  #[test]
+  fn until_with_z_suffix_is_utc() {
+    let rule = RecurrenceRule::parse("FREQ=DAILY;UNTIL=20251231T090000Z").unwrap();
+    assert_eq!(rule.until, Some(local("2025-12-31 09:00:00")));
+    assert!(rule.until_utc);
+    assert!(!rule.is_after_utc_until(local("2025-12-31 09:00:00").timestamp()));
+    assert!(rule.is_after_utc_until(local("2025-12-31 09:00:01").timestamp()));
+  }
+
+  #[test]
+  fn until_without_suffix_is_local() {
+    let rule = RecurrenceRule::parse("FREQ=DAILY;UNTIL=20250105").unwrap();
+    assert_eq!(rule.until, Some(local("2025-01-05 23:59:59")));
+    assert!(!rule.until_utc);
+    let occurrences = rule.expand(local("2025-01-01 09:00:00"), 100);
+    assert_eq!(occurrences.len(), 5);
+    assert_eq!(occurrences.last(), Some(&local("2025-01-05 09:00:00")));
+  }
+
+  #[test]
+  fn utc_until_keeps_local_times_within_the_largest_offset() {
+    let rule = RecurrenceRule::parse("FREQ=DAILY;UNTIL=20250103T000000Z").unwrap();
+    let occurrences = rule.expand(local("2025-01-01 09:00:00"), 100);
+    assert_eq!(occurrences.last(), Some(&local("2025-01-03 09:00:00")));
+  }
+}
@@ -0,0 +1,432 @@
+use serde::{Serialize, Deserialize};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Datelike, Duration, Weekday};
+use julian_day_converter::iso_fuzzy_string_to_datetime;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelativeUnit {
+  Second,
+  Minute,
+  Hour,
+  Day,
+  Week,
+  Month,
+  Year,
+}
+
+impl RelativeUnit {
+  /*
+  * Unit from an abbreviation or name in the singular or plural, e.g. s, secs, min, hrs or weeks.
+  * Only one plural s is removed and only from keys longer than two letters, so ms is not read as minutes.
+  */
+  pub fn from_key(key: &str) -> Option<RelativeUnit> {
+    let singular = if key.len() > 2 && key.ends_with('s') { &key[..key.len() - 1] } else { key };
+    match singular {
+      "s" | "sec" | "second" => Some(RelativeUnit::Second),
+      "m" | "min" | "minute" => Some(RelativeUnit::Minute),
+      "h" | "hr" | "hour" => Some(RelativeUnit::Hour),
+      "d" | "day" => Some(RelativeUnit::Day),
+      "w" | "wk" | "week" => Some(RelativeUnit::Week),
+      "mo" | "mon" | "month" => Some(RelativeUnit::Month),
+      "y" | "yr" | "year" => Some(RelativeUnit::Year),
+      _ => None
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      RelativeUnit::Second => "second",
+      RelativeUnit::Minute => "minute",
+      RelativeUnit::Hour => "hour",
+      RelativeUnit::Day => "day",
+      RelativeUnit::Week => "week",
+      RelativeUnit::Month => "month",
+      RelativeUnit::Year => "year",
+    }
+  }
+
+  /*
+  * Seconds, minutes and hours shift the resolved instant, while longer units shift the wall-clock time,
+  * so that "tomorrow 09:00" or "+1 day" keep the same local time across DST transitions
+  */
+  pub fn is_calendar(&self) -> bool {
+    matches!(self, RelativeUnit::Day | RelativeUnit::Week | RelativeUnit::Month | RelativeUnit::Year)
+  }
+
+  pub fn seconds(&self) -> i64 {
+    match self {
+      RelativeUnit::Second => 1,
+      RelativeUnit::Minute => 60,
+      RelativeUnit::Hour => 3600,
+      RelativeUnit::Day => 86_400,
+      RelativeUnit::Week => 604_800,
+      _ => 0,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelativeBase {
+  Now,
+  Today,
+  Date(NaiveDateTime),
+  // direction: 1 = next, -1 = last, 0 = on or after today
+  Weekday(Weekday, i8),
+}
+
+/*
+* Parsed relative or natural-language date expression such as now+3h, next monday 09:00 or 2024-03-31 02:30 + 1 day
+*/
+#[derive(Debug, Clone)]
+pub struct RelativeDateTime {
+  pub expression: String,
+  pub base: RelativeBase,
+  pub time: Option<NaiveTime>,
+  pub shifts: Vec<(i64, RelativeUnit)>,
+}
+
+/*
+* Summary of how a relative expression was interpreted, returned with the response
+*/
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelativeInterpretation {
+  pub expression: String,
+  pub interpretation: String,
+  pub local: bool,
+}
+
+fn match_weekday(key: &str) -> Option<Weekday> {
+  match key {
+    "mon" | "monday" => Some(Weekday::Mon),
+    "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+    "wed" | "wednesday" => Some(Weekday::Wed),
+    "thu" | "thur" | "thurs" | "thursday" => Some(Weekday::Thu),
+    "fri" | "friday" => Some(Weekday::Fri),
+    "sat" | "saturday" => Some(Weekday::Sat),
+    "sun" | "sunday" => Some(Weekday::Sun),
+    _ => None
+  }
+}
+
+fn weekday_name(wd: Weekday) -> &'static str {
+  match wd {
+    Weekday::Mon => "Monday",
+    Weekday::Tue => "Tuesday",
+    Weekday::Wed => "Wednesday",
+    Weekday::Thu => "Thursday",
+    Weekday::Fri => "Friday",
+    Weekday::Sat => "Saturday",
+    Weekday::Sun => "Sunday",
+  }
+}
+
+/*
+* Match times of day as HH:MM(:SS), 9am, 9:30pm, noon or midnight
+*/
+fn parse_time_of_day(token: &str) -> Option<NaiveTime> {
+  match token {
+    "noon" | "midday" => return NaiveTime::from_hms_opt(12, 0, 0),
+    "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+    _ => ()
+  }
+  let (clock, meridiem) = if let Some(stripped) = token.strip_suffix("am") {
+    (stripped, Some(false))
+  } else if let Some(stripped) = token.strip_suffix("pm") {
+    (stripped, Some(true))
+  } else {
+    (token, None)
+  };
+  if meridiem.is_none() && !clock.contains(':') {
+    return None;
+  }
+  let parts: Vec<u32> = clock.split(':').map(|p| p.parse::<u32>()).collect::<Result<Vec<u32>, _>>().ok()?;
+  let mut hours = *parts.first()?;
+  let minutes = *parts.get(1).unwrap_or(&0);
+  let seconds = *parts.get(2).unwrap_or(&0);
+  if let Some(pm) = meridiem {
+    if !(1..=12).contains(&hours) {
+      return None;
+    }
+    hours = match (pm, hours) {
+      (false, 12) => 0,
+      (true, 12) => 12,
+      (true, h) => h + 12,
+      (false, h) => h,
+    };
+  }
+  NaiveTime::from_hms_opt(hours, minutes, seconds)
+}
+
+/*
+* Split a compact shift such as 3h, 10min or 2weeks into its number and unit
+*/
+fn parse_compact_shift(token: &str) -> Option<(i64, RelativeUnit)> {
+  let split_index = token.find(|c: char| !c.is_ascii_digit())?;
+  if split_index < 1 {
+    return None;
+  }
+  let (num_str, unit_str) = token.split_at(split_index);
+  let num = num_str.parse::<i64>().ok()?;
+  RelativeUnit::from_key(unit_str).map(|unit| (num, unit))
+}
+
+/*
+* Put spaces around + and - signs that introduce shifts, but not around hyphens within ISO dates.
+* A hyphen directly after a digit joins date parts, while one after a space as in 2024-03-31 -1d is a minus sign.
+*/
+fn separate_operators(expr: &str) -> String {
+  let mut output = String::new();
+  let mut prev: Option<char> = None;
+  for c in expr.chars() {
+    let is_operator = c == '+' || (c == '-' && !prev.map(|p| p.is_ascii_digit()).unwrap_or(false));
+    if is_operator {
+      output.push(' ');
+      output.push(c);
+      output.push(' ');
+    } else {
+      output.push(c);
+    }
+    prev = Some(c);
+  }
+  output
+}
+
+fn add_months(dt: NaiveDateTime, months: i64) -> NaiveDateTime {
+  let total = dt.year() as i64 * 12 + dt.month0() as i64 + months;
+  let year = total.div_euclid(12) as i32;
+  let month = total.rem_euclid(12) as u32 + 1;
+  let mut day = dt.day();
+  while day > 28 && NaiveDate::from_ymd_opt(year, month, day).is_none() {
+    day -= 1;
+  }
+  match NaiveDate::from_ymd_opt(year, month, day) {
+    Some(date) => date.and_time(dt.time()),
+    None => dt
+  }
+}
+
+impl RelativeDateTime {
+
+  /*
+  * Parse an expression. Plain ISO dates without any relative component are left to the standard date parser
+  */
+  pub fn parse(expr: &str) -> Option<RelativeDateTime> {
+    let expression = expr.trim().to_string();
+    let normalized = separate_operators(&expression.to_lowercase().replace(',', " "));
+    let tokens: Vec<&str> = normalized.split_whitespace().filter(|t| *t != "at" && *t != "on").collect();
+    if tokens.is_empty() {
+      return None;
+    }
+    let mut base: Option<RelativeBase> = None;
+    let mut time: Option<NaiveTime> = None;
+    let mut shifts: Vec<(i64, RelativeUnit)> = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+      let token = tokens[index];
+      let next = tokens.get(index + 1).copied();
+      match token {
+        "now" => base = Some(RelativeBase::Now),
+        "today" => base = Some(RelativeBase::Today),
+        "tomorrow" => {
+          base = Some(RelativeBase::Today);
+          shifts.push((1, RelativeUnit::Day));
+        },
+        "yesterday" => {
+          base = Some(RelativeBase::Today);
+          shifts.push((-1, RelativeUnit::Day));
+        },
+        "next" | "last" | "this" => {
+          let direction = match token { "next" => 1, "last" => -1, _ => 0 };
+          if let Some(wd) = next.and_then(match_weekday) {
+            base = Some(RelativeBase::Weekday(wd, direction));
+          } else if let Some(unit) = next.and_then(RelativeUnit::from_key) {
+            if direction != 0 {
+              shifts.push((direction as i64, unit));
+            }
+          } else {
+            return None;
+          }
+          index += 1;
+        },
+        "+" | "-" | "in" => {
+          let sign = if token == "-" { -1 } else { 1 };
+          let (num, unit, consumed) = if let Some((num, unit)) = next.and_then(parse_compact_shift) {
+            (num, unit, 1)
+          } else {
+            let num = next.and_then(|n| n.parse::<i64>().ok())?;
+            let unit = tokens.get(index + 2).and_then(|u| RelativeUnit::from_key(u))?;
+            (num, unit, 2)
+          };
+          shifts.push((sign * num, unit));
+          if base.is_none() {
+            base = Some(RelativeBase::Now);
+          }
+          index += consumed;
+        },
+        "ago" => {
+          if let Some(last) = shifts.last_mut() {
+            last.0 = 0 - last.0;
+          }
+        },
+        _ => {
+          if let Some(wd) = match_weekday(token) {
+            base = Some(RelativeBase::Weekday(wd, 0));
+          } else if let Some(tm) = parse_time_of_day(token) {
+            time = Some(tm);
+          } else if let Some(shift) = parse_compact_shift(token) {
+            shifts.push(shift);
+          } else if let Ok(num) = token.parse::<i64>() {
+            // e.g. 3 days ago
+            let unit = next.and_then(RelativeUnit::from_key)?;
+            shifts.push((num, unit));
+            index += 1;
+          } else if token.contains('-') {
+            let dt = iso_fuzzy_string_to_datetime(&token.replace('t', " ")).ok()?;
+            base = Some(RelativeBase::Date(dt));
+          } else {
+            return None;
+          }
+        }
+      }
+      index += 1;
+    }
+    let base = base.unwrap_or(if time.is_some() { RelativeBase::Today } else { RelativeBase::Now });
+    let is_plain_date = matches!(base, RelativeBase::Date(_)) && shifts.is_empty();
+    if is_plain_date {
+      None
+    } else {
+      Some(RelativeDateTime { expression, base, time, shifts })
+    }
+  }
+
+  /*
+  * True if the expression only shifts the current instant and needs no wall-clock resolution
+  */
+  pub fn is_instant(&self) -> bool {
+    self.base == RelativeBase::Now && self.time.is_none() && self.shifts.iter().all(|(_, unit)| !unit.is_calendar())
+  }
+
+  /*
+  * Seconds to add to the resolved instant
+  */
+  pub fn extra_seconds(&self) -> i64 {
+    self.shifts.iter().filter(|(_, unit)| !unit.is_calendar()).map(|(num, unit)| num * unit.seconds()).sum()
+  }
+
+  /*
+  * Wall-clock date-time in the frame of reference with the given UTC offset in seconds
+  */
+  pub fn wall_time(&self, now_ts: i64, offset: i64) -> NaiveDateTime {
+    let now = NaiveDateTime::from_timestamp(now_ts + offset, 0);
+    let midnight = now.date().and_hms(0, 0, 0);
+    let mut dt = match &self.base {
+      RelativeBase::Now => now,
+      RelativeBase::Today => midnight,
+      RelativeBase::Date(dt) => *dt,
+      RelativeBase::Weekday(wd, direction) => {
+        let today = now.weekday().num_days_from_monday() as i64;
+        let target = wd.num_days_from_monday() as i64;
+        let days = match direction {
+          1 => (target - today - 1).rem_euclid(7) + 1,
+          -1 => 0 - ((today - target - 1).rem_euclid(7) + 1),
+          _ => (target - today).rem_euclid(7),
+        };
+        midnight + Duration::days(days)
+      }
+    };
+    if let Some(tm) = self.time {
+      dt = dt.date().and_time(tm);
+    }
+    for (num, unit) in self.shifts.iter() {
+      dt = match unit {
+        RelativeUnit::Month => add_months(dt, *num),
+        RelativeUnit::Year => add_months(dt, *num * 12),
+        RelativeUnit::Day | RelativeUnit::Week => dt + Duration::seconds(num * unit.seconds()),
+        _ => dt
+      };
+    }
+    dt
+  }
+
+  /*
+  * Human-readable description of how the expression was understood
+  */
+  pub fn describe(&self, frame: &str) -> String {
+    let mut parts: Vec<String> = vec![match &self.base {
+      RelativeBase::Now => "now".to_string(),
+      RelativeBase::Today => "today".to_string(),
+      RelativeBase::Date(dt) => dt.format("%Y-%m-%d %H:%M:%S").to_string(),
+      RelativeBase::Weekday(wd, direction) => {
+        let prefix = match direction { 1 => "next ", -1 => "last ", _ => "" };
+        format!("{}{}", prefix, weekday_name(*wd))
+      }
+    }];
+    if let Some(tm) = self.time {
+      parts.push(format!("at {}", tm.format("%H:%M:%S")));
+    }
+    for (num, unit) in self.shifts.iter() {
+      let sign = if *num < 0 { "-" } else { "+" };
+      let plural = if num.abs() != 1 { "s" } else { "" };
+      parts.push(format!("{} {} {}{}", sign, num.abs(), unit.name(), plural));
+    }
+    format!("{} ({})", parts.join(" "), frame)
+  }
+
+  pub fn to_interpretation(&self, frame: &str, local: bool) -> RelativeInterpretation {
+    RelativeInterpretation {
+      expression: self.expression.clone(),
+      interpretation: self.describe(frame),
+      local,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn unit_keys_in_singular_and_plural() {
+    assert_eq!(RelativeUnit::from_key("s"), Some(RelativeUnit::Second));
+    assert_eq!(RelativeUnit::from_key("secs"), Some(RelativeUnit::Second));
+    assert_eq!(RelativeUnit::from_key("m"), Some(RelativeUnit::Minute));
+    assert_eq!(RelativeUnit::from_key("mins"), Some(RelativeUnit::Minute));
+    assert_eq!(RelativeUnit::from_key("hrs"), Some(RelativeUnit::Hour));
+    assert_eq!(RelativeUnit::from_key("days"), Some(RelativeUnit::Day));
+    assert_eq!(RelativeUnit::from_key("wks"), Some(RelativeUnit::Week));
+    assert_eq!(RelativeUnit::from_key("mos"), Some(RelativeUnit::Month));
+    assert_eq!(RelativeUnit::from_key("years"), Some(RelativeUnit::Year));
+    assert_eq!(RelativeUnit::from_key("ms"), None);
+    assert_eq!(RelativeUnit::from_key(""), None);
+    assert_eq!(RelativeUnit::from_key("ss"), None);
+  }
+
+  #[test]
+  fn hyphens_within_dates_are_not_operators() {
+    assert_eq!(separate_operators("2024-03-31"), "2024-03-31");
+    assert_eq!(separate_operators("2024-03-31 -1d"), "2024-03-31  - 1d");
+    assert_eq!(separate_operators("now-3h"), "now - 3h");
+  }
+
+  #[test]
+  fn date_with_negative_shift() {
+    let rel = RelativeDateTime::parse("2024-03-31 -1d").unwrap();
+    let date = NaiveDate::from_ymd_opt(2024, 3, 31).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    assert_eq!(rel.base, RelativeBase::Date(date));
+    assert_eq!(rel.shifts, vec![(-1, RelativeUnit::Day)]);
+    assert_eq!(rel.wall_time(0, 0), NaiveDate::from_ymd_opt(2024, 3, 30).unwrap().and_hms_opt(0, 0, 0).unwrap());
+  }
+
+  #[test]
+  fn time_with_negative_shift() {
+    let rel = RelativeDateTime::parse("14:30 -2 hours").unwrap();
+    assert_eq!(rel.time, NaiveTime::from_hms_opt(14, 30, 0));
+    assert_eq!(rel.shifts, vec![(-2, RelativeUnit::Hour)]);
+    assert_eq!(rel.extra_seconds(), -7200);
+  }
+
+  #[test]
+  fn plain_dates_are_left_to_the_date_parser() {
+    assert!(RelativeDateTime::parse("2024-03-31").is_none());
+    assert!(RelativeDateTime::parse("2024-03-31 + 1 day").is_some());
+  }
+}
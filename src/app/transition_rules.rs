@@ -0,0 +1,256 @@
+use chrono::{NaiveDateTime, Datelike, Timelike, Weekday};
+use super::recurrence::days_in_month;
+
+/*
+* Change of offset or abbreviation at a UTC timestamp, derived from consecutive time_zone rows
+*/
+#[derive(Debug, Clone)]
+pub struct ZoneTransition {
+  pub ts: i64,
+  pub offset_from: i64,
+  pub offset_to: i64,
+  pub abbreviation: String,
+  pub dst: bool,
+}
+
+/*
+* Annual rule such as "last Sunday in March at 02:00 local time", the latter expressed in the offset before the transition
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransitionRule {
+  pub month: u32,
+  pub weekday: Weekday,
+  // 1 to 4 for the nth weekday of the month, -1 for the last
+  pub ordinal: i32,
+  pub seconds: i64,
+}
+
+/*
+* Transitions of the same kind, either following the same annual rule in consecutive years or irregular
+*/
+#[derive(Debug, Clone)]
+pub struct TransitionRun {
+  pub transitions: Vec<ZoneTransition>,
+  pub rule: Option<TransitionRule>,
+  // the rule still applies in the final year of the available data
+  pub open_ended: bool,
+}
+
+pub fn weekday_code(weekday: Weekday) -> &'static str {
+  match weekday {
+    Weekday::Mon => "MO",
+    Weekday::Tue => "TU",
+    Weekday::Wed => "WE",
+    Weekday::Thu => "TH",
+    Weekday::Fri => "FR",
+    Weekday::Sat => "SA",
+    Weekday::Sun => "SU",
+  }
+}
+
+impl ZoneTransition {
+  pub fn local_start(&self) -> NaiveDateTime {
+    NaiveDateTime::from_timestamp(self.ts + self.offset_from, 0)
+  }
+
+  pub fn year(&self) -> i32 {
+    self.local_start().year()
+  }
+
+  pub fn rule(&self) -> TransitionRule {
+    TransitionRule::from_local(&self.local_start())
+  }
+
+  fn kind_key(&self) -> (i64, i64, String, bool) {
+    (self.offset_from, self.offset_to, self.abbreviation.clone(), self.dst)
+  }
+}
+
+impl TransitionRule {
+  pub fn from_local(dt: &NaiveDateTime) -> TransitionRule {
+    let day = dt.day();
+    let num_days = days_in_month(dt.year(), dt.month());
+    let ordinal = if day + 7 > num_days { -1 } else { ((day - 1) / 7 + 1) as i32 };
+    TransitionRule {
+      month: dt.month(),
+      weekday: dt.weekday(),
+      ordinal,
+      seconds: dt.num_seconds_from_midnight() as i64,
+    }
+  }
+
+  /*
+  * iCalendar BYDAY value, e.g. -1SU or 2SU
+  */
+  pub fn by_day(&self) -> String {
+    format!("{}{}", self.ordinal, weekday_code(self.weekday))
+  }
+
+  /*
+  * Week of the month as used in POSIX TZ Mm.w.d rules, where 5 means the last
+  */
+  pub fn posix_week(&self) -> i32 {
+    if self.ordinal < 0 { 5 } else { self.ordinal }
+  }
+}
+
+/*
+* Abbreviation for display in TZ strings and TZif files. Missing abbreviations are replaced by the numeric offset, e.g. +0545
+*/
+pub fn display_abbreviation(abbreviation: &str, offset: i64) -> String {
+  if abbreviation.trim().is_empty() {
+    let sign = if offset < 0 { "-" } else { "+" };
+    let abs = offset.abs();
+    let minutes = (abs % 3600) / 60;
+    if minutes > 0 {
+      format!("{}{:02}{:02}", sign, abs / 3600, minutes)
+    } else {
+      format!("{}{:02}", sign, abs / 3600)
+    }
+  } else {
+    abbreviation.trim().to_string()
+  }
+}
+
+/*
+* Group transitions by kind and split them into runs that follow the same annual rule in consecutive years.
+* Single transitions that match no annual pattern form runs without a rule. The horizon is the last transition
+* in the data as a whole, so rules of a zone that abolished daylight saving time are not treated as current.
+*/
+pub fn group_transition_runs(transitions: &[ZoneTransition], horizon_ts: i64) -> Vec<TransitionRun> {
+  let last_year = NaiveDateTime::from_timestamp(horizon_ts, 0).year();
+  let mut keys: Vec<(i64, i64, String, bool)> = Vec::new();
+  for tr in transitions {
+    let key = tr.kind_key();
+    if !keys.contains(&key) {
+      keys.push(key);
+    }
+  }
+  let mut runs: Vec<TransitionRun> = Vec::new();
+  for key in keys {
+    let mut current: Vec<ZoneTransition> = Vec::new();
+    for tr in transitions.iter().filter(|t| t.kind_key() == key) {
+      let continues = match current.last() {
+        Some(prev) => prev.rule() == tr.rule() && prev.year() + 1 == tr.year(),
+        None => true
+      };
+      if !continues {
+        runs.push(build_run(current, last_year));
+        current = Vec::new();
+      }
+      current.push(tr.clone());
+    }
+    if !current.is_empty() {
+      runs.push(build_run(current, last_year));
+    }
+  }
+  runs.sort_by_key(|run| run.transitions.first().map(|t| t.ts).unwrap_or(0));
+  runs
+}
+
+fn build_run(transitions: Vec<ZoneTransition>, last_year: i32) -> TransitionRun {
+  let is_regular = transitions.len() > 1;
+  let rule = if is_regular { transitions.first().map(|t| t.rule()) } else { None };
+  // rules changing late in the year may have their final transition in the year before the horizon
+  let open_ended = is_regular && transitions.last().map(|t| t.year() + 1 >= last_year).unwrap_or(false);
+  TransitionRun { transitions, rule, open_ended }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+
+  /*
+  * Timestamp of a UTC time written as YYYY-MM-DD HH:MM
+  */
+  pub(crate) fn utc_ts(utc: &str) -> i64 {
+    NaiveDateTime::parse_from_str(utc, "%Y-%m-%d %H:%M").unwrap().timestamp()
+  }
+
+  /*
+  * Last transition of the sample data as a whole
+  */
+  pub(crate) fn horizon() -> i64 {
+    utc_ts("2024-12-31 00:00")
+  }
+
+  pub(crate) fn transition(utc: &str, offset_from: i64, offset_to: i64, abbreviation: &str, dst: bool) -> ZoneTransition {
+    ZoneTransition { ts: utc_ts(utc), offset_from, offset_to, abbreviation: abbreviation.to_string(), dst }
+  }
+
+  /*
+  * Europe/London from 2021 to 2024, changing at 01:00 UTC on the last Sundays of March and October
+  */
+  pub(crate) fn london_transitions() -> Vec<ZoneTransition> {
+    [("2021-03-28", "2021-10-31"), ("2022-03-27", "2022-10-30"), ("2023-03-26", "2023-10-29"), ("2024-03-31", "2024-10-27")].iter().flat_map(|(start, end)| vec![
+      transition(&format!("{} 01:00", start), 0, 3600, "BST", true),
+      transition(&format!("{} 01:00", end), 3600, 0, "GMT", false),
+    ]).collect()
+  }
+
+  /*
+  * Europe/Moscow from 2008, when daylight saving time ended in 2010 before two permanent changes of offset
+  */
+  pub(crate) fn moscow_transitions() -> Vec<ZoneTransition> {
+    let mut moscow: Vec<ZoneTransition> = ["2008-03-29", "2009-03-28", "2010-03-27"].iter().map(|d| transition(&format!("{} 23:00", d), 10800, 14400, "MSD", true)).collect();
+    moscow.extend(["2008-10-25", "2009-10-24", "2010-10-30"].iter().map(|d| transition(&format!("{} 23:00", d), 14400, 10800, "MSK", false)));
+    moscow.push(transition("2011-03-26 23:00", 10800, 14400, "MSK", false));
+    moscow.push(transition("2014-10-25 22:00", 14400, 10800, "MSK", false));
+    moscow
+  }
+
+  /*
+  * America/Mexico_City, which abolished daylight saving time after 2022
+  */
+  pub(crate) fn mexico_city_transitions() -> Vec<ZoneTransition> {
+    [("2020-04-05", "2020-10-25"), ("2021-04-04", "2021-10-31"), ("2022-04-03", "2022-10-30")].iter().flat_map(|(start, end)| vec![
+      transition(&format!("{} 08:00", start), -21600, -18000, "CDT", true),
+      transition(&format!("{} 07:00", end), -18000, -21600, "CST", false),
+    ]).collect()
+  }
+
+  #[test]
+  fn rules_are_read_from_local_time_before_the_transition() {
+    let london = london_transitions();
+    assert_eq!(london[0].rule(), TransitionRule { month: 3, weekday: Weekday::Sun, ordinal: -1, seconds: 3600 });
+    assert_eq!(london[1].rule(), TransitionRule { month: 10, weekday: Weekday::Sun, ordinal: -1, seconds: 7200 });
+    assert_eq!(london[1].rule().by_day(), "-1SU");
+    assert_eq!(london[1].rule().posix_week(), 5);
+    // second Sunday in March, as in the US
+    let us = transition("2024-03-10 07:00", -18000, -14400, "EDT", true).rule();
+    assert_eq!((us.ordinal, us.by_day(), us.posix_week()), (2, "2SU".to_string(), 2));
+  }
+
+  #[test]
+  fn consecutive_years_with_one_rule_form_open_ended_runs() {
+    let runs = group_transition_runs(&london_transitions(), horizon());
+    assert_eq!(runs.len(), 2);
+    assert!(runs.iter().all(|run| run.open_ended && run.transitions.len() == 4));
+    assert!(runs[0].transitions[0].dst);
+  }
+
+  #[test]
+  fn runs_ending_before_the_data_are_closed_and_single_changes_have_no_rule() {
+    let runs = group_transition_runs(&moscow_transitions(), horizon());
+    assert_eq!(runs.len(), 4);
+    assert!(runs[0].rule.is_some() && !runs[0].open_ended);
+    assert!(runs[1].rule.is_some() && !runs[1].open_ended);
+    assert!(runs[2].rule.is_none() && runs[3].rule.is_none());
+  }
+
+  #[test]
+  fn runs_are_open_ended_relative_to_the_horizon_of_all_data() {
+    let mexico_city = mexico_city_transitions();
+    // the zone's own data ends in 2022, but other zones have transitions up to the horizon
+    assert!(group_transition_runs(&mexico_city, horizon()).iter().all(|run| run.rule.is_some() && !run.open_ended));
+    assert!(group_transition_runs(&mexico_city, utc_ts("2023-06-01 00:00")).iter().all(|run| run.open_ended));
+    assert!(group_transition_runs(&london_transitions(), utc_ts("2025-12-31 00:00")).iter().all(|run| run.open_ended));
+  }
+
+  #[test]
+  fn missing_abbreviations_show_the_offset() {
+    assert_eq!(display_abbreviation("", 20700), "+0545");
+    assert_eq!(display_abbreviation("", -10800), "-03");
+    assert_eq!(display_abbreviation(" CET ", 3600), "CET");
+  }
+}
@@ -0,0 +1,172 @@
+use super::transition_rules::*;
+
+struct LocalTimeType {
+  offset: i64,
+  dst: bool,
+  abbreviation: String,
+}
+
+fn push_counts(output: &mut Vec<u8>, time_count: usize, type_count: usize, char_count: usize) {
+  // isutcnt, isstdcnt and leapcnt are zero, as all transitions are in UT and leap seconds are not included
+  for count in [0, 0, 0, time_count, type_count, char_count] {
+    output.extend_from_slice(&(count as u32).to_be_bytes());
+  }
+}
+
+fn push_header(output: &mut Vec<u8>, version: u8, time_count: usize, type_count: usize, char_count: usize) {
+  output.extend_from_slice(b"TZif");
+  output.push(version);
+  output.extend_from_slice(&[0u8; 15]);
+  push_counts(output, time_count, type_count, char_count);
+}
+
+fn push_types_and_designations(output: &mut Vec<u8>, types: &[LocalTimeType], designations: &[u8], indices: &[usize]) {
+  for (lt, desig_index) in types.iter().zip(indices.iter()) {
+    output.extend_from_slice(&(lt.offset as i32).to_be_bytes());
+    output.push(lt.dst as u8);
+    output.push(*desig_index as u8);
+  }
+  output.extend_from_slice(designations);
+}
+
+/*
+* Version 3 allows rule times in the footer that are negative or beyond 24 hours, e.g. M3.5.0/-1 or J365/25
+*/
+fn footer_version(posix_tz: &str) -> u8 {
+  let needs_v3 = posix_tz.split(',').skip(1).filter_map(|rule| rule.split_once('/').map(|(_, time)| time)).any(|time| {
+    let hours = time.trim_start_matches(['-', '+']).split(':').next().and_then(|h| h.parse::<i64>().ok()).unwrap_or(0);
+    time.starts_with('-') || hours > 24
+  });
+  if needs_v3 { b'3' } else { b'2' }
+}
+
+/*
+* Build a TZif file (RFC 8536) from zone transitions, with the first transition describing the initial local time type
+* and a POSIX TZ string footer for times after the last transition. Files are version 2 unless the footer needs version 3.
+*/
+pub fn build_tzif(transitions: &[ZoneTransition], posix_tz: &str) -> Vec<u8> {
+  let mut types: Vec<LocalTimeType> = Vec::new();
+  let mut type_indices: Vec<usize> = Vec::new();
+  for tr in transitions {
+    let abbreviation = display_abbreviation(&tr.abbreviation, tr.offset_to);
+    let index = match types.iter().position(|lt| lt.offset == tr.offset_to && lt.dst == tr.dst && lt.abbreviation == abbreviation) {
+      Some(index) => index,
+      None => {
+        types.push(LocalTimeType { offset: tr.offset_to, dst: tr.dst, abbreviation });
+        types.len() - 1
+      }
+    };
+    type_indices.push(index);
+  }
+  if types.is_empty() {
+    types.push(LocalTimeType { offset: 0, dst: false, abbreviation: "UTC".to_string() });
+  }
+  let mut designations: Vec<u8> = Vec::new();
+  let mut desig_indices: Vec<usize> = Vec::new();
+  for lt in types.iter() {
+    desig_indices.push(designations.len());
+    designations.extend_from_slice(lt.abbreviation.as_bytes());
+    designations.push(0);
+  }
+  // the first item only sets the initial local time type, which applies before the first transition
+  let timed: Vec<(i64, usize)> = transitions.iter().map(|t| t.ts).zip(type_indices.iter().copied()).skip(1).collect();
+  let timed_32: Vec<(i64, usize)> = timed.iter().filter(|(ts, _)| *ts >= i32::MIN as i64 && *ts <= i32::MAX as i64).copied().collect();
+  let version = footer_version(posix_tz);
+  let mut output: Vec<u8> = Vec::new();
+  push_header(&mut output, version, timed_32.len(), types.len(), designations.len());
+  for (ts, _) in timed_32.iter() {
+    output.extend_from_slice(&(*ts as i32).to_be_bytes());
+  }
+  output.extend(timed_32.iter().map(|(_, index)| *index as u8));
+  push_types_and_designations(&mut output, &types, &designations, &desig_indices);
+  push_header(&mut output, version, timed.len(), types.len(), designations.len());
+  for (ts, _) in timed.iter() {
+    output.extend_from_slice(&ts.to_be_bytes());
+  }
+  output.extend(timed.iter().map(|(_, index)| *index as u8));
+  push_types_and_designations(&mut output, &types, &designations, &desig_indices);
+  output.push(b'\n');
+  output.extend_from_slice(posix_tz.as_bytes());
+  output.push(b'\n');
+  output
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::transition_rules::tests::{transition, london_transitions};
+
+  const HEADER_LEN: usize = 44;
+
+  fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap())
+  }
+
+  /*
+  * isutcnt, isstdcnt, leapcnt, timecnt, typecnt and charcnt of the header at pos
+  */
+  fn read_counts(data: &[u8], pos: usize) -> Vec<u32> {
+    (0..6).map(|i| read_u32(data, pos + 20 + i * 4)).collect()
+  }
+
+  fn block_len(counts: &[u32], time_size: usize) -> usize {
+    let (time_count, type_count, char_count) = (counts[3] as usize, counts[4] as usize, counts[5] as usize);
+    time_count * (time_size + 1) + type_count * 6 + char_count
+  }
+
+  #[test]
+  fn v1_and_v2_blocks_share_types_and_footer() {
+    let london = london_transitions();
+    let data = build_tzif(&london, "GMT0BST,M3.5.0/1,M10.5.0");
+    assert_eq!(&data[0..5], b"TZif2");
+    assert!(data[5..20].iter().all(|b| *b == 0));
+    let v1_counts = read_counts(&data, 0);
+    // no UT/standard indicators or leap seconds, the first transition only sets the initial type
+    assert_eq!(v1_counts, vec![0, 0, 0, 7, 2, 8]);
+    assert_eq!(read_u32(&data, HEADER_LEN) as i64, london[1].ts);
+    let v1_indices = &data[HEADER_LEN + 28..HEADER_LEN + 35];
+    assert_eq!(v1_indices, &[1, 0, 1, 0, 1, 0, 1]);
+    let types_pos = HEADER_LEN + 35;
+    assert_eq!(&data[types_pos..types_pos + 12], &[0, 0, 0x0e, 0x10, 1, 0, 0, 0, 0, 0, 0, 4]);
+    assert_eq!(&data[types_pos + 12..types_pos + 20], b"BST\0GMT\0");
+    let v2_pos = HEADER_LEN + block_len(&v1_counts, 4);
+    assert_eq!(&data[v2_pos..v2_pos + 5], b"TZif2");
+    let v2_counts = read_counts(&data, v2_pos);
+    assert_eq!(v2_counts, v1_counts);
+    assert_eq!(i64::from_be_bytes(data[v2_pos + HEADER_LEN..v2_pos + HEADER_LEN + 8].try_into().unwrap()), london[1].ts);
+    let footer_pos = v2_pos + HEADER_LEN + block_len(&v2_counts, 8);
+    assert_eq!(&data[footer_pos..], b"\nGMT0BST,M3.5.0/1,M10.5.0\n");
+  }
+
+  #[test]
+  fn v1_block_omits_times_beyond_32_bits() {
+    let transitions = vec![
+      transition("2030-01-01 00:00", 0, 0, "GMT", false),
+      transition("2037-03-29 01:00", 0, 3600, "BST", true),
+      transition("2038-03-28 01:00", 0, 3600, "BST", true),
+    ];
+    let data = build_tzif(&transitions, "GMT0BST,M3.5.0/1,M10.5.0");
+    let v1_counts = read_counts(&data, 0);
+    assert_eq!(&v1_counts[3..], &[1, 2, 8]);
+    let v2_counts = read_counts(&data, HEADER_LEN + block_len(&v1_counts, 4));
+    assert_eq!(&v2_counts[..4], &[0, 0, 0, 2]);
+  }
+
+  #[test]
+  fn footers_with_extended_rule_times_need_version_3() {
+    assert_eq!(footer_version("GMT0BST,M3.5.0/1,M10.5.0"), b'2');
+    assert_eq!(footer_version("<+1030>-10:30<+11>-11,M10.1.0,M4.1.0"), b'2');
+    assert_eq!(footer_version("<-02>2<-01>,M3.5.0/-1,M10.5.0/0"), b'3');
+    assert_eq!(footer_version("EST5EDT,0/0,J365/25"), b'3');
+    let data = build_tzif(&london_transitions(), "<-02>2<-01>,M3.5.0/-1,M10.5.0/0");
+    let v2_pos = HEADER_LEN + block_len(&read_counts(&data, 0), 4);
+    assert_eq!((data[4], data[v2_pos + 4]), (b'3', b'3'));
+  }
+
+  #[test]
+  fn zones_without_transitions_have_a_utc_type() {
+    let data = build_tzif(&[], "UTC0");
+    assert_eq!(read_counts(&data, 0), vec![0, 0, 0, 0, 1, 4]);
+    assert_eq!(&data[HEADER_LEN + 6..HEADER_LEN + 10], b"UTC\0");
+  }
+}
@@ -0,0 +1,176 @@
+use chrono::NaiveDateTime;
+use super::transition_rules::*;
+
+/*
+* UTC offset as +HHMM, or +HHMMSS for offsets with seconds as in early local mean times
+*/
+pub fn format_ical_offset(offset: i64) -> String {
+  let sign = if offset < 0 { "-" } else { "+" };
+  let abs = offset.abs();
+  let (hours, minutes, seconds) = (abs / 3600, (abs % 3600) / 60, abs % 60);
+  if seconds > 0 {
+    format!("{}{:02}{:02}{:02}", sign, hours, minutes, seconds)
+  } else {
+    format!("{}{:02}{:02}", sign, hours, minutes)
+  }
+}
+
+fn format_ical_local(dt: &NaiveDateTime) -> String {
+  dt.format("%Y%m%dT%H%M%S").to_string()
+}
+
+fn format_ical_utc(ts: i64) -> String {
+  format!("{}Z", format_ical_local(&NaiveDateTime::from_timestamp(ts, 0)))
+}
+
+fn build_component(first: &ZoneTransition, lines: Vec<String>) -> Vec<String> {
+  let component = if first.dst { "DAYLIGHT" } else { "STANDARD" };
+  let mut output = vec![
+    format!("BEGIN:{}", component),
+    format!("TZOFFSETFROM:{}", format_ical_offset(first.offset_from)),
+    format!("TZOFFSETTO:{}", format_ical_offset(first.offset_to)),
+  ];
+  if !first.abbreviation.is_empty() {
+    output.push(format!("TZNAME:{}", first.abbreviation));
+  }
+  output.push(format!("DTSTART:{}", format_ical_local(&first.local_start())));
+  output.extend(lines);
+  output.push(format!("END:{}", component));
+  output
+}
+
+/*
+* Build a VCALENDAR with a single VTIMEZONE component. Transitions following an annual rule become
+* STANDARD or DAYLIGHT blocks with an RRULE, while irregular transitions of the same kind are listed as RDATEs.
+* The initial transition should describe the offset in force at the start of the exported range.
+*/
+pub fn build_vtimezone(zone_name: &str, transitions: &[ZoneTransition], horizon_ts: i64) -> String {
+  let mut lines: Vec<String> = vec![
+    "BEGIN:VCALENDAR".to_string(),
+    "VERSION:2.0".to_string(),
+    "PRODID:-//GeoTimeZone//VTIMEZONE//EN".to_string(),
+    "BEGIN:VTIMEZONE".to_string(),
+    format!("TZID:{}", zone_name),
+    format!("X-LIC-LOCATION:{}", zone_name),
+  ];
+  let runs = group_transition_runs(transitions, horizon_ts);
+  let mut irregular: Vec<Vec<ZoneTransition>> = Vec::new();
+  for run in runs.iter() {
+    match (run.rule, run.transitions.first()) {
+      (Some(rule), Some(first)) => {
+        let until = if run.open_ended {
+          "".to_string()
+        } else {
+          run.transitions.last().map(|t| format!(";UNTIL={}", format_ical_utc(t.ts))).unwrap_or_default()
+        };
+        let rrule = format!("RRULE:FREQ=YEARLY;BYMONTH={};BYDAY={}{}", rule.month, rule.by_day(), until);
+        lines.extend(build_component(first, vec![rrule]));
+      },
+      (None, Some(first)) => {
+        let same_kind = irregular.iter_mut().find(|items| {
+          items.first().map(|t| t.offset_from == first.offset_from && t.offset_to == first.offset_to && t.abbreviation == first.abbreviation && t.dst == first.dst).unwrap_or(false)
+        });
+        match same_kind {
+          Some(items) => items.extend(run.transitions.clone()),
+          None => irregular.push(run.transitions.clone())
+        }
+      },
+      _ => ()
+    }
+  }
+  for items in irregular.iter_mut() {
+    items.sort_by_key(|t| t.ts);
+    if let Some(first) = items.first() {
+      let rdates: Vec<String> = items.iter().skip(1).map(|t| format!("RDATE:{}", format_ical_local(&t.local_start()))).collect();
+      lines.extend(build_component(first, rdates));
+    }
+  }
+  lines.push("END:VTIMEZONE".to_string());
+  lines.push("END:VCALENDAR".to_string());
+  lines.join("\r\n") + "\r\n"
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::transition_rules::tests::{transition, horizon, london_transitions, moscow_transitions, mexico_city_transitions};
+
+  fn component_lines(ics: &str, component: &str) -> Vec<Vec<String>> {
+    let begin = format!("BEGIN:{}", component);
+    let end = format!("END:{}", component);
+    let mut blocks: Vec<Vec<String>> = vec![];
+    let mut current: Option<Vec<String>> = None;
+    for line in ics.split("\r\n") {
+      if line == begin {
+        current = Some(vec![]);
+      } else if line == end {
+        blocks.extend(current.take());
+      } else if let Some(lines) = current.as_mut() {
+        lines.push(line.to_string());
+      }
+    }
+    blocks
+  }
+
+  #[test]
+  fn offsets_include_seconds_only_when_needed() {
+    assert_eq!(format_ical_offset(3600), "+0100");
+    assert_eq!(format_ical_offset(-12600), "-0330");
+    assert_eq!(format_ical_offset(-75), "-000115");
+  }
+
+  #[test]
+  fn current_rules_are_open_ended() {
+    let ics = build_vtimezone("Europe/London", &london_transitions(), horizon());
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(ics.contains("\r\nTZID:Europe/London\r\n"));
+    assert!(ics.ends_with("END:VTIMEZONE\r\nEND:VCALENDAR\r\n"));
+    assert_eq!(component_lines(&ics, "DAYLIGHT"), vec![vec![
+      "TZOFFSETFROM:+0000", "TZOFFSETTO:+0100", "TZNAME:BST", "DTSTART:20210328T010000", "RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU"
+    ]]);
+    assert_eq!(component_lines(&ics, "STANDARD"), vec![vec![
+      "TZOFFSETFROM:+0100", "TZOFFSETTO:+0000", "TZNAME:GMT", "DTSTART:20211031T020000", "RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU"
+    ]]);
+  }
+
+  #[test]
+  fn discontinued_rules_end_with_a_utc_until() {
+    let ics = build_vtimezone("Europe/Moscow", &moscow_transitions(), horizon());
+    let daylight = component_lines(&ics, "DAYLIGHT");
+    assert_eq!(daylight.len(), 1);
+    assert_eq!(daylight[0].last().unwrap(), "RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU;UNTIL=20100327T230000Z");
+    let standard = component_lines(&ics, "STANDARD");
+    assert_eq!(standard.len(), 3);
+    assert_eq!(standard[0].last().unwrap(), "RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU;UNTIL=20101030T230000Z");
+    // single changes of offset are components of their own without a rule
+    assert_eq!(standard[1], vec!["TZOFFSETFROM:+0300", "TZOFFSETTO:+0400", "TZNAME:MSK", "DTSTART:20110327T020000"]);
+    assert_eq!(standard[2], vec!["TZOFFSETFROM:+0400", "TZOFFSETTO:+0300", "TZNAME:MSK", "DTSTART:20141026T020000"]);
+  }
+
+  #[test]
+  fn abolished_dst_rules_end_with_until() {
+    let ics = build_vtimezone("America/Mexico_City", &mexico_city_transitions(), horizon());
+    let daylight = component_lines(&ics, "DAYLIGHT");
+    assert_eq!(daylight[0].last().unwrap(), "RRULE:FREQ=YEARLY;BYMONTH=4;BYDAY=1SU;UNTIL=20220403T080000Z");
+    let standard = component_lines(&ics, "STANDARD");
+    assert_eq!(standard[0].last().unwrap(), "RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU;UNTIL=20221030T070000Z");
+  }
+
+  #[test]
+  fn irregular_transitions_of_one_kind_are_grouped_as_rdates() {
+    let cairo = vec![
+      transition("2010-04-29 22:00", 7200, 10800, "EEST", true),
+      transition("2010-09-29 21:00", 10800, 7200, "EET", false),
+      transition("2014-05-15 22:00", 7200, 10800, "EEST", true),
+      transition("2014-09-25 21:00", 10800, 7200, "EET", false),
+    ];
+    let ics = build_vtimezone("Africa/Cairo", &cairo, horizon());
+    assert_eq!(component_lines(&ics, "DAYLIGHT"), vec![vec![
+      "TZOFFSETFROM:+0200", "TZOFFSETTO:+0300", "TZNAME:EEST", "DTSTART:20100430T000000", "RDATE:20140516T000000"
+    ]]);
+    assert_eq!(component_lines(&ics, "STANDARD"), vec![vec![
+      "TZOFFSETFROM:+0300", "TZOFFSETTO:+0200", "TZNAME:EET", "DTSTART:20100930T000000", "RDATE:20140926T000000"
+    ]]);
+    assert!(!ics.contains("RRULE"));
+  }
+}
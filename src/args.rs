@@ -28,4 +28,10 @@ pub struct Args {
   // -w GeoTimes service port
   #[clap(short, long, value_parser, default_value_t = 0 )]
   pub webport: u16,
+  // --import directory with GeoNames dump files to load into local tables, then exit
+  #[clap(long, value_parser, default_value_t = empty_string() )]
+  pub import: String,
+  // --overrides CSV file of dated zone overrides to load into the zone_overrides table, then exit
+  #[clap(long, value_parser, default_value_t = empty_string() )]
+  pub overrides: String,
 }
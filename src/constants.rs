@@ -8,3 +8,36 @@ pub const GEONAMES_USERNAME_DEFAULT: &str = "demo";
 pub const GEONAMES_API_BASE: &str = "http://api.geonames.org";
 pub const GEONAMES_MAX_NEARBY_DISTANCE: f64 = 240f64;
 pub const DEFAULT_WEB_PORT: u16 = 8089;
+pub const GEONAMES_HOURLY_LIMIT_DEFAULT: u32 = 1000;
+pub const GEONAMES_DAILY_LIMIT_DEFAULT: u32 = 10000;
+pub const GEONAMES_MAX_RETRIES: u32 = 2;
+pub const GEONAMES_RETRY_DELAY_MS: u64 = 250;
+pub const CACHE_DIR_DEFAULT: &str = "cache/geotimezone";
+pub const CACHE_REDIS_URL_DEFAULT: &str = "redis://127.0.0.1:6379";
+pub const CACHE_KEY_PREFIX_DEFAULT: &str = "geotz:";
+pub const CACHE_TTL_DEFAULT: u64 = 2_592_000;
+pub const CACHE_SEARCH_TTL_DEFAULT: u64 = 604_800;
+pub const CACHE_MAX_ENTRIES_DEFAULT: u64 = 50_000;
+pub const CACHE_SWEEP_INTERVAL_DEFAULT: u64 = 600;
+pub const CACHE_COORD_PRECISION_DEFAULT: u8 = 4;
+pub const GEO_MEMO_PRECISION_DEFAULT: usize = 6;
+pub const GEO_MEMO_CONFIRMATIONS_DEFAULT: u32 = 2;
+pub const GEO_MEMO_MAX_CELLS_DEFAULT: usize = 100_000;
+pub const GEO_MEMO_VERSION_CHECK_SECS: i64 = 300;
+pub const NOMINATIM_API_BASE: &str = "https://nominatim.openstreetmap.org";
+pub const PHOTON_API_BASE: &str = "https://photon.komoot.io";
+pub const GEOCODING_USER_AGENT_DEFAULT: &str = "GeoTimeZone/0.1";
+
+// Search radii tried in turn when looking for the nearest city offline
+pub const REVERSE_GEOCODE_RADII_KM: [f64; 4] = [25.0, 100.0, 400.0, 1500.0];
+
+pub const REVERSE_GEOCODE_ADMIN2_RADIUS_KM: f64 = 50.0;
+
+pub const NEARBY_RADIUS_DEFAULT_KM: f64 = 50.0;
+pub const NEARBY_RADIUS_MAX_KM: f64 = 2000.0;
+
+// Minimum rows requested from geocoders when results are filtered by admin1 region
+pub const REGION_SEARCH_ROWS_MIN: u8 = 20;
+
+// Places with the same name listed when a place reference matches several zones
+pub const PLACE_CANDIDATES_MAX: u8 = 5;
@@ -0,0 +1,221 @@
+/*
+* First-level divisions of countries spanning several time zones, with postal or ISO 3166-2 codes
+* and English names, followed by common variants separated by |, as matched by the reg parameter.
+* (COUNTRY, CODE, NAMES)
+*/
+pub const ADMIN1_REGIONS: [(&str, &str, &str); 214] = [
+  ("AU", "ACT", "Australian Capital Territory"),
+  ("AU", "NSW", "New South Wales"),
+  ("AU", "NT", "Northern Territory"),
+  ("AU", "QLD", "Queensland"),
+  ("AU", "SA", "South Australia"),
+  ("AU", "TAS", "Tasmania"),
+  ("AU", "VIC", "Victoria"),
+  ("AU", "WA", "Western Australia"),
+  ("BR", "AC", "Acre"),
+  ("BR", "AL", "Alagoas"),
+  ("BR", "AP", "Amapá"),
+  ("BR", "AM", "Amazonas"),
+  ("BR", "BA", "Bahia"),
+  ("BR", "CE", "Ceará"),
+  ("BR", "DF", "Distrito Federal|Federal District"),
+  ("BR", "ES", "Espírito Santo"),
+  ("BR", "GO", "Goiás"),
+  ("BR", "MA", "Maranhão"),
+  ("BR", "MT", "Mato Grosso"),
+  ("BR", "MS", "Mato Grosso do Sul"),
+  ("BR", "MG", "Minas Gerais"),
+  ("BR", "PA", "Pará"),
+  ("BR", "PB", "Paraíba"),
+  ("BR", "PR", "Paraná"),
+  ("BR", "PE", "Pernambuco"),
+  ("BR", "PI", "Piauí"),
+  ("BR", "RJ", "Rio de Janeiro"),
+  ("BR", "RN", "Rio Grande do Norte"),
+  ("BR", "RS", "Rio Grande do Sul"),
+  ("BR", "RO", "Rondônia"),
+  ("BR", "RR", "Roraima"),
+  ("BR", "SC", "Santa Catarina"),
+  ("BR", "SP", "São Paulo"),
+  ("BR", "SE", "Sergipe"),
+  ("BR", "TO", "Tocantins"),
+  ("CA", "AB", "Alberta"),
+  ("CA", "BC", "British Columbia"),
+  ("CA", "MB", "Manitoba"),
+  ("CA", "NB", "New Brunswick"),
+  ("CA", "NL", "Newfoundland and Labrador"),
+  ("CA", "NS", "Nova Scotia"),
+  ("CA", "NT", "Northwest Territories"),
+  ("CA", "NU", "Nunavut"),
+  ("CA", "ON", "Ontario"),
+  ("CA", "PE", "Prince Edward Island"),
+  ("CA", "QC", "Quebec"),
+  ("CA", "SK", "Saskatchewan"),
+  ("CA", "YT", "Yukon"),
+  ("MX", "AGU", "Aguascalientes"),
+  ("MX", "BCN", "Baja California"),
+  ("MX", "BCS", "Baja California Sur"),
+  ("MX", "CAM", "Campeche"),
+  ("MX", "CHP", "Chiapas"),
+  ("MX", "CHH", "Chihuahua"),
+  ("MX", "COA", "Coahuila|Coahuila de Zaragoza"),
+  ("MX", "COL", "Colima"),
+  ("MX", "CMX", "Mexico City|Ciudad de México|Distrito Federal"),
+  ("MX", "DUR", "Durango"),
+  ("MX", "GUA", "Guanajuato"),
+  ("MX", "GRO", "Guerrero"),
+  ("MX", "HID", "Hidalgo"),
+  ("MX", "JAL", "Jalisco"),
+  ("MX", "MEX", "México|State of Mexico|Estado de México"),
+  ("MX", "MIC", "Michoacán|Michoacán de Ocampo"),
+  ("MX", "MOR", "Morelos"),
+  ("MX", "NAY", "Nayarit"),
+  ("MX", "NLE", "Nuevo León"),
+  ("MX", "OAX", "Oaxaca"),
+  ("MX", "PUE", "Puebla"),
+  ("MX", "QUE", "Querétaro"),
+  ("MX", "ROO", "Quintana Roo"),
+  ("MX", "SLP", "San Luis Potosí"),
+  ("MX", "SIN", "Sinaloa"),
+  ("MX", "SON", "Sonora"),
+  ("MX", "TAB", "Tabasco"),
+  ("MX", "TAM", "Tamaulipas"),
+  ("MX", "TLA", "Tlaxcala"),
+  ("MX", "VER", "Veracruz|Veracruz de Ignacio de la Llave|Veracruz-Llave"),
+  ("MX", "YUC", "Yucatán"),
+  ("MX", "ZAC", "Zacatecas"),
+  ("RU", "AD", "Adygeya Republic|Adygea"),
+  ("RU", "AL", "Altai Republic"),
+  ("RU", "ALT", "Altai Krai"),
+  ("RU", "AMU", "Amur Oblast"),
+  ("RU", "ARK", "Arkhangelsk Oblast"),
+  ("RU", "AST", "Astrakhan Oblast"),
+  ("RU", "BA", "Bashkortostan Republic|Bashkortostan"),
+  ("RU", "BEL", "Belgorod Oblast"),
+  ("RU", "BRY", "Bryansk Oblast"),
+  ("RU", "BU", "Buryatia Republic|Buryatia"),
+  ("RU", "CE", "Chechen Republic|Chechnya"),
+  ("RU", "CHE", "Chelyabinsk Oblast"),
+  ("RU", "CHU", "Chukotka Autonomous Okrug|Chukotka"),
+  ("RU", "CU", "Chuvash Republic|Chuvashia"),
+  ("RU", "DA", "Dagestan Republic|Dagestan"),
+  ("RU", "IN", "Ingushetia Republic|Ingushetia"),
+  ("RU", "IRK", "Irkutsk Oblast"),
+  ("RU", "IVA", "Ivanovo Oblast"),
+  ("RU", "KB", "Kabardino-Balkar Republic|Kabardino-Balkaria"),
+  ("RU", "KGD", "Kaliningrad Oblast"),
+  ("RU", "KL", "Kalmykia Republic|Kalmykia"),
+  ("RU", "KLU", "Kaluga Oblast"),
+  ("RU", "KAM", "Kamchatka Krai"),
+  ("RU", "KC", "Karachay-Cherkess Republic|Karachay-Cherkessia"),
+  ("RU", "KR", "Karelia Republic|Karelia"),
+  ("RU", "KEM", "Kemerovo Oblast|Kuzbass"),
+  ("RU", "KHA", "Khabarovsk Krai"),
+  ("RU", "KK", "Khakassia Republic|Khakassia"),
+  ("RU", "KHM", "Khanty-Mansi Autonomous Okrug|Khanty-Mansia|Yugra"),
+  ("RU", "KIR", "Kirov Oblast"),
+  ("RU", "KO", "Komi Republic|Komi"),
+  ("RU", "KOS", "Kostroma Oblast"),
+  ("RU", "KDA", "Krasnodar Krai"),
+  ("RU", "KYA", "Krasnoyarsk Krai"),
+  ("RU", "KGN", "Kurgan Oblast"),
+  ("RU", "KRS", "Kursk Oblast"),
+  ("RU", "LEN", "Leningrad Oblast"),
+  ("RU", "LIP", "Lipetsk Oblast"),
+  ("RU", "MAG", "Magadan Oblast"),
+  ("RU", "ME", "Mari El Republic|Mari El"),
+  ("RU", "MO", "Mordovia Republic|Mordovia"),
+  ("RU", "MOS", "Moscow Oblast"),
+  ("RU", "MOW", "Moscow"),
+  ("RU", "MUR", "Murmansk Oblast"),
+  ("RU", "NEN", "Nenets Autonomous Okrug|Nenets"),
+  ("RU", "NIZ", "Nizhny Novgorod Oblast"),
+  ("RU", "NGR", "Novgorod Oblast"),
+  ("RU", "NVS", "Novosibirsk Oblast"),
+  ("RU", "OMS", "Omsk Oblast"),
+  ("RU", "ORE", "Orenburg Oblast"),
+  ("RU", "ORL", "Oryol Oblast"),
+  ("RU", "PNZ", "Penza Oblast"),
+  ("RU", "PER", "Perm Krai"),
+  ("RU", "PRI", "Primorsky Krai|Primorskiy (Maritime) Kray|Primorye"),
+  ("RU", "PSK", "Pskov Oblast"),
+  ("RU", "ROS", "Rostov Oblast"),
+  ("RU", "RYA", "Ryazan Oblast"),
+  ("RU", "SA", "Sakha Republic|Yakutia"),
+  ("RU", "SAK", "Sakhalin Oblast"),
+  ("RU", "SAM", "Samara Oblast"),
+  ("RU", "SAR", "Saratov Oblast"),
+  ("RU", "SE", "North Ossetia-Alania Republic|North Ossetia"),
+  ("RU", "SMO", "Smolensk Oblast"),
+  ("RU", "SPE", "Saint Petersburg|St. Petersburg"),
+  ("RU", "STA", "Stavropol Krai"),
+  ("RU", "SVE", "Sverdlovsk Oblast"),
+  ("RU", "TA", "Tatarstan Republic|Tatarstan"),
+  ("RU", "TAM", "Tambov Oblast"),
+  ("RU", "TOM", "Tomsk Oblast"),
+  ("RU", "TUL", "Tula Oblast"),
+  ("RU", "TVE", "Tver Oblast"),
+  ("RU", "TY", "Tuva Republic|Tyva"),
+  ("RU", "TYU", "Tyumen Oblast"),
+  ("RU", "UD", "Udmurt Republic|Udmurtia"),
+  ("RU", "ULY", "Ulyanovsk Oblast"),
+  ("RU", "VGG", "Volgograd Oblast"),
+  ("RU", "VLA", "Vladimir Oblast"),
+  ("RU", "VLG", "Vologda Oblast"),
+  ("RU", "VOR", "Voronezh Oblast"),
+  ("RU", "YAN", "Yamalo-Nenets Autonomous Okrug|Yamal"),
+  ("RU", "YAR", "Yaroslavl Oblast"),
+  ("RU", "YEV", "Jewish Autonomous Oblast"),
+  ("RU", "ZAB", "Zabaykalsky Krai|Zabaykalskiy Kray"),
+  ("US", "AL", "Alabama"),
+  ("US", "AK", "Alaska"),
+  ("US", "AZ", "Arizona"),
+  ("US", "AR", "Arkansas"),
+  ("US", "CA", "California"),
+  ("US", "CO", "Colorado"),
+  ("US", "CT", "Connecticut"),
+  ("US", "DE", "Delaware"),
+  ("US", "DC", "District of Columbia|Washington, D.C."),
+  ("US", "FL", "Florida"),
+  ("US", "GA", "Georgia"),
+  ("US", "HI", "Hawaii"),
+  ("US", "ID", "Idaho"),
+  ("US", "IL", "Illinois"),
+  ("US", "IN", "Indiana"),
+  ("US", "IA", "Iowa"),
+  ("US", "KS", "Kansas"),
+  ("US", "KY", "Kentucky"),
+  ("US", "LA", "Louisiana"),
+  ("US", "ME", "Maine"),
+  ("US", "MD", "Maryland"),
+  ("US", "MA", "Massachusetts"),
+  ("US", "MI", "Michigan"),
+  ("US", "MN", "Minnesota"),
+  ("US", "MS", "Mississippi"),
+  ("US", "MO", "Missouri"),
+  ("US", "MT", "Montana"),
+  ("US", "NE", "Nebraska"),
+  ("US", "NV", "Nevada"),
+  ("US", "NH", "New Hampshire"),
+  ("US", "NJ", "New Jersey"),
+  ("US", "NM", "New Mexico"),
+  ("US", "NY", "New York"),
+  ("US", "NC", "North Carolina"),
+  ("US", "ND", "North Dakota"),
+  ("US", "OH", "Ohio"),
+  ("US", "OK", "Oklahoma"),
+  ("US", "OR", "Oregon"),
+  ("US", "PA", "Pennsylvania"),
+  ("US", "RI", "Rhode Island"),
+  ("US", "SC", "South Carolina"),
+  ("US", "SD", "South Dakota"),
+  ("US", "TN", "Tennessee"),
+  ("US", "TX", "Texas"),
+  ("US", "UT", "Utah"),
+  ("US", "VT", "Vermont"),
+  ("US", "VA", "Virginia"),
+  ("US", "WA", "Washington"),
+  ("US", "WV", "West Virginia"),
+  ("US", "WI", "Wisconsin"),
+  ("US", "WY", "Wyoming"),
+];
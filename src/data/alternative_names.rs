@@ -7,7 +7,7 @@
 * This lookup set serves only for post-filtering as geonames usually picks up such variants from the search query, 
 * but does not include them in the results, e.f. q=Madras will match Chennai, 
 * but this will not appear in the results for capital of Tamil Nadu.
-* Should this data set grow beyond a few hundred entries, it should be migrated to a full database.
+* The alternate_names table supersedes this list, which is only used when the table does not exist.
 */
 pub const ALTERNATIVE_NAMES: [(&'static str, &'static str); 11] = [
   ("Madras", "Chennai"),
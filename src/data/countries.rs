@@ -0,0 +1,301 @@
+/*
+* ISO 3166-1 countries and territories with their alpha-3 code, numeric code, English short name, capital
+* and default time zones, the first being the zone of the capital or most populous region.
+* Kosovo uses the user-assigned XK and XKX codes and has no numeric code.
+* (ALPHA-2, ALPHA-3, NUMERIC, NAME, CAPITAL, ZONES separated by |)
+*/
+pub static COUNTRIES: [(&str, &str, u16, &str, &str, &str); 250] = [
+  ("AD", "AND", 20, "Andorra", "Andorra la Vella", "Europe/Andorra"),
+  ("AE", "ARE", 784, "United Arab Emirates", "Abu Dhabi", "Asia/Dubai"),
+  ("AF", "AFG", 4, "Afghanistan", "Kabul", "Asia/Kabul"),
+  ("AG", "ATG", 28, "Antigua and Barbuda", "St. John's", "America/Antigua"),
+  ("AI", "AIA", 660, "Anguilla", "The Valley", "America/Anguilla"),
+  ("AL", "ALB", 8, "Albania", "Tirana", "Europe/Tirane"),
+  ("AM", "ARM", 51, "Armenia", "Yerevan", "Asia/Yerevan"),
+  ("AO", "AGO", 24, "Angola", "Luanda", "Africa/Luanda"),
+  ("AQ", "ATA", 10, "Antarctica", "", "Antarctica/McMurdo"),
+  ("AR", "ARG", 32, "Argentina", "Buenos Aires", "America/Argentina/Buenos_Aires"),
+  ("AS", "ASM", 16, "American Samoa", "Pago Pago", "Pacific/Pago_Pago"),
+  ("AT", "AUT", 40, "Austria", "Vienna", "Europe/Vienna"),
+  ("AU", "AUS", 36, "Australia", "Canberra", "Australia/Sydney|Australia/Melbourne|Australia/Brisbane|Australia/Adelaide|Australia/Darwin|Australia/Perth|Australia/Hobart"),
+  ("AW", "ABW", 533, "Aruba", "Oranjestad", "America/Aruba"),
+  ("AX", "ALA", 248, "Åland", "Mariehamn", "Europe/Mariehamn"),
+  ("AZ", "AZE", 31, "Azerbaijan", "Baku", "Asia/Baku"),
+  ("BA", "BIH", 70, "Bosnia and Herzegovina", "Sarajevo", "Europe/Sarajevo"),
+  ("BB", "BRB", 52, "Barbados", "Bridgetown", "America/Barbados"),
+  ("BD", "BGD", 50, "Bangladesh", "Dhaka", "Asia/Dhaka"),
+  ("BE", "BEL", 56, "Belgium", "Brussels", "Europe/Brussels"),
+  ("BF", "BFA", 854, "Burkina Faso", "Ouagadougou", "Africa/Ouagadougou"),
+  ("BG", "BGR", 100, "Bulgaria", "Sofia", "Europe/Sofia"),
+  ("BH", "BHR", 48, "Bahrain", "Manama", "Asia/Bahrain"),
+  ("BI", "BDI", 108, "Burundi", "Gitega", "Africa/Bujumbura"),
+  ("BJ", "BEN", 204, "Benin", "Porto-Novo", "Africa/Porto-Novo"),
+  ("BL", "BLM", 652, "Saint Barthélemy", "Gustavia", "America/St_Barthelemy"),
+  ("BM", "BMU", 60, "Bermuda", "Hamilton", "Atlantic/Bermuda"),
+  ("BN", "BRN", 96, "Brunei", "Bandar Seri Begawan", "Asia/Brunei"),
+  ("BO", "BOL", 68, "Bolivia", "Sucre", "America/La_Paz"),
+  ("BQ", "BES", 535, "Bonaire, Sint Eustatius and Saba", "Kralendijk", "America/Kralendijk"),
+  ("BR", "BRA", 76, "Brazil", "Brasília", "America/Sao_Paulo|America/Manaus|America/Fortaleza|America/Cuiaba|America/Rio_Branco|America/Noronha"),
+  ("BS", "BHS", 44, "Bahamas", "Nassau", "America/Nassau"),
+  ("BT", "BTN", 64, "Bhutan", "Thimphu", "Asia/Thimphu"),
+  ("BV", "BVT", 74, "Bouvet Island", "", "Europe/Oslo"),
+  ("BW", "BWA", 72, "Botswana", "Gaborone", "Africa/Gaborone"),
+  ("BY", "BLR", 112, "Belarus", "Minsk", "Europe/Minsk"),
+  ("BZ", "BLZ", 84, "Belize", "Belmopan", "America/Belize"),
+  ("CA", "CAN", 124, "Canada", "Ottawa", "America/Toronto|America/Vancouver|America/Edmonton|America/Winnipeg|America/Regina|America/Halifax|America/St_Johns"),
+  ("CC", "CCK", 166, "Cocos (Keeling) Islands", "West Island", "Indian/Cocos"),
+  ("CD", "COD", 180, "Democratic Republic of the Congo", "Kinshasa", "Africa/Kinshasa|Africa/Lubumbashi"),
+  ("CF", "CAF", 140, "Central African Republic", "Bangui", "Africa/Bangui"),
+  ("CG", "COG", 178, "Republic of the Congo", "Brazzaville", "Africa/Brazzaville"),
+  ("CH", "CHE", 756, "Switzerland", "Bern", "Europe/Zurich"),
+  ("CI", "CIV", 384, "Ivory Coast", "Yamoussoukro", "Africa/Abidjan"),
+  ("CK", "COK", 184, "Cook Islands", "Avarua", "Pacific/Rarotonga"),
+  ("CL", "CHL", 152, "Chile", "Santiago", "America/Santiago|Pacific/Easter"),
+  ("CM", "CMR", 120, "Cameroon", "Yaoundé", "Africa/Douala"),
+  ("CN", "CHN", 156, "China", "Beijing", "Asia/Shanghai"),
+  ("CO", "COL", 170, "Colombia", "Bogotá", "America/Bogota"),
+  ("CR", "CRI", 188, "Costa Rica", "San José", "America/Costa_Rica"),
+  ("CU", "CUB", 192, "Cuba", "Havana", "America/Havana"),
+  ("CV", "CPV", 132, "Cabo Verde", "Praia", "Atlantic/Cape_Verde"),
+  ("CW", "CUW", 531, "Curaçao", "Willemstad", "America/Curacao"),
+  ("CX", "CXR", 162, "Christmas Island", "Flying Fish Cove", "Indian/Christmas"),
+  ("CY", "CYP", 196, "Cyprus", "Nicosia", "Asia/Nicosia"),
+  ("CZ", "CZE", 203, "Czechia", "Prague", "Europe/Prague"),
+  ("DE", "DEU", 276, "Germany", "Berlin", "Europe/Berlin"),
+  ("DJ", "DJI", 262, "Djibouti", "Djibouti", "Africa/Djibouti"),
+  ("DK", "DNK", 208, "Denmark", "Copenhagen", "Europe/Copenhagen"),
+  ("DM", "DMA", 212, "Dominica", "Roseau", "America/Dominica"),
+  ("DO", "DOM", 214, "Dominican Republic", "Santo Domingo", "America/Santo_Domingo"),
+  ("DZ", "DZA", 12, "Algeria", "Algiers", "Africa/Algiers"),
+  ("EC", "ECU", 218, "Ecuador", "Quito", "America/Guayaquil|Pacific/Galapagos"),
+  ("EE", "EST", 233, "Estonia", "Tallinn", "Europe/Tallinn"),
+  ("EG", "EGY", 818, "Egypt", "Cairo", "Africa/Cairo"),
+  ("EH", "ESH", 732, "Western Sahara", "Laayoune", "Africa/El_Aaiun"),
+  ("ER", "ERI", 232, "Eritrea", "Asmara", "Africa/Asmara"),
+  ("ES", "ESP", 724, "Spain", "Madrid", "Europe/Madrid|Atlantic/Canary"),
+  ("ET", "ETH", 231, "Ethiopia", "Addis Ababa", "Africa/Addis_Ababa"),
+  ("FI", "FIN", 246, "Finland", "Helsinki", "Europe/Helsinki"),
+  ("FJ", "FJI", 242, "Fiji", "Suva", "Pacific/Fiji"),
+  ("FK", "FLK", 238, "Falkland Islands", "Stanley", "Atlantic/Stanley"),
+  ("FM", "FSM", 583, "Micronesia", "Palikir", "Pacific/Pohnpei|Pacific/Chuuk|Pacific/Kosrae"),
+  ("FO", "FRO", 234, "Faroe Islands", "Tórshavn", "Atlantic/Faroe"),
+  ("FR", "FRA", 250, "France", "Paris", "Europe/Paris"),
+  ("GA", "GAB", 266, "Gabon", "Libreville", "Africa/Libreville"),
+  ("GB", "GBR", 826, "United Kingdom", "London", "Europe/London"),
+  ("GD", "GRD", 308, "Grenada", "St. George's", "America/Grenada"),
+  ("GE", "GEO", 268, "Georgia", "Tbilisi", "Asia/Tbilisi"),
+  ("GF", "GUF", 254, "French Guiana", "Cayenne", "America/Cayenne"),
+  ("GG", "GGY", 831, "Guernsey", "St Peter Port", "Europe/Guernsey"),
+  ("GH", "GHA", 288, "Ghana", "Accra", "Africa/Accra"),
+  ("GI", "GIB", 292, "Gibraltar", "Gibraltar", "Europe/Gibraltar"),
+  ("GL", "GRL", 304, "Greenland", "Nuuk", "America/Nuuk|America/Danmarkshavn|America/Scoresbysund|America/Thule"),
+  ("GM", "GMB", 270, "Gambia", "Banjul", "Africa/Banjul"),
+  ("GN", "GIN", 324, "Guinea", "Conakry", "Africa/Conakry"),
+  ("GP", "GLP", 312, "Guadeloupe", "Basse-Terre", "America/Guadeloupe"),
+  ("GQ", "GNQ", 226, "Equatorial Guinea", "Malabo", "Africa/Malabo"),
+  ("GR", "GRC", 300, "Greece", "Athens", "Europe/Athens"),
+  ("GS", "SGS", 239, "South Georgia and the South Sandwich Islands", "Grytviken", "Atlantic/South_Georgia"),
+  ("GT", "GTM", 320, "Guatemala", "Guatemala City", "America/Guatemala"),
+  ("GU", "GUM", 316, "Guam", "Hagåtña", "Pacific/Guam"),
+  ("GW", "GNB", 624, "Guinea-Bissau", "Bissau", "Africa/Bissau"),
+  ("GY", "GUY", 328, "Guyana", "Georgetown", "America/Guyana"),
+  ("HK", "HKG", 344, "Hong Kong", "Hong Kong", "Asia/Hong_Kong"),
+  ("HM", "HMD", 334, "Heard Island and McDonald Islands", "", "Indian/Kerguelen"),
+  ("HN", "HND", 340, "Honduras", "Tegucigalpa", "America/Tegucigalpa"),
+  ("HR", "HRV", 191, "Croatia", "Zagreb", "Europe/Zagreb"),
+  ("HT", "HTI", 332, "Haiti", "Port-au-Prince", "America/Port-au-Prince"),
+  ("HU", "HUN", 348, "Hungary", "Budapest", "Europe/Budapest"),
+  ("ID", "IDN", 360, "Indonesia", "Jakarta", "Asia/Jakarta|Asia/Makassar|Asia/Jayapura"),
+  ("IE", "IRL", 372, "Ireland", "Dublin", "Europe/Dublin"),
+  ("IL", "ISR", 376, "Israel", "Jerusalem", "Asia/Jerusalem"),
+  ("IM", "IMN", 833, "Isle of Man", "Douglas", "Europe/Isle_of_Man"),
+  ("IN", "IND", 356, "India", "New Delhi", "Asia/Kolkata"),
+  ("IO", "IOT", 86, "British Indian Ocean Territory", "Diego Garcia", "Indian/Chagos"),
+  ("IQ", "IRQ", 368, "Iraq", "Baghdad", "Asia/Baghdad"),
+  ("IR", "IRN", 364, "Iran", "Tehran", "Asia/Tehran"),
+  ("IS", "ISL", 352, "Iceland", "Reykjavík", "Atlantic/Reykjavik"),
+  ("IT", "ITA", 380, "Italy", "Rome", "Europe/Rome"),
+  ("JE", "JEY", 832, "Jersey", "St Helier", "Europe/Jersey"),
+  ("JM", "JAM", 388, "Jamaica", "Kingston", "America/Jamaica"),
+  ("JO", "JOR", 400, "Jordan", "Amman", "Asia/Amman"),
+  ("JP", "JPN", 392, "Japan", "Tokyo", "Asia/Tokyo"),
+  ("KE", "KEN", 404, "Kenya", "Nairobi", "Africa/Nairobi"),
+  ("KG", "KGZ", 417, "Kyrgyzstan", "Bishkek", "Asia/Bishkek"),
+  ("KH", "KHM", 116, "Cambodia", "Phnom Penh", "Asia/Phnom_Penh"),
+  ("KI", "KIR", 296, "Kiribati", "Tarawa", "Pacific/Tarawa|Pacific/Kanton|Pacific/Kiritimati"),
+  ("KM", "COM", 174, "Comoros", "Moroni", "Indian/Comoro"),
+  ("KN", "KNA", 659, "Saint Kitts and Nevis", "Basseterre", "America/St_Kitts"),
+  ("KP", "PRK", 408, "North Korea", "Pyongyang", "Asia/Pyongyang"),
+  ("KR", "KOR", 410, "South Korea", "Seoul", "Asia/Seoul"),
+  ("KW", "KWT", 414, "Kuwait", "Kuwait City", "Asia/Kuwait"),
+  ("KY", "CYM", 136, "Cayman Islands", "George Town", "America/Cayman"),
+  ("KZ", "KAZ", 398, "Kazakhstan", "Astana", "Asia/Almaty|Asia/Aqtobe"),
+  ("LA", "LAO", 418, "Laos", "Vientiane", "Asia/Vientiane"),
+  ("LB", "LBN", 422, "Lebanon", "Beirut", "Asia/Beirut"),
+  ("LC", "LCA", 662, "Saint Lucia", "Castries", "America/St_Lucia"),
+  ("LI", "LIE", 438, "Liechtenstein", "Vaduz", "Europe/Vaduz"),
+  ("LK", "LKA", 144, "Sri Lanka", "Sri Jayawardenepura Kotte", "Asia/Colombo"),
+  ("LR", "LBR", 430, "Liberia", "Monrovia", "Africa/Monrovia"),
+  ("LS", "LSO", 426, "Lesotho", "Maseru", "Africa/Maseru"),
+  ("LT", "LTU", 440, "Lithuania", "Vilnius", "Europe/Vilnius"),
+  ("LU", "LUX", 442, "Luxembourg", "Luxembourg", "Europe/Luxembourg"),
+  ("LV", "LVA", 428, "Latvia", "Riga", "Europe/Riga"),
+  ("LY", "LBY", 434, "Libya", "Tripoli", "Africa/Tripoli"),
+  ("MA", "MAR", 504, "Morocco", "Rabat", "Africa/Casablanca"),
+  ("MC", "MCO", 492, "Monaco", "Monaco", "Europe/Monaco"),
+  ("MD", "MDA", 498, "Moldova", "Chişinău", "Europe/Chisinau"),
+  ("ME", "MNE", 499, "Montenegro", "Podgorica", "Europe/Podgorica"),
+  ("MF", "MAF", 663, "Saint Martin", "Marigot", "America/Marigot"),
+  ("MG", "MDG", 450, "Madagascar", "Antananarivo", "Indian/Antananarivo"),
+  ("MH", "MHL", 584, "Marshall Islands", "Majuro", "Pacific/Majuro"),
+  ("MK", "MKD", 807, "North Macedonia", "Skopje", "Europe/Skopje"),
+  ("ML", "MLI", 466, "Mali", "Bamako", "Africa/Bamako"),
+  ("MM", "MMR", 104, "Myanmar", "Naypyidaw", "Asia/Yangon"),
+  ("MN", "MNG", 496, "Mongolia", "Ulaanbaatar", "Asia/Ulaanbaatar|Asia/Hovd"),
+  ("MO", "MAC", 446, "Macao", "Macao", "Asia/Macau"),
+  ("MP", "MNP", 580, "Northern Mariana Islands", "Saipan", "Pacific/Saipan"),
+  ("MQ", "MTQ", 474, "Martinique", "Fort-de-France", "America/Martinique"),
+  ("MR", "MRT", 478, "Mauritania", "Nouakchott", "Africa/Nouakchott"),
+  ("MS", "MSR", 500, "Montserrat", "Brades", "America/Montserrat"),
+  ("MT", "MLT", 470, "Malta", "Valletta", "Europe/Malta"),
+  ("MU", "MUS", 480, "Mauritius", "Port Louis", "Indian/Mauritius"),
+  ("MV", "MDV", 462, "Maldives", "Malé", "Indian/Maldives"),
+  ("MW", "MWI", 454, "Malawi", "Lilongwe", "Africa/Blantyre"),
+  ("MX", "MEX", 484, "Mexico", "Mexico City", "America/Mexico_City|America/Cancun|America/Chihuahua|America/Hermosillo|America/Tijuana"),
+  ("MY", "MYS", 458, "Malaysia", "Kuala Lumpur", "Asia/Kuala_Lumpur"),
+  ("MZ", "MOZ", 508, "Mozambique", "Maputo", "Africa/Maputo"),
+  ("NA", "NAM", 516, "Namibia", "Windhoek", "Africa/Windhoek"),
+  ("NC", "NCL", 540, "New Caledonia", "Nouméa", "Pacific/Noumea"),
+  ("NE", "NER", 562, "Niger", "Niamey", "Africa/Niamey"),
+  ("NF", "NFK", 574, "Norfolk Island", "Kingston", "Pacific/Norfolk"),
+  ("NG", "NGA", 566, "Nigeria", "Abuja", "Africa/Lagos"),
+  ("NI", "NIC", 558, "Nicaragua", "Managua", "America/Managua"),
+  ("NL", "NLD", 528, "Netherlands", "Amsterdam", "Europe/Amsterdam"),
+  ("NO", "NOR", 578, "Norway", "Oslo", "Europe/Oslo"),
+  ("NP", "NPL", 524, "Nepal", "Kathmandu", "Asia/Kathmandu"),
+  ("NR", "NRU", 520, "Nauru", "Yaren", "Pacific/Nauru"),
+  ("NU", "NIU", 570, "Niue", "Alofi", "Pacific/Niue"),
+  ("NZ", "NZL", 554, "New Zealand", "Wellington", "Pacific/Auckland|Pacific/Chatham"),
+  ("OM", "OMN", 512, "Oman", "Muscat", "Asia/Muscat"),
+  ("PA", "PAN", 591, "Panama", "Panama City", "America/Panama"),
+  ("PE", "PER", 604, "Peru", "Lima", "America/Lima"),
+  ("PF", "PYF", 258, "French Polynesia", "Papeete", "Pacific/Tahiti|Pacific/Marquesas|Pacific/Gambier"),
+  ("PG", "PNG", 598, "Papua New Guinea", "Port Moresby", "Pacific/Port_Moresby"),
+  ("PH", "PHL", 608, "Philippines", "Manila", "Asia/Manila"),
+  ("PK", "PAK", 586, "Pakistan", "Islamabad", "Asia/Karachi"),
+  ("PL", "POL", 616, "Poland", "Warsaw", "Europe/Warsaw"),
+  ("PM", "SPM", 666, "Saint Pierre and Miquelon", "Saint-Pierre", "America/Miquelon"),
+  ("PN", "PCN", 612, "Pitcairn Islands", "Adamstown", "Pacific/Pitcairn"),
+  ("PR", "PRI", 630, "Puerto Rico", "San Juan", "America/Puerto_Rico"),
+  ("PS", "PSE", 275, "Palestine", "Ramallah", "Asia/Gaza"),
+  ("PT", "PRT", 620, "Portugal", "Lisbon", "Europe/Lisbon|Atlantic/Azores|Atlantic/Madeira"),
+  ("PW", "PLW", 585, "Palau", "Ngerulmud", "Pacific/Palau"),
+  ("PY", "PRY", 600, "Paraguay", "Asunción", "America/Asuncion"),
+  ("QA", "QAT", 634, "Qatar", "Doha", "Asia/Qatar"),
+  ("RE", "REU", 638, "Réunion", "Saint-Denis", "Indian/Reunion"),
+  ("RO", "ROU", 642, "Romania", "Bucharest", "Europe/Bucharest"),
+  ("RS", "SRB", 688, "Serbia", "Belgrade", "Europe/Belgrade"),
+  ("RU", "RUS", 643, "Russia", "Moscow", "Europe/Moscow|Europe/Kaliningrad|Europe/Samara|Asia/Yekaterinburg|Asia/Omsk|Asia/Novosibirsk|Asia/Krasnoyarsk|Asia/Irkutsk|Asia/Yakutsk|Asia/Vladivostok|Asia/Magadan|Asia/Kamchatka"),
+  ("RW", "RWA", 646, "Rwanda", "Kigali", "Africa/Kigali"),
+  ("SA", "SAU", 682, "Saudi Arabia", "Riyadh", "Asia/Riyadh"),
+  ("SB", "SLB", 90, "Solomon Islands", "Honiara", "Pacific/Guadalcanal"),
+  ("SC", "SYC", 690, "Seychelles", "Victoria", "Indian/Mahe"),
+  ("SD", "SDN", 729, "Sudan", "Khartoum", "Africa/Khartoum"),
+  ("SE", "SWE", 752, "Sweden", "Stockholm", "Europe/Stockholm"),
+  ("SG", "SGP", 702, "Singapore", "Singapore", "Asia/Singapore"),
+  ("SH", "SHN", 654, "Saint Helena, Ascension and Tristan da Cunha", "Jamestown", "Atlantic/St_Helena"),
+  ("SI", "SVN", 705, "Slovenia", "Ljubljana", "Europe/Ljubljana"),
+  ("SJ", "SJM", 744, "Svalbard and Jan Mayen", "Longyearbyen", "Arctic/Longyearbyen"),
+  ("SK", "SVK", 703, "Slovakia", "Bratislava", "Europe/Bratislava"),
+  ("SL", "SLE", 694, "Sierra Leone", "Freetown", "Africa/Freetown"),
+  ("SM", "SMR", 674, "San Marino", "San Marino", "Europe/San_Marino"),
+  ("SN", "SEN", 686, "Senegal", "Dakar", "Africa/Dakar"),
+  ("SO", "SOM", 706, "Somalia", "Mogadishu", "Africa/Mogadishu"),
+  ("SR", "SUR", 740, "Suriname", "Paramaribo", "America/Paramaribo"),
+  ("SS", "SSD", 728, "South Sudan", "Juba", "Africa/Juba"),
+  ("ST", "STP", 678, "São Tomé and Príncipe", "São Tomé", "Africa/Sao_Tome"),
+  ("SV", "SLV", 222, "El Salvador", "San Salvador", "America/El_Salvador"),
+  ("SX", "SXM", 534, "Sint Maarten", "Philipsburg", "America/Lower_Princes"),
+  ("SY", "SYR", 760, "Syria", "Damascus", "Asia/Damascus"),
+  ("SZ", "SWZ", 748, "Eswatini", "Mbabane", "Africa/Mbabane"),
+  ("TC", "TCA", 796, "Turks and Caicos Islands", "Cockburn Town", "America/Grand_Turk"),
+  ("TD", "TCD", 148, "Chad", "N'Djamena", "Africa/Ndjamena"),
+  ("TF", "ATF", 260, "French Southern Territories", "Port-aux-Français", "Indian/Kerguelen"),
+  ("TG", "TGO", 768, "Togo", "Lomé", "Africa/Lome"),
+  ("TH", "THA", 764, "Thailand", "Bangkok", "Asia/Bangkok"),
+  ("TJ", "TJK", 762, "Tajikistan", "Dushanbe", "Asia/Dushanbe"),
+  ("TK", "TKL", 772, "Tokelau", "Nukunonu", "Pacific/Fakaofo"),
+  ("TL", "TLS", 626, "Timor-Leste", "Dili", "Asia/Dili"),
+  ("TM", "TKM", 795, "Turkmenistan", "Ashgabat", "Asia/Ashgabat"),
+  ("TN", "TUN", 788, "Tunisia", "Tunis", "Africa/Tunis"),
+  ("TO", "TON", 776, "Tonga", "Nuku'alofa", "Pacific/Tongatapu"),
+  ("TR", "TUR", 792, "Turkey", "Ankara", "Europe/Istanbul"),
+  ("TT", "TTO", 780, "Trinidad and Tobago", "Port of Spain", "America/Port_of_Spain"),
+  ("TV", "TUV", 798, "Tuvalu", "Funafuti", "Pacific/Funafuti"),
+  ("TW", "TWN", 158, "Taiwan", "Taipei", "Asia/Taipei"),
+  ("TZ", "TZA", 834, "Tanzania", "Dodoma", "Africa/Dar_es_Salaam"),
+  ("UA", "UKR", 804, "Ukraine", "Kyiv", "Europe/Kyiv"),
+  ("UG", "UGA", 800, "Uganda", "Kampala", "Africa/Kampala"),
+  ("UM", "UMI", 581, "United States Minor Outlying Islands", "", "Pacific/Midway"),
+  ("US", "USA", 840, "United States", "Washington", "America/New_York|America/Chicago|America/Denver|America/Phoenix|America/Los_Angeles|America/Anchorage|Pacific/Honolulu"),
+  ("UY", "URY", 858, "Uruguay", "Montevideo", "America/Montevideo"),
+  ("UZ", "UZB", 860, "Uzbekistan", "Tashkent", "Asia/Tashkent"),
+  ("VA", "VAT", 336, "Vatican City", "Vatican City", "Europe/Vatican"),
+  ("VC", "VCT", 670, "Saint Vincent and the Grenadines", "Kingstown", "America/St_Vincent"),
+  ("VE", "VEN", 862, "Venezuela", "Caracas", "America/Caracas"),
+  ("VG", "VGB", 92, "British Virgin Islands", "Road Town", "America/Tortola"),
+  ("VI", "VIR", 850, "U.S. Virgin Islands", "Charlotte Amalie", "America/St_Thomas"),
+  ("VN", "VNM", 704, "Vietnam", "Hanoi", "Asia/Ho_Chi_Minh"),
+  ("VU", "VUT", 548, "Vanuatu", "Port Vila", "Pacific/Efate"),
+  ("WF", "WLF", 876, "Wallis and Futuna", "Mata-Utu", "Pacific/Wallis"),
+  ("WS", "WSM", 882, "Samoa", "Apia", "Pacific/Apia"),
+  ("XK", "XKX", 0, "Kosovo", "Pristina", "Europe/Belgrade"),
+  ("YE", "YEM", 887, "Yemen", "Sanaa", "Asia/Aden"),
+  ("YT", "MYT", 175, "Mayotte", "Mamoudzou", "Indian/Mayotte"),
+  ("ZA", "ZAF", 710, "South Africa", "Pretoria", "Africa/Johannesburg"),
+  ("ZM", "ZMB", 894, "Zambia", "Lusaka", "Africa/Lusaka"),
+  ("ZW", "ZWE", 716, "Zimbabwe", "Harare", "Africa/Harare"),
+];
+
+/*
+* Common, former or long-form country names not covered by the names above
+* (ALIAS, ALPHA-2)
+*/
+pub const COUNTRY_NAME_ALIASES: [(&str, &str); 36] = [
+  ("United States of America", "US"),
+  ("America", "US"),
+  ("Great Britain", "GB"),
+  ("Britain", "GB"),
+  ("England", "GB"),
+  ("Scotland", "GB"),
+  ("Wales", "GB"),
+  ("Northern Ireland", "GB"),
+  ("Holland", "NL"),
+  ("The Netherlands", "NL"),
+  ("Czech Republic", "CZ"),
+  ("Swaziland", "SZ"),
+  ("Burma", "MM"),
+  ("East Timor", "TL"),
+  ("Cape Verde", "CV"),
+  ("Macedonia", "MK"),
+  ("Türkiye", "TR"),
+  ("Russian Federation", "RU"),
+  ("Korea", "KR"),
+  ("Republic of Korea", "KR"),
+  ("Vatican", "VA"),
+  ("Holy See", "VA"),
+  ("Côte d'Ivoire", "CI"),
+  ("UAE", "AE"),
+  ("Congo", "CG"),
+  ("DR Congo", "CD"),
+  ("DRC", "CD"),
+  ("Persia", "IR"),
+  ("Ceylon", "LK"),
+  ("Kampuchea", "KH"),
+  ("Zaire", "CD"),
+  ("Eire", "IE"),
+  ("Bahamas, The", "BS"),
+  ("The Gambia", "GM"),
+  ("Moldavia", "MD"),
+  ("Byelorussia", "BY"),
+];
@@ -0,0 +1,448 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use mysql::*;
+use mysql::prelude::*;
+use super::mysql::{connect_mysql, escape_sql};
+
+const IMPORT_BATCH_SIZE: usize = 500;
+
+/*
+* Tables for the GeoNames dumps from https://download.geonames.org/export/dump/
+*/
+pub const GEONAMES_TABLES_SQL: [&str; 5] = [
+  "CREATE TABLE IF NOT EXISTS `geonames` (
+    `geoname_id` int(11) unsigned NOT NULL,
+    `name` varchar(200) NOT NULL,
+    `ascii_name` varchar(200) NOT NULL,
+    `lat` decimal(10,5) NOT NULL,
+    `lng` decimal(10,5) NOT NULL,
+    `feature_class` char(1) NOT NULL DEFAULT '',
+    `feature_code` varchar(10) NOT NULL DEFAULT '',
+    `cc` char(2) NOT NULL DEFAULT '',
+    `admin1_code` varchar(20) NOT NULL DEFAULT '',
+    `admin2_code` varchar(80) NOT NULL DEFAULT '',
+    `population` bigint(20) NOT NULL DEFAULT 0,
+    `zone_name` varchar(64) NOT NULL DEFAULT '',
+    `modified` date DEFAULT NULL,
+    PRIMARY KEY (`geoname_id`),
+    KEY `ascii_name` (`ascii_name`),
+    KEY `name` (`name`),
+    KEY `cc_admin1` (`cc`, `admin1_code`),
+    KEY `lat_lng` (`lat`, `lng`)
+  ) DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci",
+  "CREATE TABLE IF NOT EXISTS `alternate_names` (
+    `id` int(11) unsigned NOT NULL AUTO_INCREMENT,
+    `geoname_id` int(11) unsigned NOT NULL DEFAULT 0,
+    `lang` varchar(7) NOT NULL DEFAULT '',
+    `alternate_name` varchar(400) NOT NULL,
+    `canonical_name` varchar(200) NOT NULL DEFAULT '',
+    `preferred` tinyint(1) NOT NULL DEFAULT 0,
+    `short` tinyint(1) NOT NULL DEFAULT 0,
+    `colloquial` tinyint(1) NOT NULL DEFAULT 0,
+    `historic` tinyint(1) NOT NULL DEFAULT 0,
+    `valid_from` varchar(20) NOT NULL DEFAULT '',
+    `valid_to` varchar(20) NOT NULL DEFAULT '',
+    PRIMARY KEY (`id`),
+    KEY `geoname_id` (`geoname_id`),
+    KEY `alternate_name` (`alternate_name`(64)),
+    KEY `canonical_name` (`canonical_name`),
+    KEY `geoname_lang` (`geoname_id`, `lang`)
+  ) AUTO_INCREMENT=1000000000 DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci",
+  "CREATE TABLE IF NOT EXISTS `admin1_codes` (
+    `code` varchar(30) NOT NULL,
+    `name` varchar(200) NOT NULL,
+    `ascii_name` varchar(200) NOT NULL,
+    `geoname_id` int(11) unsigned NOT NULL DEFAULT 0,
+    PRIMARY KEY (`code`)
+  ) DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci",
+  "CREATE TABLE IF NOT EXISTS `admin2_codes` (
+    `code` varchar(100) NOT NULL,
+    `name` varchar(200) NOT NULL,
+    `ascii_name` varchar(200) NOT NULL,
+    `geoname_id` int(11) unsigned NOT NULL DEFAULT 0,
+    PRIMARY KEY (`code`)
+  ) DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci",
+  "CREATE TABLE IF NOT EXISTS `country_info` (
+    `cc` char(2) NOT NULL,
+    `iso3` char(3) NOT NULL DEFAULT '',
+    `iso_numeric` int(11) NOT NULL DEFAULT 0,
+    `name` varchar(200) NOT NULL,
+    `capital` varchar(200) NOT NULL DEFAULT '',
+    `continent` char(2) NOT NULL DEFAULT '',
+    `languages` varchar(200) NOT NULL DEFAULT '',
+    `geoname_id` int(11) unsigned NOT NULL DEFAULT 0,
+    `neighbours` varchar(100) NOT NULL DEFAULT '',
+    PRIMARY KEY (`cc`)
+  ) DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci",
+];
+
+/*
+* Columns and keys added to alternate_names after it was first created, with the clauses to add them to existing tables
+*/
+const ALTERNATE_NAMES_COLUMNS: [(&str, &str); 4] = [
+  ("canonical_name", "ADD COLUMN `canonical_name` varchar(200) NOT NULL DEFAULT '' AFTER `alternate_name`"),
+  ("preferred", "ADD COLUMN `preferred` tinyint(1) NOT NULL DEFAULT 0 AFTER `canonical_name`"),
+  ("short", "ADD COLUMN `short` tinyint(1) NOT NULL DEFAULT 0 AFTER `preferred`"),
+  ("colloquial", "ADD COLUMN `colloquial` tinyint(1) NOT NULL DEFAULT 0 AFTER `short`"),
+];
+
+const ALTERNATE_NAMES_KEYS: [(&str, &str); 2] = [
+  ("canonical_name", "ADD KEY `canonical_name` (`canonical_name`)"),
+  ("geoname_lang", "ADD KEY `geoname_lang` (`geoname_id`, `lang`)"),
+];
+
+/*
+* Bring an alternate_names table created by an earlier version up to date.
+* Curated entries added afterwards get ids from 1000000000 up as in new tables.
+*/
+pub fn migrate_alternate_names(conn: &mut PooledConn) -> Result<()> {
+  let mut clauses: Vec<&str> = vec![];
+  for (column, clause) in ALTERNATE_NAMES_COLUMNS {
+    let count: Option<u32> = conn.query_first(format!("SELECT COUNT(*) FROM information_schema.COLUMNS WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = 'alternate_names' AND COLUMN_NAME = '{}'", column))?;
+    if count.unwrap_or(0) < 1 {
+      clauses.push(clause);
+    }
+  }
+  let added_columns = !clauses.is_empty();
+  for (key, clause) in ALTERNATE_NAMES_KEYS {
+    let count: Option<u32> = conn.query_first(format!("SELECT COUNT(*) FROM information_schema.STATISTICS WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = 'alternate_names' AND INDEX_NAME = '{}'", key))?;
+    if count.unwrap_or(0) < 1 {
+      clauses.push(clause);
+    }
+  }
+  if !clauses.is_empty() {
+    conn.query_drop(format!("ALTER TABLE alternate_names {}", clauses.join(", ")))?;
+    println!("alternate_names: {}", clauses.join(", "));
+  }
+  if added_columns {
+    conn.query_drop("ALTER TABLE alternate_names AUTO_INCREMENT=1000000000")?;
+  }
+  Ok(())
+}
+
+/*
+* Options for importing allCountries.txt or a country extract such as GB.txt
+*/
+pub struct ImportOptions {
+  // feature classes to keep, e.g. P and A, or all if empty
+  pub feature_classes: Vec<String>,
+  pub min_population: i64,
+}
+
+impl ImportOptions {
+  pub fn from_env() -> ImportOptions {
+    let feature_classes = dotenv::var("geonames_import_classes").unwrap_or("P,A".to_owned())
+      .split(',').map(|c| c.trim().to_uppercase()).filter(|c| !c.is_empty()).collect();
+    let min_population = dotenv::var("geonames_import_min_population").ok().and_then(|v| v.parse::<i64>().ok()).unwrap_or(0);
+    ImportOptions { feature_classes, min_population }
+  }
+
+  fn accepts(&self, feature_class: &str, feature_code: &str, population: i64) -> bool {
+    let class_ok = self.feature_classes.is_empty() || self.feature_classes.iter().any(|c| c == feature_class);
+    // administrative areas and capitals are kept regardless of population
+    let pop_ok = population >= self.min_population || feature_class == "A" || feature_code == "PPLC";
+    class_ok && pop_ok
+  }
+}
+
+fn sql_string(value: &str) -> String {
+  format!("'{}'", escape_sql(value))
+}
+
+fn sql_int(value: &str) -> String {
+  value.trim().parse::<i64>().unwrap_or(0).to_string()
+}
+
+fn sql_decimal(value: &str) -> String {
+  value.trim().parse::<f64>().unwrap_or(0f64).to_string()
+}
+
+fn sql_date(value: &str) -> String {
+  if value.len() == 10 { sql_string(value) } else { "NULL".to_owned() }
+}
+
+fn sql_flag(value: &str) -> &'static str {
+  if value.trim() == "1" { "1" } else { "0" }
+}
+
+/*
+* Insert rows as multi-row statements, replacing existing rows with the same key
+*/
+fn flush_rows(conn: &mut PooledConn, table: &str, columns: &str, rows: &mut Vec<String>) -> Result<usize> {
+  let count = rows.len();
+  if count > 0 {
+    let sql = format!("REPLACE INTO `{}` ({}) VALUES {}", table, columns, rows.join(","));
+    conn.query_drop(sql)?;
+    rows.clear();
+  }
+  Ok(count)
+}
+
+/*
+* Read a tab-separated dump, skipping comments, and insert the values built for each row
+*/
+fn import_tsv<F>(conn: &mut PooledConn, path: &Path, table: &str, columns: &str, mut build: F) -> Result<usize> where F: FnMut(&[&str]) -> Option<String> {
+  let file = File::open(path)?;
+  let mut rows: Vec<String> = Vec::with_capacity(IMPORT_BATCH_SIZE);
+  let mut total = 0;
+  for line in BufReader::new(file).lines() {
+    let line = line?;
+    if line.starts_with('#') || line.trim().is_empty() {
+      continue;
+    }
+    let cols: Vec<&str> = line.split('\t').collect();
+    if let Some(values) = build(&cols) {
+      rows.push(values);
+      if rows.len() >= IMPORT_BATCH_SIZE {
+        total += flush_rows(conn, table, columns, &mut rows)?;
+      }
+    }
+  }
+  total += flush_rows(conn, table, columns, &mut rows)?;
+  Ok(total)
+}
+
+/*
+* Values for one row of allCountries.txt, noting its id, or None if it is filtered out
+*/
+fn build_geonames_row(c: &[&str], options: &ImportOptions, ids: &mut HashSet<u32>) -> Option<String> {
+  if c.len() < 19 {
+    return None;
+  }
+  let population = c[14].parse::<i64>().unwrap_or(0);
+  if !options.accepts(c[6], c[7], population) {
+    return None;
+  }
+  let id = c[0].parse::<u32>().ok()?;
+  ids.insert(id);
+  Some(format!("({},{},{},{},{},{},{},{},{},{},{},{},{})",
+    id, sql_string(c[1]), sql_string(c[2]), sql_decimal(c[4]), sql_decimal(c[5]), sql_string(c[6]), sql_string(c[7]),
+    sql_string(c[8]), sql_string(c[10]), sql_string(c[11]), population, sql_string(c[17]), sql_date(c[18])))
+}
+
+pub fn import_geonames_file(conn: &mut PooledConn, path: &Path, options: &ImportOptions, ids: &mut HashSet<u32>) -> Result<usize> {
+  let columns = "geoname_id, name, ascii_name, lat, lng, feature_class, feature_code, cc, admin1_code, admin2_code, population, zone_name, modified";
+  import_tsv(conn, path, "geonames", columns, |c| build_geonames_row(c, options, ids))
+}
+
+/*
+* Import alternate names for imported places only, skipping links, Wikidata IDs and postal codes
+*/
+fn build_alternate_name_row(c: &[&str], ids: &HashSet<u32>) -> Option<String> {
+  if c.len() < 8 {
+    return None;
+  }
+  let geoname_id = c[1].parse::<u32>().ok()?;
+  if !ids.contains(&geoname_id) || matches!(c[2], "link" | "wkdt" | "post" | "unlc" | "fr_1793") {
+    return None;
+  }
+  let valid_from = c.get(8).copied().unwrap_or("");
+  let valid_to = c.get(9).copied().unwrap_or("");
+  Some(format!("({},{},{},{},{},{},{},{},{},{})",
+    sql_int(c[0]), geoname_id, sql_string(c[2]), sql_string(c[3]), sql_flag(c[4]), sql_flag(c[5]), sql_flag(c[6]), sql_flag(c[7]),
+    sql_string(valid_from), sql_string(valid_to)))
+}
+
+pub fn import_alternate_names(conn: &mut PooledConn, path: &Path, ids: &HashSet<u32>) -> Result<usize> {
+  let columns = "id, geoname_id, lang, alternate_name, preferred, short, colloquial, historic, valid_from, valid_to";
+  import_tsv(conn, path, "alternate_names", columns, |c| build_alternate_name_row(c, ids))
+}
+
+/*
+* Split a CSV line, allowing double-quoted fields with commas and doubled quotes
+*/
+pub fn split_csv_line(line: &str) -> Vec<String> {
+  let mut cols: Vec<String> = vec![];
+  let mut current = String::new();
+  let mut quoted = false;
+  let mut chars = line.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '"' if quoted && chars.peek() == Some(&'"') => {
+        current.push('"');
+        chars.next();
+      },
+      '"' => quoted = !quoted,
+      ',' if !quoted => cols.push(std::mem::take(&mut current)),
+      _ => current.push(c)
+    }
+  }
+  cols.push(current);
+  cols.into_iter().map(|c| c.trim().to_owned()).collect()
+}
+
+/*
+* Import curated alternate names from a CSV file with a header row and the columns
+* alternate_name, canonical_name, lang, historic, valid_from, valid_to and optionally geoname_id.
+* Entries get ids from 1000000000 up, so they do not clash with GeoNames alternate name ids.
+*/
+pub fn import_alternate_names_csv(conn: &mut PooledConn, path: &Path) -> Result<usize> {
+  let columns = "geoname_id, lang, alternate_name, canonical_name, historic, valid_from, valid_to";
+  let file = File::open(path)?;
+  let mut rows: Vec<String> = vec![];
+  let mut total = 0;
+  for line in BufReader::new(file).lines().skip(1) {
+    let line = line?;
+    if line.starts_with('#') || line.trim().is_empty() {
+      continue;
+    }
+    let c = split_csv_line(&line);
+    if c.len() < 6 || c[0].is_empty() {
+      continue;
+    }
+    let geoname_id = c.get(6).map(|id| sql_int(id)).unwrap_or("0".to_owned());
+    // skip rows already imported with the same name, current name and period
+    let exists: Option<u32> = conn.query_first(format!("SELECT id FROM alternate_names WHERE alternate_name = {} AND canonical_name = {} AND valid_from = {} AND valid_to = {}",
+      sql_string(&c[0]), sql_string(&c[1]), sql_string(&c[4]), sql_string(&c[5])))?;
+    if exists.is_none() {
+      rows.push(format!("({},{},{},{},{},{},{})", geoname_id, sql_string(&c[2]), sql_string(&c[0]), sql_string(&c[1]), sql_flag(&c[3]), sql_string(&c[4]), sql_string(&c[5])));
+    }
+    if rows.len() >= IMPORT_BATCH_SIZE {
+      total += flush_rows(conn, "alternate_names", columns, &mut rows)?;
+    }
+  }
+  total += flush_rows(conn, "alternate_names", columns, &mut rows)?;
+  Ok(total)
+}
+
+pub fn import_admin_codes(conn: &mut PooledConn, path: &Path, table: &str) -> Result<usize> {
+  import_tsv(conn, path, table, "code, name, ascii_name, geoname_id", |c| {
+    if c.len() < 4 {
+      return None;
+    }
+    Some(format!("({},{},{},{})", sql_string(c[0]), sql_string(c[1]), sql_string(c[2]), sql_int(c[3])))
+  })
+}
+
+pub fn import_country_info(conn: &mut PooledConn, path: &Path) -> Result<usize> {
+  let columns = "cc, iso3, iso_numeric, name, capital, continent, languages, geoname_id, neighbours";
+  import_tsv(conn, path, "country_info", columns, |c| {
+    if c.len() < 18 {
+      return None;
+    }
+    Some(format!("({},{},{},{},{},{},{},{},{})",
+      sql_string(c[0]), sql_string(c[1]), sql_int(c[2]), sql_string(c[4]), sql_string(c[5]), sql_string(c[8]),
+      sql_string(c[15]), sql_int(c[16]), sql_string(c[17])))
+  })
+}
+
+/*
+* Load the dumps found in a directory: allCountries.txt (or the first country extract such as GB.txt),
+* alternateNamesV2.txt, admin1CodesASCII.txt, admin2Codes.txt and countryInfo.txt.
+* Alternate names are only imported in the same run as the places they refer to.
+* A path to a CSV file imports curated alternate names instead.
+*/
+pub fn import_geonames_dumps(dir: &str) -> Result<()> {
+  let dir = Path::new(dir);
+  let mut conn = connect_mysql()?;
+  for sql in GEONAMES_TABLES_SQL {
+    conn.query_drop(sql)?;
+  }
+  migrate_alternate_names(&mut conn)?;
+  if dir.is_file() && dir.extension().map(|ext| ext == "csv").unwrap_or(false) {
+    let count = import_alternate_names_csv(&mut conn, dir)?;
+    println!("{}: {} alternate names", dir.display(), count);
+    return Ok(());
+  }
+  let options = ImportOptions::from_env();
+  let mut ids: HashSet<u32> = HashSet::new();
+  let places_path = if dir.join("allCountries.txt").exists() {
+    Some(dir.join("allCountries.txt"))
+  } else {
+    std::fs::read_dir(dir)?.filter_map(|e| e.ok()).map(|e| e.path()).find(|p| {
+      let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+      name.len() == 6 && name.ends_with(".txt") && name[0..2].chars().all(|c| c.is_ascii_uppercase())
+    })
+  };
+  if let Some(path) = places_path {
+    let count = import_geonames_file(&mut conn, &path, &options, &mut ids)?;
+    println!("{}: {} places", path.display(), count);
+  }
+  let alternate_path = ["alternateNamesV2.txt", "alternateNames.txt"].iter().map(|n| dir.join(n)).find(|p| p.exists());
+  if let Some(path) = alternate_path {
+    let count = import_alternate_names(&mut conn, &path, &ids)?;
+    println!("{}: {} alternate names", path.display(), count);
+  }
+  for (file_name, table) in [("admin1CodesASCII.txt", "admin1_codes"), ("admin2Codes.txt", "admin2_codes")] {
+    let path = dir.join(file_name);
+    if path.exists() {
+      let count = import_admin_codes(&mut conn, &path, table)?;
+      println!("{}: {} codes", path.display(), count);
+    }
+  }
+  let country_path = dir.join("countryInfo.txt");
+  if country_path.exists() {
+    let count = import_country_info(&mut conn, &country_path)?;
+    println!("{}: {} countries", country_path.display(), count);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn options(classes: &[&str], min_population: i64) -> ImportOptions {
+    ImportOptions { feature_classes: classes.iter().map(|c| c.to_string()).collect(), min_population }
+  }
+
+  #[test]
+  fn csv_fields_may_be_quoted_with_commas_and_doubled_quotes() {
+    assert_eq!(split_csv_line("Königsberg,Kaliningrad,de,1,,1946-07-04"), vec!["Königsberg", "Kaliningrad", "de", "1", "", "1946-07-04"]);
+    assert_eq!(split_csv_line(r#""Danzig, Free City", "The ""Free"" City" ,en"#), vec!["Danzig, Free City", r#"The "Free" City"#, "en"]);
+    assert_eq!(split_csv_line(""), vec![""]);
+  }
+
+  #[test]
+  fn options_keep_admin_areas_and_capitals_below_the_minimum_population() {
+    let opts = options(&["P", "A"], 1000);
+    assert!(opts.accepts("P", "PPL", 1000));
+    assert!(!opts.accepts("P", "PPL", 999));
+    assert!(opts.accepts("P", "PPLC", 0));
+    assert!(opts.accepts("A", "ADM2", 0));
+    assert!(!opts.accepts("H", "LK", 50_000));
+    assert!(options(&[], 0).accepts("H", "LK", 0));
+  }
+
+  #[test]
+  fn values_are_quoted_or_coerced() {
+    assert_eq!(sql_string("Land's End"), "'Land''s End'");
+    assert_eq!(sql_int(" 42 "), "42");
+    assert_eq!(sql_int("n/a"), "0");
+    assert_eq!(sql_decimal("51.50853"), "51.50853");
+    assert_eq!(sql_decimal(""), "0");
+    assert_eq!(sql_date("2023-01-15"), "'2023-01-15'");
+    assert_eq!(sql_date(""), "NULL");
+    assert_eq!(sql_flag("1"), "1");
+    assert_eq!(sql_flag(""), "0");
+  }
+
+  #[test]
+  fn geonames_rows_are_filtered_and_their_ids_noted() {
+    let line = "2643743\tLondon\tLondon\tLondres,Londra\t51.50853\t-0.12574\tP\tPPLC\tGB\t\tENG\tGLA\t\t\t8961989\t\t25\tEurope/London\t2023-01-15";
+    let cols: Vec<&str> = line.split('\t').collect();
+    let mut ids = HashSet::new();
+    let row = build_geonames_row(&cols, &options(&["P"], 10_000_000), &mut ids).unwrap();
+    assert_eq!(row, "(2643743,'London','London',51.50853,-0.12574,'P','PPLC','GB','ENG','GLA',8961989,'Europe/London','2023-01-15')");
+    assert!(ids.contains(&2643743));
+    let mut ids = HashSet::new();
+    assert!(build_geonames_row(&cols, &options(&["A"], 0), &mut ids).is_none());
+    assert!(ids.is_empty());
+    assert!(build_geonames_row(&cols[..18], &options(&[], 0), &mut ids).is_none());
+  }
+
+  #[test]
+  fn alternate_names_skip_unknown_places_and_pseudo_languages() {
+    let ids: HashSet<u32> = [554234].into_iter().collect();
+    let cols = ["1563213", "554234", "de", "Königsberg", "", "", "", "1", "", "1946"];
+    assert_eq!(build_alternate_name_row(&cols, &ids).unwrap(), "(1563213,554234,'de','Königsberg',0,0,0,1,'','1946')");
+    let cols = ["1563214", "554234", "de", "Königsberg", "1", "", "", ""];
+    assert_eq!(build_alternate_name_row(&cols, &ids).unwrap(), "(1563214,554234,'de','Königsberg',1,0,0,0,'','')");
+    let cols = ["1563215", "554234", "link", "https://en.wikipedia.org/wiki/Kaliningrad", "", "", "", ""];
+    assert!(build_alternate_name_row(&cols, &ids).is_none());
+    let cols = ["1563216", "2643743", "en", "London", "1", "", "", ""];
+    assert!(build_alternate_name_row(&cols, &ids).is_none());
+  }
+}
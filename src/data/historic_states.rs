@@ -0,0 +1,36 @@
+/*
+* Sovereign states in place of the current country for a whole country or, if a region is given,
+* for places with a first or second-level division of that name in GeoNames (English names).
+* Region entries take precedence over country entries for the same period.
+* Dates are ISO dates, the end date is exclusive and an empty start date means open-ended.
+* (CURRENT COUNTRY CODE, REGION, SOVEREIGN STATE, STATE CODE, FROM, TO)
+*/
+pub const HISTORIC_SOVEREIGN_STATES: [(&str, &str, &str, &str, &str, &str); 27] = [
+  ("IN", "", "British India", "", "1858-08-02", "1947-08-15"),
+  ("PK", "", "British India", "", "1858-08-02", "1947-08-14"),
+  ("BD", "", "British India", "", "1858-08-02", "1947-08-14"),
+  ("BD", "", "Pakistan", "PK", "1947-08-14", "1971-12-16"),
+  ("MM", "", "British India", "", "1886-01-01", "1937-04-01"),
+  ("IE", "", "United Kingdom", "UK", "1801-01-01", "1922-12-06"),
+  ("HK", "", "British Hong Kong", "UK", "1841-01-26", "1997-07-01"),
+  ("RU", "Kaliningrad", "Germany", "DE", "", "1945-04-09"),
+  ("RU", "", "Russian Empire", "", "", "1917-11-07"),
+  ("RU", "", "Russian SFSR", "", "1917-11-07", "1922-12-30"),
+  ("RU", "", "Soviet Union", "SU", "1922-12-30", "1991-12-26"),
+  ("UA", "", "Soviet Union", "SU", "1922-12-30", "1991-08-24"),
+  ("BY", "", "Soviet Union", "SU", "1922-12-30", "1991-08-25"),
+  ("PL", "Lower Silesia", "Germany", "DE", "", "1945-08-02"),
+  ("PL", "Opole Voivodeship", "Germany", "DE", "", "1945-08-02"),
+  ("PL", "Warmia-Masuria", "Germany", "DE", "", "1945-08-02"),
+  ("FR", "Bas-Rhin", "German Empire", "DE", "1871-05-10", "1918-11-11"),
+  ("FR", "Haut-Rhin", "German Empire", "DE", "1871-05-10", "1918-11-11"),
+  ("FR", "Moselle", "German Empire", "DE", "1871-05-10", "1918-11-11"),
+  ("DE", "Brandenburg", "East Germany", "DD", "1949-10-07", "1990-10-03"),
+  ("DE", "Saxony", "East Germany", "DD", "1949-10-07", "1990-10-03"),
+  ("DE", "Saxony-Anhalt", "East Germany", "DD", "1949-10-07", "1990-10-03"),
+  ("DE", "Thuringia", "East Germany", "DD", "1949-10-07", "1990-10-03"),
+  ("DE", "Mecklenburg-Vorpommern", "East Germany", "DD", "1949-10-07", "1990-10-03"),
+  ("DE", "", "West Germany", "DE", "1949-05-23", "1990-10-03"),
+  ("VN", "Ho Chi Minh", "South Vietnam", "", "1955-10-26", "1975-04-30"),
+  ("VN", "", "French Indochina", "", "1887-10-17", "1954-07-21"),
+];
@@ -1,2 +1,8 @@
 pub mod mysql;
-pub mod alternative_names;
\ No newline at end of file
+pub mod alternative_names;
+pub mod geonames_import;
+pub mod historic_states;
+pub mod zone_overrides;
+pub mod countries;
+pub mod zone_aliases;
+pub mod admin_regions;
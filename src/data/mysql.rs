@@ -84,4 +84,18 @@ pub fn connect_mysql() -> Result<PooledConn> {
           Err(e)
       }
   }
-}
\ No newline at end of file
+}
+
+/*
+* Escape a string value for interpolation within single quotes in an SQL statement
+*/
+pub fn escape_sql(value: &str) -> String {
+  value.replace('\\', "\\\\").replace('\'', "''")
+}
+
+/*
+* Escape a string value for a LIKE pattern within single quotes, so % and _ in user input match literally
+*/
+pub fn escape_like(value: &str) -> String {
+  escape_sql(&value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"))
+}
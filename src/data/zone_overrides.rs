@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use mysql::*;
+use mysql::prelude::*;
+use super::mysql::{connect_mysql, escape_sql};
+use super::geonames_import::split_csv_line;
+
+/*
+* Zones to use instead of the current zone for points within a polygon during a period,
+* e.g. where borders or time zone assignments have changed
+*/
+pub const ZONE_OVERRIDES_TABLE_SQL: &str = "CREATE TABLE IF NOT EXISTS `zone_overrides` (
+    `id` int(11) unsigned NOT NULL AUTO_INCREMENT,
+    `name` varchar(100) NOT NULL,
+    `zone_name` varchar(64) NOT NULL,
+    `valid_from` varchar(20) NOT NULL DEFAULT '',
+    `valid_to` varchar(20) NOT NULL DEFAULT '',
+    `min_lat` decimal(10,5) NOT NULL,
+    `max_lat` decimal(10,5) NOT NULL,
+    `min_lng` decimal(10,5) NOT NULL,
+    `max_lng` decimal(10,5) NOT NULL,
+    `polygon` text NOT NULL,
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `name_period` (`name`, `valid_from`, `valid_to`)
+  ) DEFAULT CHARSET=utf8mb4 COLLATE=utf8mb4_general_ci";
+
+/*
+* Parse a polygon written as lng lat pairs separated by semicolons
+*/
+pub fn parse_polygon(text: &str) -> Vec<(f64, f64)> {
+  text.split(';').filter_map(|pair| {
+    let mut parts = pair.split_whitespace().map(|v| v.parse::<f64>());
+    match (parts.next(), parts.next()) {
+      (Some(Ok(lng)), Some(Ok(lat))) => Some((lng, lat)),
+      _ => None
+    }
+  }).collect()
+}
+
+/*
+* Import zone overrides from a CSV file with a header row and the columns
+* name, zone_name, valid_from, valid_to and polygon. Rows with the same name and period are replaced.
+*/
+pub fn import_zone_overrides(path: &str) -> Result<usize> {
+  let mut conn = connect_mysql()?;
+  conn.query_drop(ZONE_OVERRIDES_TABLE_SQL)?;
+  let file = File::open(Path::new(path))?;
+  let mut total = 0;
+  for line in BufReader::new(file).lines().skip(1) {
+    let line = line?;
+    if line.starts_with('#') || line.trim().is_empty() {
+      continue;
+    }
+    let c = split_csv_line(&line);
+    if c.len() < 5 {
+      continue;
+    }
+    let points = parse_polygon(&c[4]);
+    if points.len() < 3 {
+      continue;
+    }
+    let lats = points.iter().map(|p| p.1);
+    let lngs = points.iter().map(|p| p.0);
+    let (min_lat, max_lat) = (lats.clone().fold(f64::MAX, f64::min), lats.fold(f64::MIN, f64::max));
+    let (min_lng, max_lng) = (lngs.clone().fold(f64::MAX, f64::min), lngs.fold(f64::MIN, f64::max));
+    conn.query_drop(format!("REPLACE INTO zone_overrides (name, zone_name, valid_from, valid_to, min_lat, max_lat, min_lng, max_lng, polygon) VALUES ('{}', '{}', '{}', '{}', {}, {}, {}, {}, '{}')",
+      escape_sql(&c[0]), escape_sql(&c[1]), escape_sql(&c[2]), escape_sql(&c[3]), min_lat, max_lat, min_lng, max_lng, escape_sql(&c[4])))?;
+    total += 1;
+  }
+  Ok(total)
+}
@@ -12,8 +12,10 @@ mod routes;
 
 use args::*;
 use clap::Parser;
-use actix_web::{App, HttpServer, web::{self}};
+use actix_web::{App, HttpServer, dev::Service, http::header::{HeaderName, HeaderValue}, web::{self}};
 use routes::*;
+use app::app_cache::with_cache_trace;
+use services::geonames_quota::with_geonames_limit_trace;
 
 fn match_port() -> u16 {
   let args = Args::parse();
@@ -31,16 +33,62 @@ fn match_port() -> u16 {
 
 #[actix_web::main]
 async fn main()  -> std::io::Result<()> {
+    let args = Args::parse();
+    if !args.import.is_empty() {
+      if let Err(e) = data::geonames_import::import_geonames_dumps(&args.import) {
+        println!("{:?}", e);
+      }
+      return Ok(());
+    }
+    if !args.overrides.is_empty() {
+      match data::zone_overrides::import_zone_overrides(&args.overrides) {
+        Ok(count) => println!("{}: {} zone overrides", args.overrides, count),
+        Err(e) => println!("{:?}", e)
+      }
+      return Ok(());
+    }
     let port = match_port();
-    
+    // build the locality search index in the background rather than on the first /localities request
+    std::thread::spawn(services::locality_index::warm_locality_index);
+    // expire and trim application cache entries in the background rather than on each write
+    std::thread::spawn(app::app_cache::run_cache_sweeper);
+
     HttpServer::new(move || {
         App::new()
+        .wrap_fn(|req, srv| {
+          let future = srv.call(req);
+          async move {
+            // report whether application cache lookups made by the handler were hits
+            let (result, trace) = with_cache_trace(with_geonames_limit_trace(future)).await;
+            let mut res = result?;
+            if let Some(status) = trace.status() {
+              res.headers_mut().insert(HeaderName::from_static("x-cache"), HeaderValue::from_static(status));
+            }
+            Ok(res)
+          }
+        })
         .route("/", web::get().to(welcome))
         .service(tz_info)
         .service(geo_time_info)
         .service(search_by_name)
         .service(lookup_by_name)
         .service(lookup_by_locality_name)
+        .service(nearby_localities)
+        .service(country_registry)
+        .service(country_zone_info)
+        .service(zone_catalogue)
+        .service(zone_metadata)
+        .service(abbreviation_info)
+        .service(offset_zones)
+        .service(recurrence_info)
+        .service(vtimezone_export)
+        .service(posix_tz_info)
+        .service(tzif_export)
+        .service(geonames_quota_info)
+        .service(app_cache_info)
+        .service(purge_app_cache)
+        .service(list_alternate_names)
+        .service(add_alternate_name)
         .route("/{sec1}", web::get().to(route_not_found))
         .route("/{sec1}/{sec2}", web::get().to(route_not_found))
         .route("/{sec1}/{sec2}/{sec3}", web::get().to(route_not_found))
@@ -1,7 +1,7 @@
 
 use serde::Deserialize;
 use actix_web::web::Query;
-use super::app::{date_conv::*, coords::*};
+use super::app::{date_conv::*, coords::*, relative_dates::*};
 
 #[derive(Deserialize)]
 pub struct InputOptions {
@@ -9,7 +9,17 @@ pub struct InputOptions {
   pub dtl: Option<String>, // primary date string in local time
   pub jd: Option<f64>, // primary jd as a float
   pub un: Option<i64>, // primary unix timestamp as an integer
+  pub gps: Option<String>, // GPS time as week,seconds or seconds since the GPS epoch
+  pub mjd: Option<f64>, // Modified Julian Date
+  pub xls: Option<f64>, // Excel serial date (1900 date system)
+  pub ntp: Option<f64>, // NTP timestamp in seconds since 1900
+  pub ft: Option<i64>, // Windows FILETIME in 100-nanosecond intervals since 1601
+  pub mac: Option<f64>, // Mac absolute time in seconds since 2001
+  pub epochs: Option<u8>, // 1: add the reference time in all supported epoch formats
+  pub rule: Option<String>, // RRULE-like recurrence, e.g. FREQ=WEEKLY;BYDAY=MO,WE;COUNT=10
   pub zn: Option<String>, // comma-separated lat,lng(,alt) numeric string
+  pub abbr: Option<String>, // time zone abbreviation, e.g. IST or CEST
+  pub offset: Option<String>, // UTC offset, e.g. +05:45 or -0330
   pub loc: Option<String>, // comma-separated lat,lng(,alt) numeric string
   pub place: Option<String>, // simple string
   pub cc: Option<String>, // country code
@@ -17,7 +27,12 @@ pub struct InputOptions {
   pub mode: Option<String>, // all: all features, default cities and regions / countries only for search endpoint
   pub fuzzy: Option<u8>, // fuzziness on a scale from 0 to 100
   pub max: Option<u8>, // max rows returned in the /lookup route, default is 20
+  pub radius: Option<f64>, // search radius in km for the /nearby route
+  pub pop: Option<u32>, // minimum population for the /nearby route
   pub included: Option<u8>, // Default: 1 (true), 0: false. Place name includes the search string, not just a district of a larger metropolis or region
+  pub provider: Option<String>, // geocoding provider for place name searches: geonames, nominatim or photon
+  pub lang: Option<String>, // ISO 639 language code for localised place names, e.g. de
+  pub hist: Option<u8>, // Default 1: add names and sovereign states in use on past dates to /geotime place names, 0: omit
   pub dst: Option<u8>, // Default 1: apply in the overlap, 0: do not apply when in the overlap hour when clocks go back, 
 }
 
@@ -32,7 +47,7 @@ pub fn match_datetime_from_params(params:&Query<InputOptions>) -> (String, bool)
   if !has_dt {
     dt_str = params.dtl.clone().unwrap_or("".to_string());
     has_dt = is_valid_date_string(&dt_str);
-    local = true;
+    local = has_dt;
   }
   let jd = if has_dt { 0f64 } else { params.jd.clone().unwrap_or(0f64) };
   let has_jd = jd > 2_000_000f64;
@@ -44,6 +59,8 @@ pub fn match_datetime_from_params(params:&Query<InputOptions>) -> (String, bool)
     let un = params.un.clone().unwrap_or(min_unix_ts);
     if un > min_unix_ts && un <= max_unix_ts {
       dt_str = unixtime_to_utc(un);
+    } else if let Some(ts) = match_epoch_timestamp_from_params(params) {
+      dt_str = unixtime_to_utc(ts);
     } else {
       dt_str = current_datetime_string();
     }
@@ -51,6 +68,56 @@ pub fn match_datetime_from_params(params:&Query<InputOptions>) -> (String, bool)
   (iso_string_to_datetime(dt_str.as_str()).to_string().replace(" ", "T"), local)
 }
 
+/*
+* Whether a reference date-time is given in any form, as opposed to defaulting to the current time
+*/
+pub fn has_datetime_param(params:&Query<InputOptions>) -> bool {
+  [&params.dt, &params.dtl].into_iter().any(|dt| dt.as_deref().map(is_valid_date_string).unwrap_or(false))
+    || params.jd.unwrap_or(0f64) > 2_000_000f64
+    || params.un.is_some()
+    || match_epoch_timestamp_from_params(params).is_some()
+}
+
+/*
+* Match relative or natural-language expressions in dt (UTC) or dtl (local time), e.g. now+3h or next monday 09:00
+*/
+pub fn match_relative_datetime_from_params(params:&Query<InputOptions>) -> Option<(RelativeDateTime, bool)> {
+  if let Some(rel) = params.dt.clone().and_then(|dt_str| RelativeDateTime::parse(&dt_str)) {
+    Some((rel, false))
+  } else {
+    params.dtl.clone().and_then(|dt_str| RelativeDateTime::parse(&dt_str)).map(|rel| (rel, true))
+  }
+}
+
+fn parse_gps_time(gps_str: &str) -> Option<i64> {
+  let parts: Vec<f64> = gps_str.split(",").filter_map(|p| p.trim().parse::<f64>().ok()).collect();
+  match parts.len() {
+    1 => Some(gps_time_to_unixtime(0, parts[0])),
+    2 => Some(gps_time_to_unixtime(parts[0] as i64, parts[1])),
+    _ => None
+  }
+}
+
+/*
+* Match alternative epoch-based inputs in order of precedence and normalise them to a unix timestamp.
+* These are always interpreted as UTC instants
+*/
+pub fn match_epoch_timestamp_from_params(params:&Query<InputOptions>) -> Option<i64> {
+  if let Some(ts) = params.gps.clone().and_then(|gps_str| parse_gps_time(&gps_str)) {
+    Some(ts)
+  } else if let Some(mjd) = params.mjd {
+    Some(mjd_to_unixtime(mjd))
+  } else if let Some(serial) = params.xls {
+    Some(excel_serial_to_unixtime(serial))
+  } else if let Some(ntp) = params.ntp {
+    Some(ntp_to_unixtime(ntp))
+  } else if let Some(ft) = params.ft {
+    Some(filetime_to_unixtime(ft))
+  } else {
+    params.mac.map(mac_absolute_to_unixtime)
+  }
+}
+
 pub fn match_coords_from_params(params:&Query<InputOptions>) -> Option<Coords> {
   let coord_str: String = params.loc.clone().unwrap_or("".to_string());
   let has_coords = coord_str.contains(",");
@@ -1,8 +1,14 @@
-use crate::services::{timezonedb::*, geonames::*};
+use crate::services::{timezonedb::*, geonames::*, geonames_quota::*, zone_memo::*, geocoder::{PlaceQuery, geocoding_provider}, reverse_geocoder::match_nearby_localities, locality_index::clear_locality_index, alternate_names::*, zone_overrides::clear_zone_overrides, countries::*, zone_catalogue::*, zone_abbreviations::*};
 use serde_json::*;
-use actix_web::{get, Responder, web::{Query, Json}};
+use actix_web::{get, post, delete, Responder, HttpRequest, HttpResponse, web::{Query, Json}};
 use crate::query_params::*;
 use crate::app::coords::*;
+use crate::app::recurrence::*;
+use crate::app::vtimezone::*;
+use crate::app::posix_tz::*;
+use crate::app::tzif::*;
+use crate::app::date_conv::*;
+use crate::app::app_cache::*;
 
 pub async fn welcome() -> impl Responder {
   Json(json!({ "message": "Welcome to GeoTImeZone" }))
@@ -12,8 +18,17 @@ pub async fn route_not_found() -> impl Responder {
   Json( json!({ "valid": false, "error": "route not found" }))
 }
 
+/*
+* 503 response when every GeoNames account has exhausted its quota
+*/
+fn geonames_unavailable(retry_after: i64) -> HttpResponse {
+  HttpResponse::ServiceUnavailable()
+    .insert_header(("Retry-After", retry_after.to_string()))
+    .json(json!({ "valid": false, "message": "The GeoNames quota is exhausted, please try again later", "retryAfter": retry_after }))
+}
+
 #[get("/geotime")]
-pub async fn geo_time_info(params: Query<InputOptions>) -> impl Responder {
+pub async fn geo_time_info(params: Query<InputOptions>) -> HttpResponse {
   let mut coords_option = match_coords_from_params(&params);
   let has_coords = coords_option.is_some();
   if !has_coords { 
@@ -26,31 +41,78 @@ pub async fn geo_time_info(params: Query<InputOptions>) -> impl Responder {
     Some(cs) => cs,
     _ => Coords::zero()
   };
-  let (corrected_dt, local) = match_datetime_from_params(&params);
+  let (mut corrected_dt, mut local) = match_datetime_from_params(&params);
   let enforce_dst = params.dst.unwrap_or(1) > 0;
   reset_override();
+  let mut relative = None;
+  if let Some((rel, rel_local)) = match_relative_datetime_from_params(&params) {
+    let (utc_dt, interpretation) = resolve_relative_datetime_at_coords(&rel, rel_local, coords, enforce_dst).await;
+    corrected_dt = utc_dt;
+    local = false;
+    relative = Some(interpretation);
+  }
   let adjusted_dt = if local { fetch_adjusted_date_str(coords.lat, coords.lng, &corrected_dt, enforce_dst).await } else { corrected_dt.clone() };
 
-  let info = fetch_geo_time_info(coords.lat, coords.lng, &adjusted_dt, enforce_dst).await;
-  Json(json!(info))
+  let mut info = fetch_geo_time_info(coords.lat, coords.lng, &adjusted_dt, enforce_dst).await;
+  if info.is_empty() {
+    if let Some(retry_after) = geonames_limit_retry_after() {
+      return geonames_unavailable(retry_after);
+    }
+  }
+  if params.epochs.unwrap_or(0) > 0 {
+    info.add_epochs();
+  }
+  if params.hist.unwrap_or(1) > 0 && adjusted_dt.get(0..10) < current_datetime_string().get(0..10) {
+    info.add_historic_names(&adjusted_dt);
+  }
+  if let Some(lang) = match_lang_code(&params.lang) {
+    info.localise(&lang);
+  }
+  if let Some(interpretation) = relative {
+    info.set_relative(interpretation);
+  }
+  HttpResponse::Ok().json(json!(info))
 }
 
 #[get("/timezone")]
-pub async fn tz_info(params: Query<InputOptions>) -> impl Responder {
-  let mut zn: String = params.zn.clone().unwrap_or("".to_string());
+pub async fn tz_info(params: Query<InputOptions>) -> HttpResponse {
+  let (mut zn, abbreviation_zones) = match_zone_name_param(&params);
   let mut has_zn = is_valid_zone_name(&zn);
+  if !has_zn {
+    if let Some(info) = ambiguous_abbreviation_json(&params, &abbreviation_zones) {
+      return HttpResponse::Ok().json(info);
+    }
+  }
   let coords_option = match_coords_from_params(&params);
-  let (corrected_dt, local) = match_datetime_from_params(&params);
+  let (mut corrected_dt, mut local) = match_datetime_from_params(&params);
   let has_coords = coords_option.is_some();
   if !has_zn && !has_coords { 
-    let tz_info_opt = extract_zone_name_from_place_params(&params).await;
-    if let Some((tz_info, _coords)) = tz_info_opt {
-      zn = tz_info.tz;
+    let candidates = match_place_zone_candidates(&params).await;
+    if candidates.iter().any(|c| c.zone_name != candidates[0].zone_name) {
+      return HttpResponse::Ok().json(json!({
+        "valid": false,
+        "message": "The place name matches places in more than one time zone, please specify a region via reg or coordinates via loc",
+        "candidates": candidates
+      }));
+    }
+    if let Some(first) = candidates.first() {
+      zn = first.zone_name.clone();
       has_zn = is_valid_zone_name(&zn);
     }
   }
   let enforce_dst = params.dst.unwrap_or(1) > 0;
   reset_override();
+  let mut relative = None;
+  if let Some((rel, rel_local)) = match_relative_datetime_from_params(&params) {
+    let (utc_dt, interpretation) = if has_zn {
+      resolve_relative_datetime(&rel, rel_local, Some(&zn), 0f64, enforce_dst)
+    } else {
+      resolve_relative_datetime_at_coords(&rel, rel_local, coords_option.unwrap_or(Coords::zero()), enforce_dst).await
+    };
+    corrected_dt = utc_dt;
+    local = false;
+    relative = Some(interpretation);
+  }
   let result = match has_zn {
     true => match_current_time_zone(&zn, &corrected_dt, None, enforce_dst),
     _ => {
@@ -62,34 +124,47 @@ pub async fn tz_info(params: Query<InputOptions>) -> impl Responder {
       fetch_time_info_from_coords_adjusted(ref_coords, &corrected_dt, local, enforce_dst).await
     }
   };
-  let json_info = if let Some(data) = result {
+  let json_info = if let Some(mut data) = result {
+    if params.epochs.unwrap_or(0) > 0 {
+      data.add_epochs();
+    }
+    if let Some(interpretation) = relative {
+      data.set_relative(interpretation);
+    }
     json!(data)
+  } else if let Some(retry_after) = geonames_limit_retry_after() {
+    return geonames_unavailable(retry_after);
   } else {
-    json!({ "valid": false, "message": "Cannot identify a time zone from the query parameters" })
+    unknown_zone_json(&params, &abbreviation_zones)
   };
-  Json(json_info)
+  HttpResponse::Ok().json(json_info)
 }
 
 #[get("/search")]
-pub async fn search_by_name(params: Query<InputOptions>) -> impl Responder {
+pub async fn search_by_name(params: Query<InputOptions>) -> HttpResponse {
   let place: String = params.place.clone().unwrap_or("".to_string());
   let has_search = place.len() > 1;
   let fuzzy_100 = params.fuzzy.unwrap_or(100);
   let fuzzy_opt = if fuzzy_100 < 100 && fuzzy_100 > 0 { Some(fuzzy_100 as f32 / 100f32) } else { None };
-  let cc_str = params.cc.clone().unwrap_or("".to_string());
-  let cc_len = cc_str.len();
-  let cc = if cc_len > 1 && cc_len < 4 { 
-    Some(cc_str.to_uppercase())
-   } else { None };
+  let cc = match_country_param(&params.cc);
    let region = params.reg.clone();
    let max_ref = params.max.unwrap_or(50);
    let max = if max_ref > 0 { max_ref } else { 50 };
   let included = params.included.unwrap_or(1) != 0;
-  let results = if has_search {
-    search_by_fuzzy_names(&place, &cc, &region, fuzzy_opt, false, included, max).await
+  let mut results = if has_search {
+    let query = PlaceQuery::new(&place, &cc, &region, fuzzy_opt, false, included, max);
+    search_places(&query, params.provider.as_deref()).await
   } else {
     vec![]
   };
+  if let Some(lang) = match_lang_code(&params.lang) {
+    results.iter_mut().for_each(|row| row.localise(&lang));
+  }
+  if results.is_empty() && has_search {
+    if let Some(retry_after) = geonames_limit_retry_after() {
+      return geonames_unavailable(retry_after);
+    }
+  }
   let count = results.len();
   let message = if has_search {
     "OK"
@@ -99,49 +174,501 @@ pub async fn search_by_name(params: Query<InputOptions>) -> impl Responder {
   let info = json!({
     "count": count,
     "message": message,
+    "provider": geocoding_provider(params.provider.as_deref()).name(),
     "results": results
   });  
-  Json(json!(info))
+  HttpResponse::Ok().json(json!(info))
 }
 
 #[get("/lookup")]
-pub async fn lookup_by_name(params: Query<InputOptions>) -> impl Responder {
+pub async fn lookup_by_name(params: Query<InputOptions>) -> HttpResponse {
   let place: String = params.place.clone().unwrap_or("".to_string());
   let has_search = place.len() > 1;
   let fuzzy_100 = params.fuzzy.unwrap_or(100);
   let fuzzy_opt = if fuzzy_100 < 100 && fuzzy_100 > 0 { Some(fuzzy_100 as f32 / 100f32) } else { None };
-  let cc_str = params.cc.clone().unwrap_or("".to_string());
-  let cc_len = cc_str.len();
   let max_ref = params.max.unwrap_or(20);
   let max = if max_ref > 0 { max_ref } else { 20 };
-  let cc = if cc_len > 1 && cc_len < 4 { 
-    Some(cc_str.to_uppercase())
-   } else { None };
+  let cc = match_country_param(&params.cc);
   let region = params.reg.clone();
   let results = if has_search {
     //list_by_fuzzy_name_match(&place, &cc, &region, fuzzy_opt, max).await
-    list_by_fuzzy_localities(&place, &cc, &region, fuzzy_opt, max).await
+    list_by_fuzzy_localities(&place, &cc, &region, fuzzy_opt, max, params.provider.as_deref(), match_lang_code(&params.lang).as_deref()).await
   } else {
     vec![]
   };
-  Json(json!(results))
+  if results.is_empty() && has_search {
+    if let Some(retry_after) = geonames_limit_retry_after() {
+      return geonames_unavailable(retry_after);
+    }
+  }
+  HttpResponse::Ok().json(json!(results))
 }
 
 #[get("/localities")]
 pub async fn lookup_by_locality_name(params: Query<InputOptions>) -> impl Responder {
   let place: String = params.place.clone().unwrap_or("".to_string());
   let has_search = place.len() > 1;
-  let cc_str = params.cc.clone().unwrap_or("".to_string());
-  let cc_len = cc_str.len();
   let max_ref = params.max.unwrap_or(20);
   let max = if max_ref > 0 { max_ref } else { 20 };
-  let cc = if cc_len > 1 && cc_len < 4 { 
-    Some(cc_str.to_uppercase())
-   } else { None };
-  let results = if has_search {
-    match_locality(&place, &cc, max)
+  let cc = match_country_param(&params.cc);
+  let fuzzy = params.fuzzy.unwrap_or(100);
+  let mut results = if has_search {
+    match_locality(&place, &cc, &params.reg, max, fuzzy)
+  } else {
+    vec![]
+  };
+  if let Some(lang) = match_lang_code(&params.lang) {
+    results.iter_mut().for_each(|row| row.localise(&lang));
+  }
+  Json(json!(results))
+}
+
+#[get("/nearby")]
+pub async fn nearby_localities(params: Query<InputOptions>) -> impl Responder {
+  let max_ref = params.max.unwrap_or(20);
+  let max = if max_ref > 0 { max_ref } else { 20 };
+  let cc = match_country_param(&params.cc);
+  let mut results = if let Some(coords) = match_coords_from_params(&params) {
+    match_nearby_localities(&coords, params.radius.unwrap_or(0f64), params.pop.unwrap_or(0), cc.as_deref(), max)
   } else {
     vec![]
   };
+  if let Some(lang) = match_lang_code(&params.lang) {
+    results.iter_mut().for_each(|row| row.localise(&lang));
+  }
   Json(json!(results))
-}
\ No newline at end of file
+}
+
+/*
+* Country registry with ISO codes, names, capitals and default zones, or the single country matching cc
+*/
+#[get("/countries")]
+pub async fn country_registry(params: Query<InputOptions>) -> impl Responder {
+  let results = match params.cc.as_ref().filter(|cc| cc.trim().len() > 1) {
+    Some(cc) => find_country(cc).into_iter().collect(),
+    None => list_countries()
+  };
+  Json(json!(results))
+}
+
+/*
+* Zones of a country with their current offsets, next transitions, DST rules and representative cities
+*/
+#[get("/country")]
+pub async fn country_zone_info(params: Query<InputOptions>) -> impl Responder {
+  reset_override();
+  let (date_str, _local) = match_datetime_from_params(&params);
+  let json_info = match params.cc.as_ref().and_then(|cc| find_country(cc)) {
+    Some(country) => {
+      let mut info = match_country_zones(country, match_unix_ts_from_fuzzy_datetime(&date_str));
+      if let Some(lang) = match_lang_code(&params.lang) {
+        info.localise(&lang);
+      }
+      json!(info)
+    },
+    None => json!({ "valid": false, "message": "Please specify a country code or name via ?cc=CODE" })
+  };
+  Json(json_info)
+}
+
+/*
+* Zone name from zn given as a zone name, alias or abbreviation that identifies a single zone or offset at the reference time.
+* If the abbreviation stands for different offsets, the zone name is empty and the candidate zones are returned with it.
+*/
+fn match_zone_name_param(params: &Query<InputOptions>) -> (String, Vec<AbbreviationZone>) {
+  let zn_ref = params.zn.clone().unwrap_or_default();
+  if let Some(zn) = resolve_zone_name(&zn_ref) {
+    return (zn, vec![]);
+  }
+  if is_zone_abbreviation(&zn_ref) {
+    let (date_str, _local) = match_datetime_from_params(params);
+    return match select_abbreviation_zone(&zn_ref, &date_str, match_country_param(&params.cc).as_deref()) {
+      Ok(zn) => (zn, vec![]),
+      Err(zones) => ("".to_string(), zones)
+    };
+  }
+  ("".to_string(), vec![])
+}
+
+/*
+* Error listing the candidate zones and offsets if zn is an abbreviation that stands for more than one offset
+*/
+fn ambiguous_abbreviation_json(params: &Query<InputOptions>, zones: &[AbbreviationZone]) -> Option<Value> {
+  if zones.is_empty() {
+    return None;
+  }
+  let zn = params.zn.clone().unwrap_or_default();
+  let mut info = json!(AbbreviationInfo::new(zn.trim(), zones.to_vec()));
+  info["valid"] = json!(false);
+  info["message"] = json!(format!("The abbreviation {} stands for more than one UTC offset. Please specify a zone name or a country via cc", zn.trim()));
+  Some(info)
+}
+
+/*
+* Zones that used a time zone abbreviation with the offsets it stood for, at a given date or at any time
+*/
+#[get("/abbreviation")]
+pub async fn abbreviation_info(params: Query<InputOptions>) -> impl Responder {
+  reset_override();
+  let abbr = params.abbr.clone().or(params.zn.clone()).unwrap_or_default();
+  let json_info = if is_zone_abbreviation(&abbr) {
+    let date_str = if has_datetime_param(&params) { Some(match_datetime_from_params(&params).0) } else { None };
+    let zones = match_abbreviation_zones(&abbr, date_str.as_deref(), match_country_param(&params.cc).as_deref());
+    json!(AbbreviationInfo::new(&abbr.trim().to_uppercase(), zones))
+  } else {
+    json!({ "valid": false, "message": "Please specify a time zone abbreviation via ?abbr=IST" })
+  };
+  Json(json_info)
+}
+
+/*
+* Zones that had a given UTC offset at a point in time, e.g. to identify the zone behind a logged timestamp.
+* With dtl the time is read as local time at that offset.
+*/
+#[get("/offset")]
+pub async fn offset_zones(params: Query<InputOptions>) -> impl Responder {
+  reset_override();
+  let json_info = match params.offset.as_deref().and_then(parse_utc_offset) {
+    Some(offset) => {
+      let (date_str, local) = match_datetime_from_params(&params);
+      let ts = match_unix_ts_from_fuzzy_datetime(&date_str) - if local { offset as i64 } else { 0 };
+      let zones = match_zones_by_offset(offset, ts, params.dst.map(|d| d > 0), match_country_param(&params.cc).as_deref(), params.reg.as_deref());
+      json!({
+        "gmtOffset": offset,
+        "utcOffset": format_ical_offset(offset as i64),
+        "utc": unixtime_to_utc(ts),
+        "count": zones.len(),
+        "zones": zones
+      })
+    },
+    None => json!({ "valid": false, "message": "Please specify a UTC offset via ?offset=+05:45" })
+  };
+  Json(json_info)
+}
+
+/*
+* Error for requests without an identifiable zone, suggesting similar zone names for an unrecognised zn
+*/
+fn unknown_zone_json(params: &Query<InputOptions>, abbreviation_zones: &[AbbreviationZone]) -> Value {
+  if let Some(info) = ambiguous_abbreviation_json(params, abbreviation_zones) {
+    return info;
+  }
+  let zn = params.zn.clone().unwrap_or_default();
+  let suggestions = if zn.trim().len() > 2 && resolve_zone_name(&zn).is_none() { suggest_zone_names(&zn) } else { vec![] };
+  if suggestions.is_empty() {
+    json!({ "valid": false, "message": "Cannot identify a time zone from the query parameters" })
+  } else {
+    json!({ "valid": false, "message": format!("Unknown zone name {}", zn.trim()), "suggestions": suggestions })
+  }
+}
+
+/*
+* Zone catalogue with the current offset, country and aliases of each zone, optionally for one country
+*/
+#[get("/zones")]
+pub async fn zone_catalogue(params: Query<InputOptions>) -> impl Responder {
+  reset_override();
+  let (date_str, _local) = match_datetime_from_params(&params);
+  let cc = match_country_param(&params.cc);
+  let zones = list_zones(cc.as_deref(), match_unix_ts_from_fuzzy_datetime(&date_str));
+  Json(json!(zones))
+}
+
+/*
+* Metadata of a zone given by its name in any letter case or a linked name, with suggestions for unknown names
+*/
+#[get("/zone")]
+pub async fn zone_metadata(params: Query<InputOptions>) -> impl Responder {
+  reset_override();
+  let (date_str, _local) = match_datetime_from_params(&params);
+  let requested = params.zn.clone().unwrap_or_default();
+  let entry = resolve_zone_name(&requested).and_then(|zn| match_zone_entry(&zn, &date_str));
+  let json_info = match entry {
+    Some(entry) => {
+      let mut info = json!(entry);
+      if entry.zone_name != requested.trim() {
+        info["requested"] = json!(requested.trim());
+      }
+      info
+    },
+    None => unknown_zone_json(&params, &[])
+  };
+  Json(json_info)
+}
+
+/*
+* Match a zone name directly from zn or else via the loc or place parameters, with any candidates for an ambiguous abbreviation
+*/
+async fn match_zone_name_from_params(params: &Query<InputOptions>) -> (String, Vec<AbbreviationZone>) {
+  let (mut zn, abbreviation_zones) = match_zone_name_param(params);
+  if !is_valid_zone_name(&zn) {
+    if let Some(coords) = match_coords_from_params(params) {
+      if let Some(tz_item) = fetch_tz_from_geonames(coords.lat, coords.lng).await {
+        zn = tz_item.tz;
+      }
+    } else if let Some((tz_item, _coords)) = extract_zone_name_from_place_params(params).await {
+      zn = tz_item.tz;
+    }
+  }
+  (zn, abbreviation_zones)
+}
+
+#[get("/recurrence")]
+pub async fn recurrence_info(params: Query<InputOptions>) -> impl Responder {
+  let rule_str = params.rule.clone().unwrap_or("".to_string());
+  let rule_opt = RecurrenceRule::parse(&rule_str);
+  let (zn, abbreviation_zones) = match_zone_name_from_params(&params).await;
+  let enforce_dst = params.dst.unwrap_or(1) > 0;
+  reset_override();
+  let json_info = match (rule_opt, is_valid_zone_name(&zn)) {
+    (Some(rule), true) => {
+      let start_local = params.dtl.clone().or(params.dt.clone()).unwrap_or_else(|| {
+        let offset = match_zone_offset(&zn, current_timestamp()).unwrap_or(0);
+        unixtime_to_utc(current_timestamp() + offset)
+      });
+      let max_ref = params.max.unwrap_or(100) as usize;
+      let max = if max_ref > 0 { max_ref } else { 100 };
+      let occurrences = expand_recurrence_in_zone(&zn, &rule, &start_local, max, enforce_dst);
+      json!({
+        "zoneName": zn,
+        "rule": rule_str,
+        "count": occurrences.len(),
+        "occurrences": occurrences
+      })
+    },
+    (None, _) => json!({ "valid": false, "message": "Please specify a recurrence rule via ?rule=FREQ=WEEKLY;COUNT=10" }),
+    _ => unknown_zone_json(&params, &abbreviation_zones)
+  };
+  Json(json_info)
+}
+
+#[get("/vtimezone")]
+pub async fn vtimezone_export(params: Query<InputOptions>) -> HttpResponse {
+  let (zn, abbreviation_zones) = match_zone_name_from_params(&params).await;
+  let start_ts = params.dt.clone().map(|dt_str| match_unix_ts_from_fuzzy_datetime(&dt_str)).unwrap_or(0);
+  let transitions = if is_valid_zone_name(&zn) { match_zone_transitions(&zn, start_ts) } else { vec![] };
+  if transitions.is_empty() {
+    HttpResponse::NotFound().json(unknown_zone_json(&params, &abbreviation_zones))
+  } else {
+    HttpResponse::Ok()
+      .content_type("text/calendar; charset=utf-8")
+      .insert_header(("Content-Disposition", format!("attachment; filename=\"{}.ics\"", zn.replace('/', "_"))))
+      .body(build_vtimezone(&zn, &transitions, match_transition_horizon()))
+  }
+}
+
+#[get("/posix")]
+pub async fn posix_tz_info(params: Query<InputOptions>) -> impl Responder {
+  let (zn, abbreviation_zones) = match_zone_name_from_params(&params).await;
+  let transitions = if is_valid_zone_name(&zn) { match_zone_transitions(&zn, MIN_TRANSITION_TS) } else { vec![] };
+  let json_info = match build_posix_tz(&transitions, match_transition_horizon()) {
+    Some(posix) => json!({ "zoneName": zn, "posix": posix }),
+    _ => unknown_zone_json(&params, &abbreviation_zones)
+  };
+  Json(json_info)
+}
+
+#[get("/tzif")]
+pub async fn tzif_export(params: Query<InputOptions>) -> HttpResponse {
+  let (zn, abbreviation_zones) = match_zone_name_from_params(&params).await;
+  let transitions = if is_valid_zone_name(&zn) { match_zone_transitions(&zn, MIN_TRANSITION_TS) } else { vec![] };
+  match build_posix_tz(&transitions, match_transition_horizon()) {
+    Some(posix) => HttpResponse::Ok()
+      .content_type("application/octet-stream")
+      .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", zn.replace('/', "_"))))
+      .body(build_tzif(&transitions, &posix)),
+    _ => HttpResponse::NotFound().json(unknown_zone_json(&params, &abbreviation_zones))
+  }
+}
+
+#[get("/geonames/quota")]
+pub async fn geonames_quota_info() -> impl Responder {
+  let accounts = geonames_quota_status();
+  Json(json!({
+    "available": geonames_retry_after().is_none(),
+    "accounts": accounts
+  }))
+}
+
+/*
+* Admin endpoints require the admin_token value in an X-Admin-Token header or as a bearer token,
+* and are disabled if no token is configured
+*/
+fn is_admin_request(req: &HttpRequest) -> bool {
+  let token = dotenv::var("admin_token").unwrap_or_default();
+  token.len() >= 8 && has_admin_token(req, &token)
+}
+
+/*
+* The Authorization header must read "Bearer " followed by the token alone. Tokens are compared in constant time.
+*/
+fn has_admin_token(req: &HttpRequest, token: &str) -> bool {
+  let headers = req.headers();
+  let supplied = match headers.get("x-admin-token") {
+    Some(value) => value.to_str().ok().map(|v| v.trim()),
+    None => headers.get("authorization").and_then(|v| v.to_str().ok()).and_then(|v| v.strip_prefix("Bearer "))
+      .filter(|t| !t.is_empty() && !t.contains(char::is_whitespace))
+  };
+  supplied.map(|s| constant_time_eq(s.as_bytes(), token.as_bytes())).unwrap_or(false)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+  let diff = (0..a.len().max(b.len())).fold(a.len() ^ b.len(), |acc, i| acc | (a.get(i).copied().unwrap_or(0) ^ b.get(i).copied().unwrap_or(0)) as usize);
+  diff == 0
+}
+
+fn admin_forbidden() -> HttpResponse {
+  HttpResponse::Forbidden().json(json!({ "valid": false, "message": "A valid admin token is required" }))
+}
+
+#[get("/admin/cache")]
+pub async fn app_cache_info(req: HttpRequest) -> HttpResponse {
+  if !is_admin_request(&req) {
+    return admin_forbidden();
+  }
+  let (memo_cells, memo_hits) = memo_stats();
+  let memo = json!({ "cells": memo_cells, "hits": memo_hits });
+  match app_cache_backend() {
+    Some(backend) => HttpResponse::Ok().json(json!({ "backend": backend.name(), "entries": cache_count(&backend).await, "memo": memo })),
+    None => HttpResponse::Ok().json(json!({ "backend": "none", "entries": 0, "memo": memo }))
+  }
+}
+
+#[delete("/admin/cache")]
+pub async fn purge_app_cache(req: HttpRequest) -> HttpResponse {
+  if !is_admin_request(&req) {
+    return admin_forbidden();
+  }
+  let purged = match app_cache_backend() {
+    Some(backend) => cache_purge(&backend).await,
+    None => 0
+  };
+  let memo_cells = clear_memo();
+  let indexed_localities = clear_locality_index();
+  let zone_overrides = clear_zone_overrides();
+  let zone_names = clear_zone_catalogue();
+  HttpResponse::Ok().json(json!({ "valid": true, "purged": purged, "memoCells": memo_cells, "indexedLocalities": indexed_localities, "zoneOverrides": zone_overrides, "zoneNames": zone_names }))
+}
+
+#[get("/admin/alternate-names")]
+pub async fn list_alternate_names(req: HttpRequest, params: Query<InputOptions>) -> HttpResponse {
+  if !is_admin_request(&req) {
+    return admin_forbidden();
+  }
+  let place = params.place.clone().unwrap_or_default();
+  let rows = if place.trim().len() > 1 { list_alternate_names_for(&place) } else { vec![] };
+  HttpResponse::Ok().json(json!(rows))
+}
+
+#[post("/admin/alternate-names")]
+pub async fn add_alternate_name(req: HttpRequest, entry: Json<AlternateName>) -> HttpResponse {
+  if !is_admin_request(&req) {
+    return admin_forbidden();
+  }
+  match insert_alternate_name(&entry) {
+    Ok(id) => HttpResponse::Ok().json(json!({ "valid": true, "id": id })),
+    Err(message) => HttpResponse::BadRequest().json(json!({ "valid": false, "message": message }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use actix_web::{test, App};
+
+  const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/geonames");
+
+  #[test]
+  async fn admin_tokens_are_read_strictly() {
+    let token = "s3cret-token";
+    let request = |name: &str, value: &str| test::TestRequest::default().insert_header((name, value)).to_http_request();
+    assert!(has_admin_token(&request("authorization", "Bearer s3cret-token"), token));
+    assert!(has_admin_token(&request("x-admin-token", " s3cret-token "), token));
+    assert!(!has_admin_token(&request("authorization", "s3cret-token"), token));
+    assert!(!has_admin_token(&request("authorization", "bearer s3cret-token"), token));
+    assert!(!has_admin_token(&request("authorization", "Bearer  s3cret-token"), token));
+    assert!(!has_admin_token(&request("authorization", "Bearer Bearer s3cret-token"), token));
+    assert!(!has_admin_token(&request("authorization", "Bearer s3cret-token2"), token));
+    assert!(!has_admin_token(&request("authorization", "Basic s3cret-token"), token));
+    assert!(!has_admin_token(&test::TestRequest::default().to_http_request(), token));
+  }
+
+  #[test]
+  async fn tokens_are_compared_over_their_full_length() {
+    assert!(constant_time_eq(b"abcdefgh", b"abcdefgh"));
+    assert!(!constant_time_eq(b"abcdefgh", b"abcdefgi"));
+    assert!(!constant_time_eq(b"abcdefgh", b"abcdefg"));
+    assert!(!constant_time_eq(b"", b"abcdefgh"));
+  }
+
+  async fn get_json<F>(factory: F, uri: &str) -> Value where F: actix_web::dev::HttpServiceFactory + 'static {
+    std::env::set_var("geonames_fixtures", FIXTURES_DIR);
+    let app = test::init_service(App::new().service(factory)).await;
+    let req = test::TestRequest::get().uri(uri).to_request();
+    test::call_and_read_body_json(&app, req).await
+  }
+
+  #[actix_web::test]
+  async fn search_returns_fixture_rows_by_weighted_population() {
+    let data = get_json(search_by_name, "/search?place=Springfield&cc=US&max=5").await;
+    assert_eq!(data["message"], "OK");
+    assert_eq!(data["provider"], "geonames");
+    assert_eq!(data["count"], 3);
+    let regions: Vec<&str> = data["results"].as_array().unwrap().iter().map(|row| row["adminName"].as_str().unwrap()).collect();
+    assert_eq!(regions, vec!["Missouri", "Massachusetts", "Illinois"]);
+    assert_eq!(data["results"][0]["countryCode"], "US");
+    assert_eq!(data["results"][0]["lat"], 37.21533);
+  }
+
+  #[actix_web::test]
+  async fn search_without_a_place_asks_for_one() {
+    let data = get_json(search_by_name, "/search?cc=US").await;
+    assert_eq!(data["count"], 0);
+    assert!(data["message"].as_str().unwrap().contains("?place=NAME"));
+  }
+
+  #[actix_web::test]
+  async fn geotime_lists_nearby_places_without_areas_or_continents() {
+    let data = get_json(geo_time_info, "/geotime?loc=51.5074,-0.1278&dt=2024-06-01T12:00:00&hist=0").await;
+    let names: Vec<&str> = data["placenames"].as_array().unwrap().iter().map(|row| row["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["United Kingdom", "London", "England", "Greater London"]);
+    assert_eq!(data["placenames"][1]["fcode"], "PPLC");
+    assert_eq!(data["placenames"][1]["countryCode"], output_country_code("GB"));
+  }
+
+  #[actix_web::test]
+  async fn timezone_over_the_ocean_falls_back_to_solar_time() {
+    let data = get_json(tz_info, "/timezone?loc=30,-40&dt=2024-06-01T12:00:00").await;
+    assert_eq!(data["zoneName"], "Atlantic/03W");
+    assert_eq!(data["gmtOffset"], -10800);
+    assert_eq!(data["dst"], false);
+    assert_eq!(data["utc"], "2024-06-01T12:00:00");
+    assert_eq!(data["localDt"], "2024-06-01T09:00:00");
+  }
+
+  #[actix_web::test]
+  async fn timezone_lists_candidates_for_places_in_two_zones() {
+    let data = get_json(tz_info, "/timezone?place=Springfield&cc=US").await;
+    assert_eq!(data["valid"], false);
+    let candidates: Vec<(&str, &str)> = data["candidates"].as_array().unwrap().iter()
+      .map(|c| (c["adminName"].as_str().unwrap(), c["zoneName"].as_str().unwrap())).collect();
+    // Springfield Gardens is not an exact match
+    assert_eq!(candidates, vec![
+      ("Missouri", "America/Chicago"),
+      ("Massachusetts", "America/New_York"),
+      ("Illinois", "America/Chicago"),
+      ("Ohio", "America/New_York"),
+    ]);
+    assert_eq!(data["candidates"][0]["lat"], 37.21533);
+  }
+
+  #[actix_web::test]
+  async fn a_structured_region_leaves_one_candidate() {
+    std::env::set_var("geonames_fixtures", FIXTURES_DIR);
+    let params = Query::<InputOptions>::from_query("place=Springfield&cc=US&reg=IL").unwrap();
+    let candidates = match_place_zone_candidates(&params).await;
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].admin_name.as_deref(), Some("Illinois"));
+    assert_eq!(candidates[0].zone_name, "America/Chicago");
+  }
+}
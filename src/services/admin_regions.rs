@@ -0,0 +1,187 @@
+use serde::{Serialize, Deserialize};
+use crate::data::admin_regions::ADMIN1_REGIONS;
+use super::countries::resolve_country_code;
+use super::locality_index::normalize_search_text;
+
+// generic words ignored when comparing region names, so Sverdlovsk matches Sverdlovsk Oblast
+const GENERIC_REGION_WORDS: [&str; 13] = ["state", "province", "oblast", "krai", "kray", "republic", "territory", "territories", "autonomous", "okrug", "of", "the", "region"];
+
+/*
+* First-level division of a multi-zone country matched by code or name, e.g. IN for Indiana or QLD for Queensland
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminRegion {
+  pub cc: String,
+  pub code: String,
+  pub name: String,
+  #[serde(skip)]
+  variants: Vec<String>,
+  #[serde(skip)]
+  keys: Vec<String>,
+}
+
+impl AdminRegion {
+  fn from_entry(entry: &(&str, &str, &str)) -> AdminRegion {
+    let (cc, code, names) = *entry;
+    AdminRegion {
+      cc: cc.to_owned(),
+      code: code.to_owned(),
+      name: names.split('|').next().unwrap_or(names).to_owned(),
+      variants: names.split('|').map(|n| n.to_owned()).collect(),
+      keys: names.split('|').map(region_key).filter(|k| !k.is_empty()).collect(),
+    }
+  }
+
+  /*
+  * Whether a first-level division name as returned by GeoNames or held in the cities table refers to this region.
+  * Short adjectival endings are tolerated, e.g. Sverdlovskaya for Sverdlovsk, but not extra words, so Mato Grosso do Sul
+  * is not Mato Grosso.
+  */
+  pub fn matches_name(&self, admin_name: &str) -> bool {
+    let key = region_key(admin_name);
+    !key.is_empty() && self.keys.iter().any(|k| *k == key || is_inflected(k, &key) || is_inflected(&key, k))
+  }
+}
+
+fn region_key(name: &str) -> String {
+  normalize_search_text(name).split(' ').filter(|w| !GENERIC_REGION_WORDS.contains(w)).collect::<Vec<&str>>().join(" ")
+}
+
+fn is_inflected(stem: &str, key: &str) -> bool {
+  stem.len() >= 6 && key.len() <= stem.len() + 3 && key.starts_with(stem) && !key[stem.len()..].contains(' ')
+}
+
+/*
+* Countries whose first-level divisions are matched as structured regions
+*/
+pub fn has_admin_regions(cc: &str) -> bool {
+  ADMIN1_REGIONS.iter().any(|entry| entry.0 == cc)
+}
+
+/*
+* Regions of a country (ISO alpha-2) matching a code, with or without the country prefix as in US-IN, or a name
+*/
+pub fn match_admin_regions(reg: &str, cc: &str) -> Vec<AdminRegion> {
+  let upper = reg.trim().to_uppercase();
+  let code = upper.strip_prefix(&format!("{}-", cc)).unwrap_or(&upper);
+  let regions = ADMIN1_REGIONS.iter().filter(|entry| entry.0 == cc).map(AdminRegion::from_entry);
+  let by_code: Vec<AdminRegion> = regions.clone().filter(|region| region.code == code).collect();
+  if !by_code.is_empty() {
+    return by_code;
+  }
+  regions.filter(|region| region.matches_name(reg)).collect()
+}
+
+/*
+* Filter from the reg parameter. Within the US, Canada, Brazil, Russia, Australia and Mexico, a recognised admin1 code or name
+* is matched against first-level division names of results. Elsewhere the region is free text, matched by the start of the
+* division name and otherwise passed on to the geocoder with the search string.
+*/
+#[derive(Debug, Clone)]
+pub enum RegionFilter {
+  Structured(Vec<AdminRegion>),
+  Text(String),
+}
+
+impl RegionFilter {
+  pub fn new(reg: &Option<String>, cc: &Option<String>) -> Option<RegionFilter> {
+    let text = reg.as_ref().map(|r| r.trim()).filter(|r| r.len() > 1)?;
+    let regions = cc.as_ref().and_then(|c| resolve_country_code(c))
+      .filter(|c| has_admin_regions(c))
+      .map(|c| match_admin_regions(text, &c))
+      .unwrap_or_default();
+    if regions.is_empty() {
+      Some(RegionFilter::Text(normalize_search_text(text)))
+    } else {
+      Some(RegionFilter::Structured(regions))
+    }
+  }
+
+  pub fn is_structured(&self) -> bool {
+    matches!(self, RegionFilter::Structured(_))
+  }
+
+  pub fn regions(&self) -> Vec<AdminRegion> {
+    match self {
+      RegionFilter::Structured(regions) => regions.clone(),
+      RegionFilter::Text(_) => vec![]
+    }
+  }
+
+  /*
+  * Whether a result with a first-level division name and country code in any accepted convention lies in the region
+  */
+  pub fn matches(&self, admin_name: &str, cc: &str) -> bool {
+    match self {
+      RegionFilter::Structured(regions) => {
+        let iso_cc = resolve_country_code(cc).unwrap_or_default();
+        regions.iter().any(|region| region.cc == iso_cc && region.matches_name(admin_name))
+      },
+      RegionFilter::Text(text) => {
+        let name = normalize_search_text(admin_name);
+        !name.is_empty() && (name.starts_with(text.as_str()) || region_key(&name).starts_with(text.as_str()))
+      }
+    }
+  }
+
+  /*
+  * English names and common variants of the matched regions, for prefix matches against admin1 names in SQL
+  */
+  pub fn region_names(&self) -> Vec<String> {
+    self.regions().into_iter().flat_map(|region| region.variants).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn codes(regions: &[AdminRegion]) -> Vec<String> {
+    regions.iter().map(|r| format!("{}-{}", r.cc, r.code)).collect()
+  }
+
+  #[test]
+  fn codes_are_matched_within_the_country() {
+    assert_eq!(codes(&match_admin_regions("IN", "US")), vec!["US-IN"]);
+    assert_eq!(codes(&match_admin_regions("in", "US")), vec!["US-IN"]);
+    assert_eq!(codes(&match_admin_regions("US-IN", "US")), vec!["US-IN"]);
+    assert_eq!(codes(&match_admin_regions("IN", "RU")), vec!["RU-IN"]);
+    assert!(match_admin_regions("IN", "DE").is_empty());
+  }
+
+  #[test]
+  fn names_tolerate_endings_and_generic_words_but_not_extra_words() {
+    assert_eq!(codes(&match_admin_regions("Mato Grosso", "BR")), vec!["BR-MT"]);
+    assert_eq!(codes(&match_admin_regions("Mato Grosso do Sul", "BR")), vec!["BR-MS"]);
+    assert_eq!(codes(&match_admin_regions("Sverdlovskaya oblast", "RU")), vec!["RU-SVE"]);
+    assert_eq!(codes(&match_admin_regions("Sverdlovsk", "RU")), vec!["RU-SVE"]);
+    assert_eq!(codes(&match_admin_regions("queensland", "AU")), vec!["AU-QLD"]);
+  }
+
+  #[test]
+  fn structured_filters_match_result_regions_and_countries() {
+    let filter = RegionFilter::new(&Some("IN".to_owned()), &Some("US".to_owned())).unwrap();
+    assert!(filter.is_structured());
+    assert_eq!(filter.region_names(), vec!["Indiana"]);
+    assert!(filter.matches("Indiana", "US"));
+    assert!(!filter.matches("Illinois", "US"));
+    assert!(!filter.matches("Ingushetia", "RU"));
+    let mato_grosso = RegionFilter::new(&Some("Mato Grosso".to_owned()), &Some("BR".to_owned())).unwrap();
+    assert!(mato_grosso.matches("Mato Grosso", "BR"));
+    assert!(!mato_grosso.matches("Mato Grosso do Sul", "BR"));
+  }
+
+  #[test]
+  fn other_regions_are_free_text() {
+    let filter = RegionFilter::new(&Some("Bavaria".to_owned()), &Some("DE".to_owned())).unwrap();
+    assert!(!filter.is_structured());
+    assert!(filter.region_names().is_empty());
+    assert!(filter.matches("Bavaria", "DE"));
+    assert!(!filter.matches("Baden-Wurttemberg", "DE"));
+    // a US state code without a country is only a prefix of division names
+    let filter = RegionFilter::new(&Some("IN".to_owned()), &None).unwrap();
+    assert!(!filter.is_structured());
+    assert!(filter.matches("Indiana", "US"));
+    assert!(RegionFilter::new(&Some("I".to_owned()), &Some("US".to_owned())).is_none());
+  }
+}
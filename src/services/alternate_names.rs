@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use mysql::prelude::Queryable;
+use serde::{Serialize, Deserialize};
+use crate::data::alternative_names::ALTERNATIVE_NAMES;
+use crate::data::mysql::{connect_mysql, escape_sql, escape_like};
+use super::geonames::simplify_string;
+use super::local_geonames::source_country_code;
+use super::locality_index::clear_locality_index;
+use super::zone_memo::clear_memo;
+
+const ALTERNATE_NAME_MEMO_MAX: usize = 10_000;
+
+const ALTERNATE_NAME_COLUMNS: &str = "an.id, an.geoname_id, an.alternate_name, COALESCE(NULLIF(an.canonical_name, ''), g.name, '') AS current_name, an.lang, an.historic, an.valid_from, an.valid_to";
+
+/*
+* Alternate, localised or historic name for a place, with the name in current use.
+* Dates are ISO dates or years, left empty if open-ended.
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlternateName {
+  #[serde(default)]
+  pub id: u32,
+  #[serde(rename="geonameId", default)]
+  pub geoname_id: u32,
+  pub name: String,
+  #[serde(rename="currentName", default)]
+  pub current_name: String,
+  #[serde(default)]
+  pub lang: String,
+  #[serde(default)]
+  pub historic: bool,
+  #[serde(rename="validFrom", default)]
+  pub valid_from: String,
+  #[serde(rename="validTo", default)]
+  pub valid_to: String,
+}
+
+impl AlternateName {
+  pub fn new_builtin(name: &str, current_name: &str) -> AlternateName {
+    AlternateName {
+      id: 0,
+      geoname_id: 0,
+      name: name.to_owned(),
+      current_name: current_name.to_owned(),
+      lang: "".to_owned(),
+      historic: false,
+      valid_from: "".to_owned(),
+      valid_to: "".to_owned(),
+    }
+  }
+
+  /*
+  * Whether the name was in use on an ISO date. Start dates are inclusive and end dates exclusive,
+  * while years as end dates include the whole year. Names without any dates are not treated as dated.
+  */
+  pub fn is_valid_at(&self, date: &str) -> bool {
+    if self.valid_from.is_empty() && self.valid_to.is_empty() {
+      return false;
+    }
+    let after_start = self.valid_from.is_empty() || date.get(0..self.valid_from.len()).map(|d| d >= self.valid_from.as_str()).unwrap_or(false);
+    let before_end = self.valid_to.is_empty() || match date.get(0..self.valid_to.len()) {
+      Some(d) if self.valid_to.len() == 4 => d <= self.valid_to.as_str(),
+      Some(d) => d < self.valid_to.as_str(),
+      None => false
+    };
+    after_start && before_end
+  }
+}
+
+/*
+* Lookups per search prefix, cleared when entries are added
+*/
+#[derive(Default)]
+struct AlternateNameMemo {
+  entries: HashMap<String, Vec<AlternateName>>,
+}
+
+/*
+* Rows selected with ALTERNATE_NAME_COLUMNS. Query errors are logged, e.g. if the table predates a column, and return None.
+*/
+fn fetch_alternate_name_rows(sql: String) -> Option<Vec<AlternateName>> {
+  let mut conn = connect_mysql().ok()?;
+  conn.query_map(sql, |(id, geoname_id, name, current_name, lang, historic, valid_from, valid_to): (u32, u32, String, String, String, u8, String, String)| {
+    AlternateName { id, geoname_id, name, current_name, lang, historic: historic > 0, valid_from, valid_to }
+  }).map_err(|e| println!("alternate_names: {}", e)).ok()
+}
+
+/*
+* Alternate names starting with the search string whose current name differs,
+* from the alternate_names table, resolving the current name via the geonames table where not stored with the entry.
+* None if the table is not available.
+*/
+fn fetch_alternate_names(search: &str, max: u8) -> Option<Vec<AlternateName>> {
+  let sql = format!("SELECT {}
+    FROM alternate_names AS an
+    LEFT JOIN geonames AS g ON g.geoname_id = an.geoname_id
+    WHERE an.alternate_name LIKE '{}%' AND COALESCE(NULLIF(an.canonical_name, ''), g.name, an.alternate_name) != an.alternate_name
+    ORDER BY an.historic DESC, LENGTH(an.alternate_name) LIMIT {}", ALTERNATE_NAME_COLUMNS, escape_like(search.trim()), max);
+  fetch_alternate_name_rows(sql)
+}
+
+/*
+* Built-in variants used when the alternate_names table has not been created
+*/
+fn match_builtin_alternate_names(text: &str) -> Vec<AlternateName> {
+  ALTERNATIVE_NAMES.into_iter().filter(|pair| simplify_string(pair.0).starts_with(text)).map(|(name, current)| AlternateName::new_builtin(name, current)).collect()
+}
+
+/*
+* Alternate names matching the start of a search string, memoised per simplified string
+*/
+pub fn match_alternate_names(search: &str) -> Vec<AlternateName> {
+  let text = simplify_string(search.trim());
+  if text.len() < 2 {
+    return vec![];
+  }
+  if let Some(rows) = globals::get::<AlternateNameMemo>().entries.get(&text) {
+    return rows.clone();
+  }
+  let rows = fetch_alternate_names(search, 20).unwrap_or_else(|| match_builtin_alternate_names(&text));
+  let mut memo = globals::get::<AlternateNameMemo>();
+  if memo.entries.len() >= ALTERNATE_NAME_MEMO_MAX {
+    memo.entries.clear();
+  }
+  memo.entries.insert(text, rows.clone());
+  rows
+}
+
+/*
+* Places with the name are looked up first and their alternate names joined by geoname_id,
+* with curated entries matched by canonical name and the name itself, so each branch uses an index
+*/
+fn build_alternate_names_for_sql(current_name: &str) -> String {
+  let n = escape_sql(current_name.trim());
+  format!("SELECT {c} FROM geonames AS g
+    INNER JOIN alternate_names AS an ON an.geoname_id = g.geoname_id
+    WHERE g.name = '{n}'
+    UNION SELECT {c} FROM alternate_names AS an
+    LEFT JOIN geonames AS g ON g.geoname_id = an.geoname_id
+    WHERE an.canonical_name = '{n}'
+    UNION SELECT {c} FROM alternate_names AS an
+    LEFT JOIN geonames AS g ON g.geoname_id = an.geoname_id
+    WHERE an.alternate_name = '{n}'
+    ORDER BY lang, alternate_name LIMIT 250", c = ALTERNATE_NAME_COLUMNS, n = n)
+}
+
+/*
+* Alternate names stored for a place name in current use, e.g. Madras and Bombay for the current names Chennai and Mumbai
+*/
+pub fn list_alternate_names_for(current_name: &str) -> Vec<AlternateName> {
+  fetch_alternate_name_rows(build_alternate_names_for_sql(current_name)).unwrap_or_default()
+}
+
+/*
+* Historic entries for several places in current use in one query, looked up by place name and joined by geoname_id
+* as well as by canonical name for curated entries
+*/
+fn build_historic_names_sql(current_names: &[&str]) -> String {
+  let names = current_names.iter().map(|name| format!("'{}'", escape_sql(name.trim()))).collect::<Vec<String>>().join(", ");
+  format!("SELECT {c} FROM geonames AS g
+    INNER JOIN alternate_names AS an ON an.geoname_id = g.geoname_id
+    WHERE g.name IN ({n}) AND an.historic = 1
+    UNION SELECT {c} FROM alternate_names AS an
+    LEFT JOIN geonames AS g ON g.geoname_id = an.geoname_id
+    WHERE an.canonical_name IN ({n}) AND an.historic = 1", c = ALTERNATE_NAME_COLUMNS, n = names)
+}
+
+/*
+* Historic names of places in current use, keyed by current name and memoised as with searches.
+* Names not yet memoised are fetched together.
+*/
+pub fn match_historic_names_for_all(current_names: &[&str]) -> HashMap<String, Vec<AlternateName>> {
+  let mut matches: HashMap<String, Vec<AlternateName>> = HashMap::new();
+  let mut missing: Vec<&str> = vec![];
+  {
+    let memo = globals::get::<AlternateNameMemo>();
+    for name in current_names {
+      match memo.entries.get(&format!("={}", name)) {
+        Some(rows) => { matches.insert(name.to_string(), rows.clone()); },
+        None => if !missing.contains(name) { missing.push(name) }
+      }
+    }
+  }
+  if missing.is_empty() {
+    return matches;
+  }
+  let rows = fetch_alternate_name_rows(build_historic_names_sql(&missing)).unwrap_or_default();
+  let mut memo = globals::get::<AlternateNameMemo>();
+  if memo.entries.len() + missing.len() >= ALTERNATE_NAME_MEMO_MAX {
+    memo.entries.clear();
+  }
+  for name in missing {
+    let named: Vec<AlternateName> = rows.iter().filter(|alt| alt.current_name == name).cloned().collect();
+    memo.entries.insert(format!("={}", name), named.clone());
+    matches.insert(name.to_owned(), named);
+  }
+  matches
+}
+
+/*
+* Names of a place in a language, or else in English, with preferred and short names first.
+* Places with the name, narrowed by country code, are resolved to geoname ids before their names are read by geoname_id and language.
+* Curated entries are matched by canonical name.
+*/
+fn build_localised_names_sql(name: &str, cc: Option<&str>, lang: &str) -> String {
+  let n = escape_sql(name.trim());
+  let l = escape_sql(lang);
+  let cc_clause = cc.map(|c| format!(" AND cc = '{}'", escape_sql(&source_country_code(c)))).unwrap_or_default();
+  let filter = format!("an.lang IN ('{}', 'en') AND an.historic = 0 AND an.colloquial = 0", l);
+  let sort_cols = "an.preferred, an.short, COALESCE(g.population, 0) AS population";
+  format!("SELECT id, geoname_id, alternate_name, current_name, lang, historic, valid_from, valid_to FROM (
+    SELECT {cols}, {sort} FROM (
+      SELECT geoname_id FROM geonames WHERE name = '{n}'{cc}
+      UNION SELECT geoname_id FROM geonames WHERE ascii_name = '{n}'{cc}
+    ) AS m
+    INNER JOIN alternate_names AS an ON an.geoname_id = m.geoname_id
+    INNER JOIN geonames AS g ON g.geoname_id = an.geoname_id
+    WHERE {filter}
+    UNION SELECT {cols}, {sort} FROM alternate_names AS an
+    LEFT JOIN geonames AS g ON g.geoname_id = an.geoname_id
+    WHERE an.canonical_name = '{n}' AND {filter}
+  ) AS names
+  ORDER BY lang = '{l}' DESC, preferred DESC, short ASC, population DESC LIMIT 5", cols = ALTERNATE_NAME_COLUMNS, sort = sort_cols, l = l, n = n, cc = cc_clause, filter = filter)
+}
+
+fn fetch_localised_names(name: &str, cc: Option<&str>, lang: &str) -> Vec<AlternateName> {
+  fetch_alternate_name_rows(build_localised_names_sql(name, cc, lang)).unwrap_or_default()
+}
+
+/*
+* Name of a place in the requested language or in English, memoised per language, country and name
+*/
+pub fn match_localised_name(name: &str, cc: Option<&str>, lang: &str) -> Option<String> {
+  if name.trim().is_empty() {
+    return None;
+  }
+  let key = format!("{}:{}:{}", lang, cc.unwrap_or(""), name);
+  let cached = globals::get::<AlternateNameMemo>().entries.get(&key).cloned();
+  let rows = match cached {
+    Some(rows) => rows,
+    None => {
+      let rows = fetch_localised_names(name, cc, lang);
+      let mut memo = globals::get::<AlternateNameMemo>();
+      if memo.entries.len() >= ALTERNATE_NAME_MEMO_MAX {
+        memo.entries.clear();
+      }
+      memo.entries.insert(key, rows.clone());
+      rows
+    }
+  };
+  rows.into_iter().next().map(|alt| alt.name)
+}
+
+/*
+* Two or three-letter lower-case language code from the lang parameter
+*/
+pub fn match_lang_code(lang: &Option<String>) -> Option<String> {
+  lang.as_ref().map(|l| l.trim().to_lowercase()).filter(|l| (2..=3).contains(&l.len()) && l.chars().all(|c| c.is_ascii_lowercase()))
+}
+
+/*
+* Add an entry to the alternate_names table, returning its id.
+* Memoised names, the locality index and place names memoised per geohash cell are cleared, so new names apply at once.
+*/
+pub fn insert_alternate_name(entry: &AlternateName) -> Result<u64, String> {
+  if entry.name.trim().is_empty() || (entry.current_name.trim().is_empty() && entry.geoname_id < 1) {
+    return Err("An alternate name and either a current name or a GeoNames ID are required".to_owned());
+  }
+  let sql = format!("INSERT INTO alternate_names (geoname_id, lang, alternate_name, canonical_name, historic, valid_from, valid_to) VALUES ({}, '{}', '{}', '{}', {}, '{}', '{}')",
+    entry.geoname_id, escape_sql(entry.lang.trim()), escape_sql(entry.name.trim()), escape_sql(entry.current_name.trim()),
+    entry.historic as u8, escape_sql(entry.valid_from.trim()), escape_sql(entry.valid_to.trim()));
+  let mut conn = connect_mysql().map_err(|e| e.to_string())?;
+  conn.query_drop(sql).map_err(|e| e.to_string())?;
+  globals::get::<AlternateNameMemo>().entries.clear();
+  clear_locality_index();
+  clear_memo();
+  Ok(conn.last_insert_id())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn names_for_a_place_are_joined_by_geoname_id() {
+    let sql = build_alternate_names_for_sql("Chennai");
+    assert!(sql.contains("INNER JOIN alternate_names AS an ON an.geoname_id = g.geoname_id\n    WHERE g.name = 'Chennai'"));
+    assert!(sql.contains("WHERE an.canonical_name = 'Chennai'"));
+    assert!(sql.contains("WHERE an.alternate_name = 'Chennai'"));
+    assert!(!sql.contains(" OR "));
+    assert!(build_alternate_names_for_sql("Val d'Isère").contains("'Val d''Isère'"));
+  }
+
+  #[test]
+  fn localised_names_are_read_by_geoname_id_and_language() {
+    let sql = build_localised_names_sql("Munich", Some("DE"), "de");
+    assert!(sql.contains("SELECT geoname_id FROM geonames WHERE name = 'Munich' AND cc = 'DE'"));
+    assert!(sql.contains("INNER JOIN alternate_names AS an ON an.geoname_id = m.geoname_id"));
+    assert!(sql.contains("WHERE an.canonical_name = 'Munich' AND an.lang IN ('de', 'en')"));
+    assert!(!sql.contains(" OR "));
+    assert!(build_localised_names_sql("London", Some("UK"), "fr").contains("cc = 'GB'"));
+  }
+
+  #[test]
+  fn language_codes_are_two_or_three_lower_case_letters() {
+    assert_eq!(match_lang_code(&Some(" DE ".to_owned())).as_deref(), Some("de"));
+    assert_eq!(match_lang_code(&Some("fil".to_owned())).as_deref(), Some("fil"));
+    assert_eq!(match_lang_code(&Some("pt-BR".to_owned())), None);
+    assert_eq!(match_lang_code(&Some("e".to_owned())), None);
+    assert_eq!(match_lang_code(&None), None);
+  }
+
+  #[test]
+  fn memoised_names_are_localised_and_others_keep_the_original_name() {
+    let munchen = AlternateName { lang: "de".to_owned(), ..AlternateName::new_builtin("München", "Munich") };
+    globals::get::<AlternateNameMemo>().entries.insert("de:DE:Munich".to_owned(), vec![munchen]);
+    globals::get::<AlternateNameMemo>().entries.insert("de:DE:Bavaria".to_owned(), vec![]);
+    assert_eq!(match_localised_name("Munich", Some("DE"), "de").as_deref(), Some("München"));
+    assert_eq!(match_localised_name("Bavaria", Some("DE"), "de"), None);
+    assert_eq!(match_localised_name(" ", Some("DE"), "de"), None);
+    let mut row = super::super::geonames::GeoNameRow::new_from_params(48.14, 11.58, "Munich".to_owned(), "PPLA".to_owned(), 1_260_000);
+    row.country_code = Some("DE".to_owned());
+    row.admin_name = Some("Bavaria".to_owned());
+    row.localise("de");
+    assert_eq!(row.name, "München");
+    assert_eq!(row.admin_name.as_deref(), Some("Bavaria"));
+  }
+}
@@ -0,0 +1,338 @@
+use serde::{Serialize, Deserialize};
+use crate::app::posix_tz::build_posix_tz;
+use crate::data::countries::{COUNTRIES, COUNTRY_NAME_ALIASES};
+use crate::data::alternative_names::CORRECTED_COUNTRY_CODES;
+use crate::data::mysql::escape_sql;
+use crate::app::transition_rules::ZoneTransition;
+use crate::app::date_conv::unixtime_to_utc;
+use super::geonames::{Locality, fetch_locality_rows};
+use super::locality_index::normalize_search_text;
+use super::timezonedb::{TimeZone, fetch_time_zone_rows, match_transition_horizon, MAX_TRANSITION_TS};
+
+/*
+* Country from the ISO 3166-1 registry, with the code in the configured output convention
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Country {
+  pub code: String,
+  pub alpha2: String,
+  pub alpha3: String,
+  pub numeric: String,
+  pub name: String,
+  pub capital: String,
+  pub zones: Vec<String>,
+}
+
+type CountryEntry = (&'static str, &'static str, u16, &'static str, &'static str, &'static str);
+
+impl Country {
+  fn from_entry(entry: &CountryEntry) -> Country {
+    let (alpha2, alpha3, numeric, name, capital, zones) = *entry;
+    Country {
+      code: output_country_code(alpha2),
+      alpha2: alpha2.to_owned(),
+      alpha3: alpha3.to_owned(),
+      numeric: if numeric > 0 { format!("{:03}", numeric) } else { "".to_owned() },
+      name: name.to_owned(),
+      capital: capital.to_owned(),
+      zones: zones.split('|').map(|z| z.to_owned()).collect(),
+    }
+  }
+}
+
+/*
+* Country code convention for output, set per deployment via country_codes:
+* legacy keeps the preferred codes such as UK for GB, alpha2 or iso uses ISO alpha-2 throughout and alpha3 uses ISO alpha-3
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountryCodeStyle {
+  Legacy,
+  Alpha2,
+  Alpha3,
+}
+
+pub fn country_code_style() -> CountryCodeStyle {
+  match dotenv::var("country_codes").unwrap_or_default().to_lowercase().as_str() {
+    "alpha2" | "iso" | "iso2" => CountryCodeStyle::Alpha2,
+    "alpha3" | "iso3" => CountryCodeStyle::Alpha3,
+    _ => CountryCodeStyle::Legacy
+  }
+}
+
+fn find_entry_by_code(code: &str) -> Option<&'static CountryEntry> {
+  let upper = code.trim().to_uppercase();
+  if upper.chars().all(|c| c.is_ascii_digit()) {
+    let numeric = upper.parse::<u16>().ok().filter(|n| *n > 0)?;
+    return COUNTRIES.iter().find(|entry| entry.2 == numeric);
+  }
+  let alpha2 = CORRECTED_COUNTRY_CODES.into_iter().find(|pair| pair.1 == upper).map(|pair| pair.0.to_owned()).unwrap_or(upper);
+  match alpha2.len() {
+    2 => COUNTRIES.iter().find(|entry| entry.0 == alpha2),
+    3 => COUNTRIES.iter().find(|entry| entry.1 == alpha2),
+    _ => None
+  }
+}
+
+fn find_entry_by_name(name: &str) -> Option<&'static CountryEntry> {
+  let key = normalize_search_text(name);
+  if key.is_empty() {
+    return None;
+  }
+  COUNTRIES.iter().find(|entry| normalize_search_text(entry.3) == key)
+    .or_else(|| COUNTRY_NAME_ALIASES.into_iter().find(|pair| normalize_search_text(pair.0) == key).and_then(|pair| find_entry_by_code(pair.1)))
+}
+
+/*
+* Country from an ISO alpha-2, alpha-3 or numeric code, a legacy code such as UK, or an English or common name
+*/
+pub fn find_country(text: &str) -> Option<Country> {
+  find_entry_by_code(text).or_else(|| find_entry_by_name(text)).map(Country::from_entry)
+}
+
+/*
+* ISO alpha-2 code as used by GeoNames and the imported tables for any accepted country reference
+*/
+pub fn resolve_country_code(text: &str) -> Option<String> {
+  find_entry_by_code(text).or_else(|| find_entry_by_name(text)).map(|entry| entry.0.to_owned())
+}
+
+/*
+* ISO alpha-2 code from the cc parameter. Unrecognised values and ALL yield no country filter.
+*/
+pub fn match_country_param(cc: &Option<String>) -> Option<String> {
+  cc.as_ref().filter(|c| c.trim().len() > 1).and_then(|c| resolve_country_code(c))
+}
+
+/*
+* Country code in the output convention for a code as provided by GeoNames or stored in the imported tables
+*/
+pub fn output_country_code(cc: &str) -> String {
+  let upper = cc.trim().to_uppercase();
+  match country_code_style() {
+    CountryCodeStyle::Legacy => match CORRECTED_COUNTRY_CODES.into_iter().find(|pair| pair.0 == upper) {
+      Some((_source, preferred)) => preferred.to_owned(),
+      None => upper
+    },
+    CountryCodeStyle::Alpha2 => resolve_country_code(&upper).unwrap_or(upper),
+    CountryCodeStyle::Alpha3 => find_entry_by_code(&upper).map(|entry| entry.1.to_owned()).unwrap_or(upper),
+  }
+}
+
+/*
+* All countries in the registry, in alphabetical order of their alpha-2 codes
+*/
+pub fn list_countries() -> Vec<Country> {
+  COUNTRIES.iter().map(Country::from_entry).collect()
+}
+
+/*
+* Zone of a country at a point in time with its next transition, POSIX rules and most populous city
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountryZone {
+  #[serde(rename="zoneName")]
+  pub zone_name: String,
+  pub abbreviation: String,
+  #[serde(rename="gmtOffset")]
+  pub gmt_offset: i32,
+  pub dst: bool,
+  #[serde(rename="nextTransition", skip_serializing_if = "Option::is_none")]
+  pub next_transition: Option<String>,
+  #[serde(rename="nextGmtOffset", skip_serializing_if = "Option::is_none")]
+  pub next_gmt_offset: Option<i32>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub posix: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub city: Option<Locality>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountryZones {
+  pub country: Country,
+  // more than one UTC offset in force at the reference time
+  #[serde(rename="multipleOffsets")]
+  pub multiple_offsets: bool,
+  pub offsets: Vec<i32>,
+  pub zones: Vec<CountryZone>,
+}
+
+impl CountryZones {
+  pub fn localise(&mut self, lang: &str) {
+    for zone in self.zones.iter_mut() {
+      if let Some(city) = zone.city.as_mut() {
+        city.localise(lang);
+      }
+    }
+  }
+}
+
+/*
+* Periods in force at a UTC timestamp and all later periods for every zone of a country, in one query,
+* ordered by zone and start time
+*/
+fn fetch_country_periods(cc: &str, ts: i64) -> Vec<TimeZone> {
+  let sql = format!("SELECT tz.zone_name, tz.country_code, tz.abbreviation, tz.time_start, tz.gmt_offset, IF (tz.dst = '1', true, false) AS dst FROM time_zone AS tz
+    INNER JOIN (SELECT zone_name, MAX(time_start) AS time_start FROM time_zone WHERE time_start <= {ts} AND country_code = '{cc}' GROUP BY zone_name) AS cur
+    ON cur.zone_name = tz.zone_name AND tz.time_start >= cur.time_start
+    WHERE tz.time_start <= {max_ts}
+    ORDER BY tz.zone_name, tz.time_start", ts = ts, cc = escape_sql(cc), max_ts = MAX_TRANSITION_TS);
+  fetch_time_zone_rows(sql)
+}
+
+/*
+* Most populous city in the cities table for each zone within a country
+*/
+fn fetch_representative_cities(cc: &str) -> Vec<Locality> {
+  let sql = format!("SELECT c.name, c.ascii_name, c.admin_name, c.lat, c.lng, c.cc, c.population, c.zone_name FROM cities AS c
+    INNER JOIN (SELECT zone_name, MAX(population) AS population FROM cities WHERE cc = '{cc}' GROUP BY zone_name) AS top
+    ON top.zone_name = c.zone_name AND top.population = c.population
+    WHERE c.cc = '{cc}' ORDER BY c.zone_name", cc = escape_sql(cc));
+  fetch_locality_rows(sql)
+}
+
+/*
+* Transitions from the reference time onwards, starting with the period then in force
+*/
+fn build_transitions(periods: &[TimeZone], ts: i64) -> Vec<ZoneTransition> {
+  periods.iter().enumerate().map(|(index, period)| ZoneTransition {
+    ts: if index > 0 { period.time_start() } else { period.time_start().max(ts) },
+    offset_from: if index > 0 { periods[index - 1].offset() } else { period.offset() },
+    offset_to: period.offset(),
+    abbreviation: period.abbreviation.clone(),
+    dst: period.dst,
+  }).collect()
+}
+
+/*
+* Zones of a country from its periods ordered by zone and start time, each with the representative city for the zone
+*/
+fn build_country_zones(country: Country, periods: &[TimeZone], mut cities: Vec<Locality>, ts: i64, horizon_ts: i64) -> CountryZones {
+  let mut zones: Vec<CountryZone> = vec![];
+  for zone_periods in periods.chunk_by(|a, b| a.zone_name == b.zone_name) {
+    let (current, next) = (&zone_periods[0], zone_periods.get(1));
+    let city_index = cities.iter().position(|city| city.zone_name() == current.zone_name);
+    zones.push(CountryZone {
+      zone_name: current.zone_name.clone(),
+      abbreviation: current.abbreviation.clone(),
+      gmt_offset: current.gmt_offset,
+      dst: current.dst,
+      next_transition: next.map(|n| unixtime_to_utc(n.time_start())),
+      next_gmt_offset: next.map(|n| n.gmt_offset),
+      posix: build_posix_tz(&build_transitions(zone_periods, ts), horizon_ts),
+      city: city_index.map(|index| cities.swap_remove(index)),
+    });
+  }
+  let mut offsets: Vec<i32> = zones.iter().map(|zone| zone.gmt_offset).collect();
+  offsets.sort();
+  offsets.dedup();
+  CountryZones { country, multiple_offsets: offsets.len() > 1, offsets, zones }
+}
+
+/*
+* All zones of a country in the time_zone table as in force at a UTC timestamp, with POSIX rules derived from later transitions
+*/
+pub fn match_country_zones(country: Country, ts: i64) -> CountryZones {
+  let periods = fetch_country_periods(&country.alpha2, ts);
+  let cities = fetch_representative_cities(&country.alpha2);
+  build_country_zones(country, &periods, cities, ts, match_transition_horizon())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app::transition_rules::tests::{utc_ts, horizon};
+
+  #[test]
+  fn countries_are_resolved_by_any_code_or_name() {
+    for text in ["GB", "gb", "GBR", "826", "UK", "United Kingdom", "Great Britain", " scotland "] {
+      assert_eq!(resolve_country_code(text).as_deref(), Some("GB"), "{}", text);
+    }
+    assert_eq!(resolve_country_code("DEU").as_deref(), Some("DE"));
+    assert_eq!(resolve_country_code("276").as_deref(), Some("DE"));
+    assert_eq!(resolve_country_code("Holland").as_deref(), Some("NL"));
+    assert_eq!(resolve_country_code("Narnia"), None);
+    assert_eq!(resolve_country_code("0"), None);
+    assert_eq!(resolve_country_code(""), None);
+  }
+
+  #[test]
+  fn countries_carry_all_codes_and_their_zones() {
+    let country = find_country("DEU").unwrap();
+    assert_eq!((country.alpha2.as_str(), country.alpha3.as_str(), country.numeric.as_str()), ("DE", "DEU", "276"));
+    assert_eq!(country.name, "Germany");
+    assert_eq!(country.zones.first().map(|z| z.as_str()), Some("Europe/Berlin"));
+    assert_eq!(find_country("UK").unwrap().code, output_country_code("GB"));
+  }
+
+  #[test]
+  fn output_codes_follow_the_configured_style() {
+    let expected = match country_code_style() {
+      CountryCodeStyle::Legacy => ("UK", "DE"),
+      CountryCodeStyle::Alpha2 => ("GB", "DE"),
+      CountryCodeStyle::Alpha3 => ("GBR", "DEU"),
+    };
+    assert_eq!(output_country_code("gb"), expected.0);
+    assert_eq!(output_country_code("DE"), expected.1);
+    assert_eq!(output_country_code("XX"), "XX");
+  }
+
+  #[test]
+  fn the_cc_parameter_ignores_all_and_unknown_values() {
+    assert_eq!(match_country_param(&Some("uk".to_owned())).as_deref(), Some("GB"));
+    assert_eq!(match_country_param(&Some("ALL".to_owned())), None);
+    assert_eq!(match_country_param(&Some("X".to_owned())), None);
+    assert_eq!(match_country_param(&None), None);
+  }
+
+  // periods of a zone from a reference time in mid 2022, alternating between standard and summer time
+  fn periods(zone_name: &str, std_offset: i32, std_abbr: &str, dst_abbr: &str) -> Vec<TimeZone> {
+    let changes = ["2022-03-27 01:00", "2022-10-30 01:00", "2023-03-26 01:00", "2023-10-29 01:00", "2024-03-31 01:00", "2024-10-27 01:00"];
+    changes.iter().enumerate().map(|(index, utc)| {
+      let dst = index % 2 == 0;
+      let (offset, abbr) = if dst { (std_offset + 3600, dst_abbr) } else { (std_offset, std_abbr) };
+      TimeZone::new(zone_name.to_owned(), "PT".to_owned(), abbr.to_owned(), utc_ts(utc), offset, dst)
+    }).collect()
+  }
+
+  #[test]
+  fn transitions_start_at_the_reference_time() {
+    let lisbon = periods("Europe/Lisbon", 0, "WET", "WEST");
+    let transitions = build_transitions(&lisbon, utc_ts("2022-06-01 00:00"));
+    assert_eq!(transitions.len(), 6);
+    assert_eq!(transitions[0].ts, utc_ts("2022-06-01 00:00"));
+    assert_eq!((transitions[0].offset_from, transitions[0].offset_to), (3600, 3600));
+    assert_eq!(transitions[1].ts, utc_ts("2022-10-30 01:00"));
+    assert_eq!((transitions[1].offset_from, transitions[1].offset_to), (3600, 0));
+    assert!(!transitions[1].dst);
+  }
+
+  #[test]
+  fn zones_of_a_country_get_rules_offsets_and_cities() {
+    let mut rows = periods("Atlantic/Azores", -3600, "-01", "+00");
+    rows.extend(periods("Europe/Lisbon", 0, "WET", "WEST"));
+    let cities = vec![Locality::new("Lisbon".to_owned(), "Lisbon".to_owned(), "Lisbon".to_owned(), 38.72, -9.13, "PT".to_owned(), 517802, "Europe/Lisbon".to_owned())];
+    let result = build_country_zones(find_country("PT").unwrap(), &rows, cities, utc_ts("2022-06-01 00:00"), horizon());
+    assert!(result.multiple_offsets);
+    assert_eq!(result.offsets, vec![0, 3600]);
+    let names: Vec<&str> = result.zones.iter().map(|zone| zone.zone_name.as_str()).collect();
+    assert_eq!(names, vec!["Atlantic/Azores", "Europe/Lisbon"]);
+    let lisbon = &result.zones[1];
+    assert_eq!((lisbon.abbreviation.as_str(), lisbon.gmt_offset, lisbon.dst), ("WEST", 3600, true));
+    assert_eq!(lisbon.next_transition, Some(unixtime_to_utc(utc_ts("2022-10-30 01:00"))));
+    assert_eq!(lisbon.next_gmt_offset, Some(0));
+    assert_eq!(lisbon.posix.as_deref(), Some("WET0WEST,M3.5.0/1,M10.5.0"));
+    assert_eq!(lisbon.city.as_ref().map(|city| city.zone_name()), Some("Europe/Lisbon"));
+    assert!(result.zones[0].city.is_none());
+    assert!(result.zones[0].posix.is_some());
+  }
+
+  #[test]
+  fn one_offset_is_not_multiple() {
+    // rows start with the period in force, here winter time from October 2022
+    let rows = periods("Europe/Lisbon", 0, "WET", "WEST");
+    let result = build_country_zones(find_country("PT").unwrap(), &rows[1..], vec![], utc_ts("2022-12-01 00:00"), horizon());
+    assert!(!result.multiple_offsets);
+    assert_eq!(result.offsets, vec![0]);
+    assert_eq!(result.zones[0].abbreviation, "WET");
+  }
+}
@@ -0,0 +1,444 @@
+use std::future::Future;
+use std::pin::Pin;
+use serde_json::{Map, Value};
+use crate::constants::*;
+use crate::app::cached_http_client::get_cached_http_client;
+use crate::app::json_extract::*;
+use super::geonames::{GeoNameRow, search_geonames_by_fuzzy_names, correct_country_code};
+use super::local_geonames::search_local_geonames;
+use super::admin_regions::RegionFilter;
+
+pub type GeocodeFuture<'a> = Pin<Box<dyn Future<Output = Vec<GeoNameRow>> + 'a>>;
+
+/*
+* Place name search options shared by all providers
+*/
+#[derive(Debug, Clone)]
+pub struct PlaceQuery {
+  pub search: String,
+  pub cc: Option<String>,
+  pub region: Option<String>,
+  // admin1 regions of multi-zone countries recognised in region, required of every result
+  pub admin_regions: Option<RegionFilter>,
+  pub fuzzy: Option<f32>,
+  // all feature classes rather than only populated places (P) and administrative areas (A)
+  pub all_classes: bool,
+  pub included: bool,
+  pub max_rows: u8,
+}
+
+impl PlaceQuery {
+  pub fn new(search: &str, cc: &Option<String>, region: &Option<String>, fuzzy: Option<f32>, all_classes: bool, included: bool, max_rows: u8) -> PlaceQuery {
+    let admin_regions = RegionFilter::new(region, cc).filter(|filter| filter.is_structured());
+    // send the full region name rather than a code such as IN or QLD that would match other words
+    let region = match &admin_regions {
+      Some(filter) => filter.regions().first().map(|r| r.name.clone()),
+      None => region.clone()
+    };
+    PlaceQuery {
+      search: search.to_owned(),
+      cc: cc.clone(),
+      region,
+      admin_regions,
+      fuzzy,
+      all_classes,
+      included,
+      max_rows,
+    }
+  }
+
+  /*
+  * Search string with the optional region appended, as sent to GeoNames
+  */
+  pub fn text(&self) -> String {
+    match &self.region {
+      Some(region) => format!("{} {}", self.search, region),
+      None => self.search.clone()
+    }
+  }
+}
+
+/*
+* Search with a provider, keeping only results within the recognised admin1 regions.
+* More rows are requested when filtering, as results in other regions are discarded.
+*/
+pub async fn search_with_regions(provider: &dyn GeocodingProvider, query: &PlaceQuery) -> Vec<GeoNameRow> {
+  match &query.admin_regions {
+    Some(filter) => {
+      let mut wide_query = query.clone();
+      wide_query.max_rows = query.max_rows.max(REGION_SEARCH_ROWS_MIN);
+      let mut rows: Vec<GeoNameRow> = provider.search(&wide_query).await.into_iter()
+        .filter(|row| filter.matches(row.admin_name.as_deref().unwrap_or(""), row.country_code.as_deref().unwrap_or("")))
+        .collect();
+      rows.truncate(query.max_rows.max(1) as usize);
+      rows
+    },
+    None => provider.search(query).await
+  }
+}
+
+/*
+* Forward geocoder returning results normalised as GeoNameRow with GeoNames feature codes
+*/
+pub trait GeocodingProvider {
+  fn name(&self) -> &'static str;
+
+  fn search<'a>(&'a self, query: &'a PlaceQuery) -> GeocodeFuture<'a>;
+}
+
+pub struct GeoNamesProvider;
+
+impl GeocodingProvider for GeoNamesProvider {
+  fn name(&self) -> &'static str {
+    "geonames"
+  }
+
+  fn search<'a>(&'a self, query: &'a PlaceQuery) -> GeocodeFuture<'a> {
+    Box::pin(async move {
+      search_geonames_by_fuzzy_names(&query.search, &query.cc, &query.region, query.fuzzy, query.all_classes, query.included, query.max_rows).await
+    })
+  }
+}
+
+/*
+* Offline search over GeoNames dumps imported with --import
+*/
+pub struct LocalGeoNamesProvider;
+
+impl GeocodingProvider for LocalGeoNamesProvider {
+  fn name(&self) -> &'static str {
+    "local"
+  }
+
+  fn search<'a>(&'a self, query: &'a PlaceQuery) -> GeocodeFuture<'a> {
+    Box::pin(async move {
+      search_local_geonames(query)
+    })
+  }
+}
+
+/*
+* Map OpenStreetMap tags to the closest GeoNames feature code. Administrative boundaries are mapped
+* by admin_level or, for Photon, by the type property.
+*/
+pub fn osm_feature_code(key: &str, value: &str, level: &str, is_capital: bool) -> String {
+  let code = match (key, value) {
+    ("place", "city") | ("place", "town") | ("place", "village") | ("place", "hamlet") | ("place", "isolated_dwelling") => {
+      if is_capital { "PPLC" } else { "PPL" }
+    },
+    ("place", "suburb") | ("place", "quarter") | ("place", "neighbourhood") | ("place", "city_block") | ("place", "borough") => "PPLX",
+    ("place", "locality") | ("place", "farm") => "LCTY",
+    ("place", "island") | ("place", "islet") => "ISL",
+    ("place", "archipelago") => "ISLS",
+    ("place", "country") => "PCLI",
+    ("place", "state") | ("place", "province") | ("place", "region") => "ADM1",
+    ("place", "county") | ("place", "district") => "ADM2",
+    ("place", "municipality") => "ADM3",
+    ("boundary", "administrative") => match level {
+      "2" | "country" => "PCLI",
+      "3" | "4" | "state" => "ADM1",
+      "5" | "6" | "county" => "ADM2",
+      "7" | "8" | "city" | "district" => "ADM3",
+      _ => "ADMD"
+    },
+    ("natural", "peak") | ("natural", "volcano") => "PK",
+    ("natural", "water") | ("water", "lake") => "LK",
+    ("waterway", "river") => "STM",
+    ("natural", "bay") => "BAY",
+    ("natural", "cape") => "CAPE",
+    ("aeroway", "aerodrome") => "AIRP",
+    ("railway", "station") => "RSTN",
+    _ => ""
+  };
+  code.to_owned()
+}
+
+/*
+* GeoNames feature class from a feature code, e.g. P for PPL or A for ADM1
+*/
+fn feature_class(fcode: &str) -> &'static str {
+  match fcode {
+    c if c.starts_with("PPL") => "P",
+    c if c.starts_with("ADM") || c.starts_with("PCL") => "A",
+    "ISL" | "ISLS" | "PK" | "CAPE" => "T",
+    "LK" | "STM" | "BAY" => "H",
+    "AIRP" | "RSTN" => "S",
+    "LCTY" => "L",
+    _ => ""
+  }
+}
+
+fn extract_string_from_value(value: Option<&Value>) -> String {
+  match value {
+    Some(Value::String(s)) => s.clone(),
+    Some(Value::Number(n)) => n.to_string(),
+    _ => "".to_owned()
+  }
+}
+
+fn parse_population(value: &str) -> u32 {
+  value.chars().filter(|c| c.is_ascii_digit()).collect::<String>().parse::<u32>().unwrap_or(0)
+}
+
+/*
+* Apply the feature class and country filters GeoNames supports natively, then order by weighted population
+* before keeping the requested number of rows
+*/
+fn filter_rows(rows: Vec<GeoNameRow>, query: &PlaceQuery) -> Vec<GeoNameRow> {
+  let mut rows: Vec<GeoNameRow> = rows.into_iter().filter(|row| {
+    let class_ok = query.all_classes || matches!(feature_class(&row.fcode), "P" | "A");
+    let cc_ok = match (&query.cc, &row.country_code) {
+      (Some(cc), Some(row_cc)) => correct_country_code(cc) == *row_cc,
+      (Some(_), None) => false,
+      _ => true
+    };
+    class_ok && cc_ok
+  }).collect();
+  rows.sort_by_key(|row| std::cmp::Reverse(row.weighted_pop()));
+  rows.truncate(query.max_rows.max(1) as usize);
+  rows
+}
+
+fn build_row(lat: f64, lng: f64, name: String, fcode: String, pop: u32, cc: String, admin_name: String) -> GeoNameRow {
+  let mut row = GeoNameRow::new_from_params(lat, lng, name, fcode, pop);
+  if !cc.is_empty() {
+    row.country_code = Some(correct_country_code(&cc.to_uppercase()));
+  }
+  if !admin_name.is_empty() {
+    row.admin_name = Some(admin_name);
+  }
+  row
+}
+
+/*
+* OpenStreetMap Nominatim search API, e.g. https://nominatim.openstreetmap.org or a self-hosted instance
+*/
+pub struct NominatimProvider {
+  pub base_url: String,
+}
+
+impl NominatimProvider {
+  fn map_row(item: &Map<String, Value>) -> GeoNameRow {
+    let lat = extract_string_from_value(item.get("lat")).parse::<f64>().unwrap_or(0f64);
+    let lng = extract_string_from_value(item.get("lon")).parse::<f64>().unwrap_or(0f64);
+    let empty = Map::new();
+    let address = match item.get("address") { Some(Value::Object(a)) => a, _ => &empty };
+    let extratags = match item.get("extratags") { Some(Value::Object(e)) => e, _ => &empty };
+    let mut name = extract_string_from_value_map(item, "name");
+    if name.is_empty() {
+      name = extract_string_from_value_map(item, "display_name").split(',').next().unwrap_or("").trim().to_owned();
+    }
+    let mut key = extract_string_from_value(item.get("category").or(item.get("class")));
+    let mut value = extract_string_from_value(item.get("type"));
+    let address_type = extract_string_from_value(item.get("addresstype"));
+    // cities are often matched via their administrative boundary
+    if key == "boundary" && matches!(address_type.as_str(), "city" | "town" | "village" | "hamlet") {
+      key = "place".to_owned();
+      value = address_type;
+    }
+    let level = extract_string_from_value(extratags.get("admin_level").or(item.get("addresstype")));
+    let is_capital = matches!(extract_string_from_value(extratags.get("capital")).as_str(), "yes" | "2");
+    let fcode = osm_feature_code(&key, &value, &level, is_capital);
+    let pop = parse_population(&extract_string_from_value(extratags.get("population")));
+    let cc = extract_string_from_value(address.get("country_code"));
+    let admin_name = ["state", "province", "region"].iter().map(|k| extract_string_from_value(address.get(*k))).find(|s| !s.is_empty()).unwrap_or_default();
+    build_row(lat, lng, name, fcode, pop, cc, admin_name)
+  }
+}
+
+impl GeocodingProvider for NominatimProvider {
+  fn name(&self) -> &'static str {
+    "nominatim"
+  }
+
+  fn search<'a>(&'a self, query: &'a PlaceQuery) -> GeocodeFuture<'a> {
+    Box::pin(async move {
+      let url = format!("{}/search", self.base_url);
+      let limit = (query.max_rows.max(1) as u32 * 2).min(40).to_string();
+      let mut items: Vec<(&str, String)> = vec![
+        ("q", query.text()),
+        ("format", "jsonv2".to_owned()),
+        ("addressdetails", "1".to_owned()),
+        ("extratags", "1".to_owned()),
+        ("limit", limit),
+      ];
+      if !query.all_classes {
+        items.push(("featureType", "settlement".to_owned()));
+      }
+      if let Some(cc) = &query.cc {
+        items.push(("countrycodes", cc.to_lowercase()));
+      }
+      let client = get_cached_http_client();
+      let response = client.get(url).query(&items).header("User-Agent", match_geocoding_user_agent()).send().await;
+      let data = match response {
+        Ok(res) => res.json::<Vec<Map<String, Value>>>().await.unwrap_or_default(),
+        Err(_) => vec![]
+      };
+      let rows = data.iter().map(NominatimProvider::map_row).collect();
+      filter_rows(rows, query)
+    })
+  }
+}
+
+/*
+* Photon search API, e.g. https://photon.komoot.io or a self-hosted instance
+*/
+pub struct PhotonProvider {
+  pub base_url: String,
+}
+
+impl PhotonProvider {
+  fn map_row(feature: &Map<String, Value>) -> Option<GeoNameRow> {
+    let coords = feature.get("geometry")?.get("coordinates")?.as_array()?;
+    let lng = coords.first()?.as_f64()?;
+    let lat = coords.get(1)?.as_f64()?;
+    let props = feature.get("properties")?.as_object()?;
+    let key = extract_string_from_value_map(props, "osm_key");
+    let value = extract_string_from_value_map(props, "osm_value");
+    let level = extract_string_from_value_map(props, "type");
+    let fcode = osm_feature_code(&key, &value, &level, false);
+    let pop = parse_population(&extract_string_from_value(props.get("extra").and_then(|e| e.get("population"))));
+    let cc = extract_string_from_value_map(props, "countrycode");
+    let admin_name = extract_string_from_value_map(props, "state");
+    Some(build_row(lat, lng, extract_string_from_value_map(props, "name"), fcode, pop, cc, admin_name))
+  }
+}
+
+impl GeocodingProvider for PhotonProvider {
+  fn name(&self) -> &'static str {
+    "photon"
+  }
+
+  fn search<'a>(&'a self, query: &'a PlaceQuery) -> GeocodeFuture<'a> {
+    Box::pin(async move {
+      let url = format!("{}/api", self.base_url);
+      let limit = (query.max_rows.max(1) as u32 * 2).min(50).to_string();
+      let mut items: Vec<(&str, String)> = vec![
+        ("q", query.text()),
+        ("limit", limit),
+      ];
+      if !query.all_classes {
+        items.push(("osm_tag", "place".to_owned()));
+        items.push(("osm_tag", "boundary:administrative".to_owned()));
+      }
+      let client = get_cached_http_client();
+      let response = client.get(url).query(&items).header("User-Agent", match_geocoding_user_agent()).send().await;
+      let data = match response {
+        Ok(res) => res.json::<Map<String, Value>>().await.unwrap_or_default(),
+        Err(_) => Map::new()
+      };
+      let rows = match data.get("features") {
+        Some(Value::Array(features)) => features.iter().filter_map(|f| f.as_object().and_then(PhotonProvider::map_row)).collect(),
+        _ => vec![]
+      };
+      filter_rows(rows, query)
+    })
+  }
+}
+
+fn match_geocoding_user_agent() -> String {
+  dotenv::var("geocoding_user_agent").unwrap_or(GEOCODING_USER_AGENT_DEFAULT.to_owned())
+}
+
+/*
+* Provider named in the request, else from geocoding_provider, else GeoNames
+*/
+pub fn geocoding_provider(name: Option<&str>) -> Box<dyn GeocodingProvider> {
+  let key = name.map(|n| n.to_owned()).filter(|n| !n.trim().is_empty()).unwrap_or_else(|| dotenv::var("geocoding_provider").unwrap_or_default());
+  match key.trim().to_lowercase().as_str() {
+    "nominatim" | "osm" => {
+      let base_url = dotenv::var("nominatim_api_base").unwrap_or(NOMINATIM_API_BASE.to_owned());
+      Box::new(NominatimProvider { base_url: base_url.trim_end_matches('/').to_owned() })
+    },
+    "photon" => {
+      let base_url = dotenv::var("photon_api_base").unwrap_or(PHOTON_API_BASE.to_owned());
+      Box::new(PhotonProvider { base_url: base_url.trim_end_matches('/').to_owned() })
+    },
+    "local" | "offline" => Box::new(LocalGeoNamesProvider),
+    _ => Box::new(GeoNamesProvider)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn nominatim_rows() -> Vec<GeoNameRow> {
+    let data: Vec<Map<String, Value>> = serde_json::from_str(include_str!("../../tests/fixtures/geocoders/nominatim_search_paris.json")).unwrap();
+    data.iter().map(NominatimProvider::map_row).collect()
+  }
+
+  fn photon_rows() -> Vec<GeoNameRow> {
+    let data: Map<String, Value> = serde_json::from_str(include_str!("../../tests/fixtures/geocoders/photon_api_springfield.json")).unwrap();
+    data["features"].as_array().unwrap().iter().filter_map(|f| f.as_object().and_then(PhotonProvider::map_row)).collect()
+  }
+
+  #[test]
+  fn osm_tags_map_to_geonames_feature_codes() {
+    assert_eq!(osm_feature_code("place", "city", "", true), "PPLC");
+    assert_eq!(osm_feature_code("place", "village", "", false), "PPL");
+    assert_eq!(osm_feature_code("place", "suburb", "", false), "PPLX");
+    assert_eq!(osm_feature_code("boundary", "administrative", "4", false), "ADM1");
+    assert_eq!(osm_feature_code("boundary", "administrative", "state", false), "ADM1");
+    assert_eq!(osm_feature_code("boundary", "administrative", "6", false), "ADM2");
+    assert_eq!(osm_feature_code("boundary", "administrative", "10", false), "ADMD");
+    assert_eq!(osm_feature_code("railway", "station", "", false), "RSTN");
+    assert_eq!(osm_feature_code("amenity", "cafe", "", false), "");
+  }
+
+  #[test]
+  fn nominatim_rows_use_address_type_capital_flag_and_population() {
+    let rows = nominatim_rows();
+    assert_eq!(rows.len(), 3);
+    let paris = &rows[0];
+    assert_eq!(paris.name, "Paris");
+    assert_eq!(paris.fcode, "PPLC");
+    assert_eq!(paris.pop, 2165423);
+    assert_eq!(paris.country_code.as_deref(), Some(correct_country_code("FR").as_str()));
+    assert_eq!(paris.admin_name.as_deref(), Some("Île-de-France"));
+    assert_eq!((paris.lat, paris.lng), (48.8588897, 2.3200410));
+    let texas = &rows[1];
+    assert_eq!(texas.fcode, "PPL");
+    assert_eq!(texas.pop, 24476);
+    assert_eq!(texas.admin_name.as_deref(), Some("Texas"));
+    let station = &rows[2];
+    assert_eq!(station.name, "Gare du Nord");
+    assert_eq!(station.fcode, "RSTN");
+  }
+
+  #[test]
+  fn photon_rows_skip_features_without_geometry() {
+    let rows = photon_rows();
+    assert_eq!(rows.len(), 3);
+    assert_eq!(rows[0].fcode, "PPL");
+    assert_eq!(rows[0].pop, 114394);
+    assert_eq!(rows[0].admin_name.as_deref(), Some("Illinois"));
+    assert_eq!((rows[0].lat, rows[0].lng), (39.7990175, -89.6501481));
+    assert_eq!(rows[2].name, "Springfield Township");
+    assert_eq!(rows[2].fcode, "ADM2");
+  }
+
+  #[test]
+  fn filter_rows_drops_other_classes_and_countries() {
+    let query = PlaceQuery::new("Paris", &None, &None, None, false, true, 5);
+    let names: Vec<String> = filter_rows(nominatim_rows(), &query).into_iter().map(|r| r.admin_name.unwrap_or_default()).collect();
+    assert_eq!(names, vec!["Île-de-France", "Texas"]);
+    let all_classes = PlaceQuery::new("Paris", &None, &None, None, true, true, 5);
+    assert_eq!(filter_rows(nominatim_rows(), &all_classes).len(), 3);
+    let in_us = PlaceQuery::new("Paris", &Some("US".to_owned()), &None, None, false, true, 5);
+    let rows = filter_rows(nominatim_rows(), &in_us);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].admin_name.as_deref(), Some("Texas"));
+  }
+
+  #[test]
+  fn filter_rows_sorts_by_weighted_population_before_truncating() {
+    let query = PlaceQuery::new("Springfield", &None, &None, None, false, true, 1);
+    let rows = filter_rows(photon_rows(), &query);
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].admin_name.as_deref(), Some("Massachusetts"));
+    let query = PlaceQuery::new("Springfield", &None, &None, None, false, true, 5);
+    let regions: Vec<String> = filter_rows(photon_rows(), &query).into_iter().map(|r| r.admin_name.unwrap_or_default()).collect();
+    assert_eq!(regions, vec!["Massachusetts", "Illinois", "Ohio"]);
+  }
+}
@@ -2,19 +2,27 @@ use actix_web::web::Query;
 use mysql::prelude::Queryable;
 use serde::{Serialize, Deserialize};
 use serde_json::*;
-use clap::Parser;
 use string_patterns::*;
 use diacritics::*;
-use crate::data::alternative_names::CORRECTED_COUNTRY_CODES;
 use crate::data::mysql::connect_mysql;
 use crate::app::coords::Coords;
 use crate::app::date_conv::iso_string_to_datetime;
 use crate::query_params::InputOptions;
-use crate::{app::date_conv::unixtime_to_utc, data::alternative_names::ALTERNATIVE_NAMES};
+use crate::app::relative_dates::{RelativeDateTime, RelativeInterpretation};
+use crate::app::date_conv::unixtime_to_utc;
 
-use crate::args::*;
 use super::timezonedb::*;
-use crate::{constants::*, app::json_extract::*, app::cached_http_client::*};
+use super::geonames_client::*;
+use super::zone_memo::*;
+use super::geocoder::*;
+use super::locality_index::{search_locality_index, normalize_search_text};
+use super::alternate_names::{match_alternate_names, match_localised_name};
+use super::historic_names::apply_historic_names;
+use super::zone_overrides::match_zone_override;
+use super::countries::{output_country_code, match_country_param};
+use super::reverse_geocoder::{reverse_geocode_local, use_local_reverse_geocoder};
+use super::admin_regions::RegionFilter;
+use crate::{constants::*, app::json_extract::*};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GeoNameRow {
@@ -28,6 +36,12 @@ pub struct GeoNameRow {
     pub country_code: Option<String>,
     #[serde(rename="adminName",skip_serializing_if = "Option::is_none")]
     pub admin_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bearing: Option<f64>,
+    #[serde(rename="historicName",skip_serializing_if = "Option::is_none")]
+    pub historic_name: Option<String>,
 }
 
 impl GeoNameRow {
@@ -50,6 +64,9 @@ impl GeoNameRow {
             pop,
             country_code,
             admin_name,
+            distance: None,
+            bearing: None,
+            historic_name: None,
         }
     }
 
@@ -66,6 +83,9 @@ impl GeoNameRow {
             pop: 0,
             country_code: None,
             admin_name: None,
+            distance: None,
+            bearing: None,
+            historic_name: None,
         }
     }
 
@@ -79,6 +99,9 @@ impl GeoNameRow {
         pop,
         country_code: None,
         admin_name: None,
+        distance: None,
+        bearing: None,
+        historic_name: None,
       }
     }
 
@@ -110,6 +133,19 @@ impl GeoNameRow {
       format!("{}_{}_{}_{}_{}", self.name, self.admin_name.clone().unwrap_or("".to_string()), self.country_code.clone().unwrap_or("".to_string()), self.lat.floor(), self.lng.floor())
     }
 
+    /*
+    * Replace the name and region with those in the given language, or in English if not available
+    */
+    pub fn localise(&mut self, lang: &str) {
+      let cc = self.country_code.clone();
+      if let Some(name) = match_localised_name(&self.name, cc.as_deref(), lang) {
+        self.name = name;
+      }
+      if let Some(admin_name) = self.admin_name.clone() {
+        self.admin_name = match_localised_name(&admin_name, cc.as_deref(), lang).or(Some(admin_name));
+      }
+    }
+
     pub fn weighted_pop(&self) -> u64 {
       if self.fcode.starts_with("P") {
         self.pop as u64 * 8u64
@@ -133,6 +169,10 @@ pub struct Locality {
   population: u32,
   #[serde(rename="zoneName")]
   zone_name: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  distance: Option<f64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  bearing: Option<f64>,
 }
 
 impl Locality {
@@ -147,9 +187,20 @@ impl Locality {
         cc,
         population,
         zone_name,
+        distance: None,
+        bearing: None,
       }
     }
 
+  /*
+  * Add the distance in km and initial bearing in degrees from a reference point
+  */
+  pub fn with_distance(mut self, distance: f64, bearing: f64) -> Locality {
+    self.distance = Some(distance);
+    self.bearing = Some(bearing);
+    self
+  }
+
   pub fn weight(&self, text: &str) -> u32 {
     let plain = self.ascii_name.to_lowercase();
     let normal = self.name.to_lowercase();
@@ -179,7 +230,43 @@ impl Locality {
     let exact_match: u32 = if ref_name == text { if ref_name.len() > 3 { 4 } else { 3 } } else { 2 };
     let start_weight: u32 = if start_word_index == main_word_index { 2 } else { 1 };
     let weight: u32 = if pos <= 20 { 20 - pos } else { 0 };
-    ((self.population + 5000) / 800) * weight * start_weight * exact_match
+    self.base_weight() * weight * start_weight * exact_match
+  }
+
+  /*
+  * Population component of the search weight
+  */
+  pub fn base_weight(&self) -> u32 {
+    (self.population + 5000) / 800
+  }
+
+  pub fn cc_code(&self) -> String {
+    self.cc.clone()
+  }
+
+  pub fn zone_name(&self) -> &str {
+    &self.zone_name
+  }
+
+  /*
+  * Replace the name and region with those in the given language, else in English, keeping the original name otherwise
+  */
+  pub fn localise(&mut self, lang: &str) {
+    self.name = match_localised_name(&self.name, Some(&self.cc), lang).unwrap_or(self.name.clone());
+    if let Some(admin_name) = match_localised_name(&self.admin_name, Some(&self.cc), lang) {
+      self.admin_name = admin_name;
+    }
+  }
+
+  /*
+  * Whether the first-level division is within the region given via reg
+  */
+  pub fn in_region(&self, filter: &RegionFilter) -> bool {
+    filter.matches(&self.admin_name, &self.cc)
+  }
+
+  pub fn search_names(&self) -> [&str; 2] {
+    [self.name.as_str(), self.ascii_name.as_str()]
   }
 
   pub fn cc_suffix(&self) -> String {
@@ -237,17 +324,12 @@ pub fn fetch_locality_rows(sql: String) -> Vec<Locality> {
 
 
 
-pub fn match_locality(text: &str, cc: &Option<String>, max: u8) -> Vec<Locality> {
-  let limit = if max < 40 { max + 10 } else if max < 80 { max + 20 } else if max < 225 { max + 30 } else { 255 };
-  let cc_ref = if let Some(cc_str) = cc { cc_str.to_owned().to_uppercase() } else { "".to_owned() };
-  let cc_len = cc_ref.len();
-  let has_cc = cc_ref != "ALL" && cc_len > 1 && cc_len < 3;
-  let country_clause = if has_cc { format!(" AND cc = '{}'", cc_ref) } else { "".to_owned() };
-  let sql = format!("select name, ascii_name, admin_name, lat, lng, cc, population, zone_name from cities WHERE (name REGEXP '[[:<:]]{}' OR ascii_name REGEXP '[[:<:]]{}') {} ORDER BY population DESC LIMIT {}", text,text, country_clause, limit);
-  let mut rows = fetch_locality_rows(sql);
-  let lc_text = text.to_lowercase();
-  rows.sort_by(|a, b| b.weight(&lc_text).cmp(&a.weight(&lc_text)));
-  rows
+/*
+* Match localities in the cities table by word prefix, within names or with typos depending on fuzzy (0-100),
+* optionally within a region such as IN (Indiana) with cc=US or any first-level division starting with the given text
+*/
+pub fn match_locality(text: &str, cc: &Option<String>, region: &Option<String>, max: u8, fuzzy: u8) -> Vec<Locality> {
+  search_locality_index(text, cc, RegionFilter::new(region, cc).as_ref(), max, fuzzy)
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -329,13 +411,37 @@ pub struct GeoTimeInfo {
     time: Option<TimeZone>,
 }
 
-fn match_geonames_username() -> String {
-  let args = Args::parse();
-  let un = args.geoname;
-  if un.len() > 2 {
-    un
-  } else {
-    dotenv::var("geonames_username").unwrap_or(GEONAMES_USERNAME_DEFAULT.to_owned())
+impl GeoTimeInfo {
+  /*
+  * Neither place names nor time zone data could be found
+  */
+  pub fn is_empty(&self) -> bool {
+    self.placenames.is_empty() && self.time.is_none()
+  }
+
+  pub fn add_epochs(&mut self) {
+    if let Some(time) = self.time.as_mut() {
+      time.add_epochs();
+    }
+  }
+
+  pub fn set_relative(&mut self, relative: RelativeInterpretation) {
+    if let Some(time) = self.time.as_mut() {
+      time.set_relative(relative);
+    }
+  }
+
+  pub fn localise(&mut self, lang: &str) {
+    for row in self.placenames.iter_mut() {
+      row.localise(lang);
+    }
+  }
+
+  /*
+  * Add place names and the sovereign state in use on the reference date
+  */
+  pub fn add_historic_names(&mut self, date: &str) {
+    apply_historic_names(&mut self.placenames, date);
   }
 }
 
@@ -354,47 +460,32 @@ fn match_max_nearby_radius() -> String {
 }
 
 pub async fn fetch_from_geonames(method: &str, lat: f64, lng: f64) -> Option<Map<String, Value>> {
-  let url = format!("{}/{}", GEONAMES_API_BASE, method);
-  let lat_str = lat.to_string();
-  let lng_str = lng.to_string();
-  let uname = match_geonames_username();
-  //let client = reqwest::Client::new();
-  let client = get_cached_http_client();
-  let result = match method {
-    "findNearbyJSON" => client.get(url).query(&[
-        ("username", &uname),
-        ("lat", &lat_str),
-        ("lng", &lng_str),
-        ("featureClass", &"P".to_owned()),
-        ("radius", &match_max_nearby_radius())
-        ]).send()
-      .await
-      .expect("failed to get response")
-      .text()
-      .await
-    ,
-    _ => client.get(url).query(&[
-        ("username", &uname),
-        ("lat", &lat_str),
-        ("lng", &lng_str),
-      ]).send()
-        .await
-        .expect("failed to get response")
-        .text()
-        .await
-  };
-  if let Ok(result_string) = result {
-      let data: Map<String, Value> = serde_json::from_str(&result_string).unwrap();
-      Some(data.clone())
-  } else {
-      None
-  }
+  let client = geonames_client();
+  match method {
+    "findNearbyJSON" => client.find_nearby(lat, lng, &match_max_nearby_radius()).await,
+    "timezoneJSON" => client.timezone(lat, lng).await,
+    "extendedFindNearbyJSON" => client.extended_find_nearby(lat, lng).await,
+    _ => client.fetch(method, vec![("lat".to_owned(), lat.to_string()), ("lng".to_owned(), lng.to_string())]).await
+  }.ok()
 }
 
 
 pub async fn fetch_extended_from_geonames(lat: f64, lng: f64) -> Vec<GeoNameRow> {
+  if use_local_reverse_geocoder() {
+    let rows = reverse_geocode_local(lat, lng);
+    if !rows.is_empty() {
+      return rows;
+    }
+  }
+  if let Some(MemoValue::Placenames(rows)) = memo_lookup(MemoKind::Placenames, lat, lng) {
+    return rows;
+  }
   let output = fetch_from_geonames("extendedFindNearbyJSON", lat, lng).await;
-  map_json_to_geoname_rows(output, Some((lat, lng))).await
+  let rows = map_json_to_geoname_rows(output, Some((lat, lng))).await;
+  if !rows.is_empty() {
+    memo_record(MemoKind::Placenames, lat, lng, MemoValue::Placenames(rows.clone()));
+  }
+  rows
 }
 
 pub async fn map_json_to_geoname_rows(output: Option<Map<String, Value>>, lat_lng: Option<(f64, f64)>) -> Vec<GeoNameRow> {
@@ -478,11 +569,15 @@ pub async fn fetch_nearby_from_geonames(lat: f64, lng: f64) -> Vec<GeoNameRow> {
 }
 
 pub async fn fetch_tz_from_geonames(lat: f64, lng: f64) -> Option<TimeZoneInfo> {
+  if let Some(MemoValue::Zone(tz_data)) = memo_lookup(MemoKind::Zone, lat, lng) {
+    return Some(tz_data);
+  }
   let data = fetch_from_geonames("timezoneJSON", lat, lng).await;
   match data {
       Some(item_data) => {
         let tz_data = TimeZoneInfo::new(item_data);
         if tz_data.tz.len() > 3 {
+          memo_record(MemoKind::Zone, lat, lng, MemoValue::Zone(tz_data.clone()));
           Some(tz_data)
         } else {
           None
@@ -506,18 +601,81 @@ pub async fn fetch_timezone_from_place_reference(place: &str, cc: &Option<String
   }
 }
 
-pub async fn extract_zone_name_from_place_params(params: &Query<InputOptions>) -> Option<(TimeZoneInfo, Coords)> {
+/*
+* Place, country and region references from the query parameters. The region only applies with a country.
+*/
+fn match_place_params(params: &Query<InputOptions>) -> (String, Option<String>, Option<String>) {
   let place_ref = params.place.clone().unwrap_or("".to_string());
   let has_place = place_ref.len() > 2;
-  let cc_ref = params.cc.clone().unwrap_or("".to_owned());
-  let has_cc = cc_ref.len() > 1 && cc_ref.len() < 4;
-  let cc = if has_cc { Some(cc_ref) } else { None };
+  let cc = match_country_param(&params.cc);
+  let has_cc = cc.is_some();
   let match_by_place = has_place && has_cc;
   let reg_ref = if match_by_place { params.reg.clone().unwrap_or("".to_owned()) } else { "".to_owned() };
   let region = if reg_ref.len() > 1 { Some(reg_ref) } else { None };
+  (place_ref, cc, region)
+}
+
+pub async fn extract_zone_name_from_place_params(params: &Query<InputOptions>) -> Option<(TimeZoneInfo, Coords)> {
+  let (place_ref, cc, region) = match_place_params(params);
   fetch_timezone_from_place_reference(&place_ref, &cc, &region).await
 }
 
+/*
+* Place matching a place name exactly, with the zone at its coordinates
+*/
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlaceZoneCandidate {
+  pub name: String,
+  #[serde(rename="adminName",skip_serializing_if = "Option::is_none")]
+  pub admin_name: Option<String>,
+  #[serde(rename="countryCode",skip_serializing_if = "Option::is_none")]
+  pub country_code: Option<String>,
+  pub lat: f64,
+  pub lng: f64,
+  #[serde(rename="zoneName")]
+  pub zone_name: String,
+}
+
+/*
+* Places in the country of the best match whose name matches the place reference exactly, each with its zone,
+* or else the best match alone. Springfield in the US, for instance, yields places in the Eastern and Central zones.
+*/
+pub async fn match_place_zone_candidates(params: &Query<InputOptions>) -> Vec<PlaceZoneCandidate> {
+  let (place_ref, cc, region) = match_place_params(params);
+  if place_ref.len() < 3 {
+    return vec![];
+  }
+  let rows = search_by_fuzzy_names(&place_ref, &cc, &region, None, false, true, PLACE_CANDIDATES_MAX * 2).await;
+  let first_cc = rows.first().and_then(|row| row.country_code.clone());
+  let key = normalize_search_text(&place_ref);
+  let mut keys: Vec<String> = vec![];
+  let mut matched: Vec<&GeoNameRow> = rows.iter().filter(|row| {
+    row.country_code == first_cc && (normalize_search_text(&row.name) == key || normalize_search_text(&row.toponym) == key)
+  }).filter(|row| {
+    let row_key = row.to_key();
+    let is_new = !keys.contains(&row_key);
+    keys.push(row_key);
+    is_new
+  }).take(PLACE_CANDIDATES_MAX as usize).collect();
+  if matched.is_empty() {
+    matched = rows.iter().take(1).collect();
+  }
+  let mut candidates: Vec<PlaceZoneCandidate> = vec![];
+  for row in matched {
+    if let Some(tz_data) = fetch_tz_from_geonames(row.lat, row.lng).await {
+      candidates.push(PlaceZoneCandidate {
+        name: row.name.clone(),
+        admin_name: row.admin_name.clone(),
+        country_code: row.country_code.clone(),
+        lat: row.lat,
+        lng: row.lng,
+        zone_name: tz_data.tz,
+      });
+    }
+  }
+  candidates
+}
+
 pub fn extract_best_lat_lng_from_placenames(placenames: &Vec<GeoNameRow>, lat: f64, lng: f64) -> (f64, f64) {
   if let Some(last_row) = placenames.last() {
     (last_row.lat, last_row.lng)
@@ -541,13 +699,24 @@ pub fn extract_time_from_first_row(placenames: &Vec<GeoNameRow>, lng: f64, utc_s
   time
 }
 
+/*
+* Zone from the dated overrides table where a point's zone assignment differed at the time, e.g. Alsace-Lorraine before 1918
+*/
+fn match_override_time_zone(lat: f64, lng: f64, utc_string: &str, enforce_dst: bool) -> Option<TimeZone> {
+  match_zone_override(lat, lng, utc_string)
+    .and_then(|zone_override| match_current_time_zone(&zone_override.zone_name, utc_string, Some(lng), enforce_dst))
+    .filter(|time_row| time_row.zone_name.len() > 2)
+}
+
 pub async fn fetch_geo_time_info(lat: f64, lng: f64, utc_string: &str, enforce_dst: bool) -> GeoTimeInfo {
   let placenames = fetch_extended_from_geonames(lat, lng).await;
-  let mut time: Option<TimeZone> = None;
-  let mut time_matched = false;
+  let mut time: Option<TimeZone> = match_override_time_zone(lat, lng, utc_string, enforce_dst);
+  let mut time_matched = time.is_some();
   let (best_lat, best_lng) = extract_best_lat_lng_from_placenames(&placenames, lat, lng);
 
-  if let Some(tz_item) = fetch_tz_from_geonames(best_lat, best_lng).await {
+  if time_matched {
+    // the override applies, so no upstream zone lookup is needed
+  } else if let Some(tz_item) = fetch_tz_from_geonames(best_lat, best_lng).await {
     if tz_item.tz.len() > 2 {
       time = match_current_time_zone(tz_item.tz.as_str(), utc_string, Some(lng), enforce_dst);
       if let Some(time_row) = time.clone() {
@@ -564,6 +733,18 @@ pub async fn fetch_geo_time_info(lat: f64, lng: f64, utc_string: &str, enforce_d
   }
 }
 
+/*
+* Evaluate a relative date expression in the local time of the zone matched for the coordinates
+*/
+pub async fn resolve_relative_datetime_at_coords(rel: &RelativeDateTime, local: bool, coords: Coords, enforce_dst: bool) -> (String, RelativeInterpretation) {
+  let zone_name = if local && !rel.is_instant() {
+    fetch_tz_from_geonames(coords.lat, coords.lng).await.map(|tz_item| tz_item.tz)
+  } else {
+    None
+  };
+  resolve_relative_datetime(rel, local, zone_name.as_deref(), coords.lng, enforce_dst)
+}
+
 pub async fn fetch_adjusted_date_str(lat: f64, lng: f64, utc_string: &str, enforce_dst: bool) ->String {
   let mut adjusted_dt = utc_string.to_owned();
   if let Some(tz_info) = fetch_time_info_from_coords(lat, lng, utc_string, enforce_dst).await {
@@ -632,7 +813,9 @@ pub async fn fetch_time_info_from_coords_adjusted(coords: Coords, utc_string: &s
 }
 
 pub async fn fetch_time_info_from_coords(lat: f64, lng: f64, utc_string: &str, enforce_dst: bool) -> Option<TimeZone> {
-  if let Some(tz_item) = fetch_tz_from_geonames(lat, lng).await {
+  if let Some(time) = match_override_time_zone(lat, lng, utc_string, enforce_dst) {
+    Some(time)
+  } else if let Some(tz_item) = fetch_tz_from_geonames(lat, lng).await {
       match_current_time_zone(&tz_item.tz, utc_string, Some(lng), enforce_dst)
   } else {
     let rows = fetch_nearby_from_geonames(lat, lng).await;
@@ -655,10 +838,23 @@ pub async fn fetch_time_info_from_coords(lat: f64, lng: f64, utc_string: &str, e
   }
 }
 
+/*
+* Search place names with the configured geocoding provider
+*/
 pub async fn search_by_fuzzy_names(search: &str, cc: &Option<String>, region: &Option<String>, fuzzy: Option<f32>, all_classes: bool, included: bool, max_rows: u8) -> Vec<GeoNameRow> {
-  let url = format!("{}/{}", GEONAMES_API_BASE, "searchJSON"); 
-  let client = get_cached_http_client();
-  let uname = match_geonames_username();
+  let query = PlaceQuery::new(search, cc, region, fuzzy, all_classes, included, max_rows);
+  search_with_regions(geocoding_provider(None).as_ref(), &query).await
+}
+
+/*
+* Search place names with the provider named in the request or else the configured default
+*/
+pub async fn search_places(query: &PlaceQuery, provider: Option<&str>) -> Vec<GeoNameRow> {
+  search_with_regions(geocoding_provider(provider).as_ref(), query).await
+}
+
+pub async fn search_geonames_by_fuzzy_names(search: &str, cc: &Option<String>, region: &Option<String>, fuzzy: Option<f32>, all_classes: bool, included: bool, max_rows: u8) -> Vec<GeoNameRow> {
+  let client = geonames_client();
   let fuzzy_int = if let Some(f_int) = fuzzy { f_int } else { 1f32 };
   let fuzzy_string = fuzzy_int.to_string();
   let mut search_str: String = search.to_owned().clone();
@@ -667,7 +863,6 @@ pub async fn search_by_fuzzy_names(search: &str, cc: &Option<String>, region: &O
     search_str.push_str(rg_str);
   }
   let mut items: Vec<(&str, &str)> = vec![
-        ("username", &uname),
         ("q", &search_str),
         ("fuzzy", &fuzzy_string)];
   if !all_classes {
@@ -686,65 +881,47 @@ pub async fn search_by_fuzzy_names(search: &str, cc: &Option<String>, region: &O
     let ml = if search_len < 2 { 1 } else { 2 };
     items.push(("name_startsWith", &search[0..ml] ));
   }
-  let m_str = string_to_static_str(max_rows);
+  let m_str = max_rows.to_string();
   if max_rows > 1 {
-    items.push(("maxRows", m_str ));
-  }
-  let result = client.get(url).query(&items).send()
-        .await
-        .expect("failed to get response")
-        .text()
-        .await;
-  if let Ok(result_string) = result {
-      let data: Map<String, Value> = serde_json::from_str(&result_string).unwrap();
-      map_json_to_geoname_rows(Some(data), None).await
+    items.push(("maxRows", &m_str ));
+  }
+  let query = items.into_iter().map(|(k, v)| (k.to_owned(), v.to_owned())).collect();
+  let data = client.search(query).await.ok();
+  if data.is_some() {
+      map_json_to_geoname_rows(data, None).await
   } else {
       vec![]
   }
 }
 
 pub fn matches_alternative(search: &str) -> Option<String> {
-  let text = simplify_string(search);
-  let pair_opt = ALTERNATIVE_NAMES.into_iter().find(|pair| simplify_string(pair.0).starts_with(&text));
-  if let Some(pair) = pair_opt {
-    Some(pair.1.to_owned())
-  } else {
-    None
-  }
+  match_alternate_names(search).into_iter().next().map(|alt| alt.current_name)
 }
 
 pub fn correct_country_code_optional(cc_opt: Option<String>) -> Option<String> {
-  if let Some(cc) = cc_opt {
-    if let Some((_cc, new_cc)) = CORRECTED_COUNTRY_CODES.into_iter().find(|pair| pair.0.to_owned() == cc) {
-      Some(new_cc.to_string())
-    } else {
-      Some(cc)
-    }
-  } else {
-    None
-  }
+  cc_opt.map(|cc| output_country_code(&cc))
 }
 
 pub fn correct_country_code(cc: &str) -> String {
-  if let Some((_cc, new_cc)) = CORRECTED_COUNTRY_CODES.into_iter().find(|pair| pair.0.to_owned() == cc) {
-    new_cc
-  } else {
-    cc
-  }.to_string()
+  output_country_code(cc)
 }
 
-pub async fn list_by_fuzzy_name_match(search: &str, cc: &Option<String>, region: &Option<String>, fuzzy: Option<f32>, max: u8) -> Vec<GeoNameSimple> {
+pub async fn list_by_fuzzy_name_match(search: &str, cc: &Option<String>, region: &Option<String>, fuzzy: Option<f32>, max: u8, provider: Option<&str>, lang: Option<&str>) -> Vec<GeoNameSimple> {
   let max_initial_search = if max < 10 { 20 } else if max < 127 {  max * 2 } else { 255 };
-  let items = search_by_fuzzy_names(search, cc, region, fuzzy, false, false, max_initial_search).await;
+  let query = PlaceQuery::new(search, cc, region, fuzzy, false, false, max_initial_search);
+  let items = search_places(&query, provider).await;
   let mut rows: Vec<GeoNameSimple> = Vec::new();
   let mut keys: Vec<String> = Vec::new();
   let mut count: usize = 0;
   let max_count = max as usize;
-  for row in items {
+  for mut row in items {
     if count < max_count {
       let key = row.to_key();
       if !keys.contains(&key) && is_in_geo_row_alternative(&row, search) {
         keys.push(key);
+        if let Some(lang_code) = lang {
+          row.localise(lang_code);
+        }
         rows.push(row.to_simple());
         count += 1;
       }
@@ -753,8 +930,8 @@ pub async fn list_by_fuzzy_name_match(search: &str, cc: &Option<String>, region:
   rows
 }
 
-pub async fn list_by_fuzzy_localities(search: &str, cc: &Option<String>, region: &Option<String>, fuzzy: Option<f32>, max: u8) -> Vec<GeoNameSimple> {
-  let local_rows = if fuzzy.unwrap_or(100f32) < 91f32 { vec![] } else { match_locality(search, cc, max) };
+pub async fn list_by_fuzzy_localities(search: &str, cc: &Option<String>, region: &Option<String>, fuzzy: Option<f32>, max: u8, provider: Option<&str>, lang: Option<&str>) -> Vec<GeoNameSimple> {
+  let local_rows = if fuzzy.unwrap_or(100f32) < 91f32 { vec![] } else { match_locality(search, cc, region, max, 100) };
   let str_len = search.len();
   let min_long = if max < 2 { 0 } else if max < 5 { max - 2 } else if max < 20 { 5 } else { 6 } as usize;
   let mut min = min_long;
@@ -774,9 +951,14 @@ pub async fn list_by_fuzzy_localities(search: &str, cc: &Option<String>, region:
     }
   }
   if local_rows.len() < min {
-    list_by_fuzzy_name_match(search, cc, region, fuzzy, max).await
+    list_by_fuzzy_name_match(search, cc, region, fuzzy, max, provider, lang).await
   } else {
-    local_rows.into_iter().map(|row| row.to_simple()).collect()
+    local_rows.into_iter().map(|mut row| {
+      if let Some(lang_code) = lang {
+        row.localise(lang_code);
+      }
+      row.to_simple()
+    }).collect()
   }
 }
 
@@ -805,15 +987,10 @@ fn is_in_simple_string(text: &String, search: &str) -> bool {
   simple_text.pattern_match(&pat, true)
 }
 
-fn simplify_string(text: &str) -> String {
+pub fn simplify_string(text: &str) -> String {
   remove_diacritics(text).to_lowercase()
 }
 
-fn string_to_static_str(value: u8) -> &'static str {
-  let s = value.to_string();
-  Box::leak(s.into_boxed_str())
-}
-
 pub fn is_valid_zone_name(zn: &str) -> bool {
   zn.len() > 4 && zn.contains("/") && !zn.ends_with("/") && !zn.starts_with("/")
 }
\ No newline at end of file
@@ -0,0 +1,416 @@
+use std::future::Future;
+use std::pin::Pin;
+use serde_json::{Map, Value};
+use clap::Parser;
+use crate::args::*;
+use crate::constants::*;
+use crate::app::cached_http_client::get_cached_http_client;
+use crate::app::app_cache::*;
+use super::geonames_quota::*;
+
+pub type GeoNamesFuture<'a> = Pin<Box<dyn Future<Output = Result<Map<String, Value>, GeoNamesError>> + 'a>>;
+
+/*
+* Transport for the GeoNames web services. Implementations only need to fetch a JSON object for a method
+* and query parameters, while the provided methods build the queries for the services used here.
+*/
+pub trait GeoNamesClient {
+  fn fetch(&self, method: &str, query: Vec<(String, String)>) -> GeoNamesFuture<'_>;
+
+  fn timezone(&self, lat: f64, lng: f64) -> GeoNamesFuture<'_> {
+    self.fetch("timezoneJSON", lat_lng_query(lat, lng))
+  }
+
+  fn extended_find_nearby(&self, lat: f64, lng: f64) -> GeoNamesFuture<'_> {
+    self.fetch("extendedFindNearbyJSON", lat_lng_query(lat, lng))
+  }
+
+  fn find_nearby(&self, lat: f64, lng: f64, radius: &str) -> GeoNamesFuture<'_> {
+    let mut query = lat_lng_query(lat, lng);
+    query.push(("featureClass".to_owned(), "P".to_owned()));
+    query.push(("radius".to_owned(), radius.to_owned()));
+    self.fetch("findNearbyJSON", query)
+  }
+
+  fn search(&self, query: Vec<(String, String)>) -> GeoNamesFuture<'_> {
+    self.fetch("searchJSON", query)
+  }
+}
+
+fn lat_lng_query(lat: f64, lng: f64) -> Vec<(String, String)> {
+  vec![
+    ("lat".to_owned(), lat.to_string()),
+    ("lng".to_owned(), lng.to_string()),
+  ]
+}
+
+/*
+* File name for a recorded response, derived from the method and query parameters other than the user name
+*/
+pub fn fixture_key(method: &str, query: &[(String, String)]) -> String {
+  let mut pairs: Vec<String> = query.iter().filter(|(k, _)| k != "username").map(|(k, v)| format!("{}-{}", k, v)).collect();
+  pairs.sort();
+  let raw = format!("{}__{}", method, pairs.join("_"));
+  let key: String = raw.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' { c } else { '~' }).collect();
+  format!("{}.json", key)
+}
+
+/*
+* Parse a response body, treating GeoNames status objects as errors
+*/
+fn parse_json_object(text: &str) -> Result<Map<String, Value>, GeoNamesError> {
+  let data = serde_json::from_str::<Map<String, Value>>(text).map_err(|e| GeoNamesError::Transport(e.to_string()))?;
+  match GeoNamesError::from_response(&data) {
+    Some(error) => Err(error),
+    None => Ok(data)
+  }
+}
+
+/*
+* Credentials for one GeoNames account. Premium accounts may use a different base URL.
+*/
+#[derive(Debug, Clone)]
+pub struct GeoNamesAccount {
+  pub username: String,
+  pub base_url: String,
+  pub hourly_limit: u32,
+  pub daily_limit: u32,
+}
+
+impl GeoNamesAccount {
+  pub fn new(username: &str, base_url: &str) -> GeoNamesAccount {
+    GeoNamesAccount {
+      username: username.to_owned(),
+      base_url: base_url.trim_end_matches('/').to_owned(),
+      hourly_limit: match_limit("geonames_hourly_limit", GEONAMES_HOURLY_LIMIT_DEFAULT),
+      daily_limit: match_limit("geonames_daily_limit", GEONAMES_DAILY_LIMIT_DEFAULT),
+    }
+  }
+
+  /*
+  * Parse an entry such as user1 or premium|https://secure.geonames.net|20000|200000,
+  * where the base URL and the hourly and daily limits are optional
+  */
+  pub fn from_entry(entry: &str, default_base: &str) -> Option<GeoNamesAccount> {
+    let parts: Vec<&str> = entry.split('|').map(|p| p.trim()).collect();
+    let username = parts.first().filter(|un| !un.is_empty())?;
+    let base_url = parts.get(1).filter(|url| !url.is_empty()).copied().unwrap_or(default_base);
+    let mut account = GeoNamesAccount::new(username, base_url);
+    if let Some(hourly) = parts.get(2).and_then(|v| v.parse::<u32>().ok()) {
+      account.hourly_limit = hourly;
+    }
+    if let Some(daily) = parts.get(3).and_then(|v| v.parse::<u32>().ok()) {
+      account.daily_limit = daily;
+    }
+    Some(account)
+  }
+}
+
+/*
+* Order in which accounts are tried. With priority the first available account in the configured list is used,
+* while round robin starts with the next account on each request. Either way later accounts serve as failover.
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccountSelection {
+  Priority,
+  RoundRobin,
+}
+
+/*
+* Live client for api.geonames.org or a compatible base URL, optionally recording responses as fixtures
+*/
+pub struct HttpGeoNamesClient {
+  pub accounts: Vec<GeoNamesAccount>,
+  pub selection: AccountSelection,
+  pub record_dir: Option<String>,
+}
+
+impl HttpGeoNamesClient {
+  pub fn new(accounts: Vec<GeoNamesAccount>, selection: AccountSelection) -> HttpGeoNamesClient {
+    for account in accounts.iter() {
+      register_geonames_account(&account.username, account.hourly_limit, account.daily_limit);
+    }
+    HttpGeoNamesClient {
+      accounts,
+      selection,
+      record_dir: None,
+    }
+  }
+
+  fn ordered_accounts(&self) -> Vec<&GeoNamesAccount> {
+    let num_accounts = self.accounts.len();
+    let start = match self.selection {
+      AccountSelection::RoundRobin if num_accounts > 0 => next_geonames_rotation() % num_accounts,
+      _ => 0
+    };
+    (0..num_accounts).map(|i| &self.accounts[(start + i) % num_accounts]).collect()
+  }
+
+  /*
+  * Single request counted against the account quota. Responses served from the HTTP cache are refunded.
+  */
+  async fn fetch_once(&self, account: &GeoNamesAccount, method: &str, query: &[(String, String)]) -> Result<Map<String, Value>, GeoNamesError> {
+    acquire_geonames_token(&account.username)?;
+    let url = format!("{}/{}", account.base_url, method);
+    let mut items = query.to_vec();
+    items.insert(0, ("username".to_owned(), account.username.clone()));
+    let client = get_cached_http_client();
+    let response = client.get(url).query(&items).send().await.map_err(|e| GeoNamesError::Transport(e.to_string()))?;
+    let from_cache = response.headers().get("x-cache").map(|v| v.as_bytes() == b"HIT").unwrap_or(false);
+    if from_cache {
+      release_geonames_token(&account.username);
+    }
+    let text = response.text().await.map_err(|e| GeoNamesError::Transport(e.to_string()))?;
+    let result = parse_json_object(&text);
+    if result.is_ok() {
+      self.record(method, query, &text);
+    }
+    result
+  }
+
+  /*
+  * Request with one account, retrying temporary failures with exponential backoff: 250ms, 500ms, ...
+  */
+  async fn fetch_with_account(&self, account: &GeoNamesAccount, method: &str, query: &[(String, String)]) -> Result<Map<String, Value>, GeoNamesError> {
+    let mut attempt = 0;
+    loop {
+      match self.fetch_once(account, method, query).await {
+        Err(error) if error.is_retryable() && attempt < GEONAMES_MAX_RETRIES => {
+          record_geonames_error(&account.username, &error);
+          let delay = GEONAMES_RETRY_DELAY_MS * 2u64.pow(attempt);
+          actix_web::rt::time::sleep(std::time::Duration::from_millis(delay)).await;
+          attempt += 1;
+        },
+        Err(error) => {
+          record_geonames_error(&account.username, &error);
+          return Err(error);
+        },
+        Ok(data) => return Ok(data)
+      }
+    }
+  }
+
+  fn record(&self, method: &str, query: &[(String, String)], text: &str) {
+    if let Some(dir) = self.record_dir.clone() {
+      let path = std::path::Path::new(&dir).join(fixture_key(method, query));
+      if let Err(e) = std::fs::write(&path, text) {
+        println!("{:?}", e);
+      }
+    }
+  }
+}
+
+impl GeoNamesClient for HttpGeoNamesClient {
+  fn fetch(&self, method: &str, query: Vec<(String, String)>) -> GeoNamesFuture<'_> {
+    let method = method.to_owned();
+    Box::pin(async move {
+      let mut last_error = GeoNamesError::RateLimited(geonames_retry_after().unwrap_or(1));
+      for account in self.ordered_accounts() {
+        match self.fetch_with_account(account, &method, &query).await {
+          // fail over to the next account when credentials are exhausted or invalid
+          Err(error) if error.is_limit() => last_error = error,
+          result => return result
+        }
+      }
+      record_geonames_limit(&last_error);
+      Err(last_error)
+    })
+  }
+}
+
+/*
+* Offline client replaying responses recorded by HttpGeoNamesClient from a fixture directory
+*/
+pub struct FixtureGeoNamesClient {
+  pub dir: String,
+}
+
+impl GeoNamesClient for FixtureGeoNamesClient {
+  fn fetch(&self, method: &str, query: Vec<(String, String)>) -> GeoNamesFuture<'_> {
+    let path = std::path::Path::new(&self.dir).join(fixture_key(method, &query));
+    Box::pin(async move {
+      let text = std::fs::read_to_string(&path).map_err(|e| GeoNamesError::Transport(format!("{}: {}", path.display(), e)))?;
+      parse_json_object(&text)
+    })
+  }
+}
+
+/*
+* Application-level cache in front of another client. Coordinates are rounded and search strings normalised
+* both in the cache key and the upstream request, so nearby points and variant spellings share entries.
+*/
+pub struct CachingGeoNamesClient {
+  pub inner: Box<dyn GeoNamesClient>,
+  pub backend: CacheBackend,
+  pub coord_precision: i32,
+}
+
+impl CachingGeoNamesClient {
+  fn normalise_query(&self, query: Vec<(String, String)>) -> Vec<(String, String)> {
+    let factor = 10f64.powi(self.coord_precision);
+    query.into_iter().map(|(k, v)| {
+      let value = match k.as_str() {
+        "lat" | "lng" => v.parse::<f64>().map(|n| ((n * factor).round() / factor).to_string()).unwrap_or(v),
+        "q" | "name" | "name_equals" | "name_startsWith" => v.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase(),
+        _ => v
+      };
+      (k, value)
+    }).collect()
+  }
+}
+
+pub fn cache_key(method: &str, query: &[(String, String)]) -> String {
+  let mut pairs: Vec<String> = query.iter().filter(|(k, _)| k != "username").map(|(k, v)| format!("{}={}", k, v)).collect();
+  pairs.sort();
+  format!("{}:{}", method, pairs.join("&"))
+}
+
+impl GeoNamesClient for CachingGeoNamesClient {
+  fn fetch(&self, method: &str, query: Vec<(String, String)>) -> GeoNamesFuture<'_> {
+    let query = self.normalise_query(query);
+    let key = cache_key(method, &query);
+    let method = method.to_owned();
+    Box::pin(async move {
+      if let Some(data) = cache_get(&self.backend, &key).await.and_then(|text| serde_json::from_str::<Map<String, Value>>(&text).ok()) {
+        return Ok(data);
+      }
+      let data = self.inner.fetch(&method, query).await?;
+      let ttl = match_cache_ttl(method.starts_with("search"));
+      cache_set(&self.backend, &key, Value::Object(data.clone()).to_string(), ttl).await;
+      Ok(data)
+    })
+  }
+}
+
+pub fn match_geonames_username() -> String {
+  let args = Args::parse();
+  let un = args.geoname;
+  if un.len() > 2 {
+    un
+  } else {
+    dotenv::var("geonames_username").unwrap_or(GEONAMES_USERNAME_DEFAULT.to_owned())
+  }
+}
+
+pub fn match_geonames_api_base() -> String {
+  dotenv::var("geonames_api_base").unwrap_or(GEONAMES_API_BASE.to_owned())
+}
+
+fn match_optional_dir(key: &str) -> Option<String> {
+  dotenv::var(key).ok().filter(|dir| !dir.trim().is_empty())
+}
+
+fn match_limit(key: &str, default_value: u32) -> u32 {
+  dotenv::var(key).ok().and_then(|v| v.parse::<u32>().ok()).unwrap_or(default_value)
+}
+
+/*
+* Accounts from the comma-separated geonames_accounts list, or else the single user name from -g or geonames_username
+*/
+pub fn match_geonames_accounts() -> Vec<GeoNamesAccount> {
+  let base_url = match_geonames_api_base();
+  let accounts: Vec<GeoNamesAccount> = dotenv::var("geonames_accounts").unwrap_or_default()
+    .split(',')
+    .filter_map(|entry| GeoNamesAccount::from_entry(entry, &base_url))
+    .collect();
+  if accounts.is_empty() {
+    vec![GeoNamesAccount::new(&match_geonames_username(), &base_url)]
+  } else {
+    accounts
+  }
+}
+
+pub fn match_account_selection() -> AccountSelection {
+  match dotenv::var("geonames_selection").unwrap_or_default().to_lowercase().replace(['-', '_'], "").as_str() {
+    "roundrobin" | "rotate" => AccountSelection::RoundRobin,
+    _ => AccountSelection::Priority
+  }
+}
+
+/*
+* Select the GeoNames client. A geonames_fixtures directory switches to offline replay, while
+* geonames_record saves live responses in the given directory for later replay.
+* Live requests go through the application cache if cache_backend is set.
+*/
+pub fn geonames_client() -> Box<dyn GeoNamesClient> {
+  if let Some(dir) = match_optional_dir("geonames_fixtures") {
+    Box::new(FixtureGeoNamesClient { dir })
+  } else {
+    let mut client = HttpGeoNamesClient::new(match_geonames_accounts(), match_account_selection());
+    client.record_dir = match_optional_dir("geonames_record");
+    match app_cache_backend() {
+      Some(backend) => Box::new(CachingGeoNamesClient { inner: Box::new(client), backend, coord_precision: match_cache_coord_precision() }),
+      None => Box::new(client)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn usernames(accounts: &[&GeoNamesAccount]) -> Vec<String> {
+    accounts.iter().map(|a| a.username.clone()).collect()
+  }
+
+  #[test]
+  fn account_entries_may_set_a_base_url_and_limits() {
+    let account = GeoNamesAccount::from_entry(" premium | https://secure.geonames.net/ | 20000 | 200000 ", "http://api.geonames.org").unwrap();
+    assert_eq!(account.username, "premium");
+    assert_eq!(account.base_url, "https://secure.geonames.net");
+    assert_eq!(account.hourly_limit, 20000);
+    assert_eq!(account.daily_limit, 200000);
+    let account = GeoNamesAccount::from_entry("free", "http://api.geonames.org").unwrap();
+    assert_eq!(account.base_url, "http://api.geonames.org");
+    assert!(GeoNamesAccount::from_entry(" ", "http://api.geonames.org").is_none());
+  }
+
+  #[test]
+  fn priority_selection_always_starts_with_the_first_account() {
+    let accounts = ["prio_a", "prio_b", "prio_c"].iter().map(|un| GeoNamesAccount::new(un, "http://api.geonames.org")).collect();
+    let client = HttpGeoNamesClient::new(accounts, AccountSelection::Priority);
+    for _ in 0..3 {
+      assert_eq!(usernames(&client.ordered_accounts()), vec!["prio_a", "prio_b", "prio_c"]);
+    }
+  }
+
+  #[test]
+  fn round_robin_selection_rotates_and_keeps_the_others_as_failover() {
+    let accounts = ["rr_a", "rr_b", "rr_c"].iter().map(|un| GeoNamesAccount::new(un, "http://api.geonames.org")).collect();
+    let client = HttpGeoNamesClient::new(accounts, AccountSelection::RoundRobin);
+    let orders: Vec<Vec<String>> = (0..3).map(|_| usernames(&client.ordered_accounts())).collect();
+    let mut first: Vec<String> = orders.iter().map(|order| order[0].clone()).collect();
+    first.sort();
+    assert_eq!(first, vec!["rr_a", "rr_b", "rr_c"]);
+    for order in orders {
+      let start = client.accounts.iter().position(|a| a.username == order[0]).unwrap();
+      let expected: Vec<String> = (0..3).map(|i| client.accounts[(start + i) % 3].username.clone()).collect();
+      assert_eq!(order, expected);
+    }
+  }
+
+  #[test]
+  fn fixture_keys_ignore_the_user_name_and_parameter_order() {
+    let query = vec![("lng".to_owned(), "-0.1".to_owned()), ("username".to_owned(), "demo".to_owned()), ("lat".to_owned(), "51.5".to_owned())];
+    assert_eq!(fixture_key("timezoneJSON", &query), "timezoneJSON__lat-51.5_lng--0.1.json");
+    let query = vec![("q".to_owned(), "São Paulo".to_owned())];
+    assert_eq!(fixture_key("searchJSON", &query), "searchJSON__q-S~o~Paulo.json");
+  }
+
+  #[test]
+  fn cached_queries_share_entries_for_nearby_points_and_name_variants() {
+    let client = CachingGeoNamesClient {
+      inner: Box::new(FixtureGeoNamesClient { dir: "tests/fixtures/geonames".to_owned() }),
+      backend: CacheBackend::Directory { dir: std::env::temp_dir(), max_entries: 1 },
+      coord_precision: 3,
+    };
+    let near = |lat: &str, lng: &str| cache_key("timezoneJSON", &client.normalise_query(vec![
+      ("lat".to_owned(), lat.to_owned()), ("lng".to_owned(), lng.to_owned()), ("username".to_owned(), "demo".to_owned()),
+    ]));
+    assert_eq!(near("51.50741", "-0.12779"), "timezoneJSON:lat=51.507&lng=-0.128");
+    assert_eq!(near("51.50741", "-0.12779"), near("51.5071", "-0.1281"));
+    let name = |q: &str| cache_key("searchJSON", &client.normalise_query(vec![("q".to_owned(), q.to_owned()), ("maxRows".to_owned(), "10".to_owned())]));
+    assert_eq!(name("  New   York "), "searchJSON:maxRows=10&q=new york");
+    assert_eq!(name("  New   York "), name("new york"));
+  }
+}
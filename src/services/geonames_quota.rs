@@ -0,0 +1,456 @@
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::future::Future;
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use crate::constants::*;
+use crate::app::date_conv::{current_timestamp, unixtime_to_utc};
+use crate::app::json_extract::*;
+
+tokio::task_local! {
+  static GEONAMES_LIMIT: Cell<Option<i64>>;
+}
+
+/*
+* Errors reported by GeoNames in a status object with HTTP 200, plus client-side and transport failures.
+* See http://www.geonames.org/export/webservice-exception.html
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GeoNamesError {
+  InvalidUser(String),
+  DailyLimitExceeded,
+  HourlyLimitExceeded,
+  WeeklyLimitExceeded,
+  ServerOverloaded,
+  DatabaseTimeout,
+  // client-side limiter, with the seconds until the next request may be sent
+  RateLimited(i64),
+  Transport(String),
+  Status(u32, String),
+}
+
+impl GeoNamesError {
+  pub fn from_status(value: u32, message: &str) -> GeoNamesError {
+    match value {
+      10 => GeoNamesError::InvalidUser(message.to_owned()),
+      13 => GeoNamesError::DatabaseTimeout,
+      18 => GeoNamesError::DailyLimitExceeded,
+      19 => GeoNamesError::HourlyLimitExceeded,
+      20 => GeoNamesError::WeeklyLimitExceeded,
+      22 => GeoNamesError::ServerOverloaded,
+      _ => GeoNamesError::Status(value, message.to_owned())
+    }
+  }
+
+  /*
+  * Extract an error from a response body such as {"status": {"message": "...", "value": 19}}
+  */
+  pub fn from_response(data: &Map<String, Value>) -> Option<GeoNamesError> {
+    match data.get("status") {
+      Some(Value::Object(status)) => {
+        let value = extract_u32_from_value_map(status, "value");
+        let message = extract_string_from_value_map(status, "message");
+        Some(GeoNamesError::from_status(value, &message))
+      },
+      _ => None
+    }
+  }
+
+  pub fn code(&self) -> String {
+    match self {
+      GeoNamesError::InvalidUser(_) => "10".to_owned(),
+      GeoNamesError::DatabaseTimeout => "13".to_owned(),
+      GeoNamesError::DailyLimitExceeded => "18".to_owned(),
+      GeoNamesError::HourlyLimitExceeded => "19".to_owned(),
+      GeoNamesError::WeeklyLimitExceeded => "20".to_owned(),
+      GeoNamesError::ServerOverloaded => "22".to_owned(),
+      GeoNamesError::RateLimited(_) => "rate_limited".to_owned(),
+      GeoNamesError::Transport(_) => "transport".to_owned(),
+      GeoNamesError::Status(value, _) => value.to_string(),
+    }
+  }
+
+  /*
+  * Account-level errors after which requests with the same credentials cannot succeed for a while
+  */
+  pub fn is_limit(&self) -> bool {
+    matches!(self, GeoNamesError::InvalidUser(_) | GeoNamesError::DailyLimitExceeded | GeoNamesError::HourlyLimitExceeded | GeoNamesError::WeeklyLimitExceeded | GeoNamesError::RateLimited(_))
+  }
+
+  /*
+  * Temporary failures worth retrying after a short delay
+  */
+  pub fn is_retryable(&self) -> bool {
+    matches!(self, GeoNamesError::ServerOverloaded | GeoNamesError::DatabaseTimeout | GeoNamesError::Transport(_))
+  }
+
+  /*
+  * Unix timestamp until which the account should not be used. Limits are assumed to reset
+  * at the start of the next UTC hour, day or week (Monday).
+  */
+  pub fn blocked_until(&self, now: i64) -> Option<i64> {
+    let hour = 3600;
+    let day = 86_400;
+    match self {
+      GeoNamesError::HourlyLimitExceeded => Some((now / hour + 1) * hour),
+      GeoNamesError::DailyLimitExceeded => Some((now / day + 1) * day),
+      // 1 Jan 1970 was a Thursday, so weeks from Monday start 4 days later
+      GeoNamesError::WeeklyLimitExceeded => Some(((now - 4 * day) / (7 * day) + 1) * 7 * day + 4 * day),
+      GeoNamesError::InvalidUser(_) => Some(now + hour),
+      GeoNamesError::RateLimited(secs) => Some(now + secs),
+      _ => None
+    }
+  }
+}
+
+/*
+* Token bucket for one set of GeoNames credentials with hourly and daily allowances
+*/
+#[derive(Debug, Clone)]
+pub struct AccountQuota {
+  pub username: String,
+  pub hourly_limit: u32,
+  pub daily_limit: u32,
+  tokens: f64,
+  last_refill: f64,
+  day: i64,
+  daily_used: u32,
+  pub blocked_until: Option<i64>,
+  pub requests: u64,
+  pub cache_hits: u64,
+  pub errors: BTreeMap<String, u64>,
+  pub last_error: Option<GeoNamesError>,
+}
+
+fn now_secs_f64() -> f64 {
+  match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+    Ok(duration) => duration.as_secs_f64(),
+    Err(_) => 0f64
+  }
+}
+
+impl AccountQuota {
+  pub fn new(username: &str, hourly_limit: u32, daily_limit: u32) -> AccountQuota {
+    AccountQuota {
+      username: username.to_owned(),
+      hourly_limit,
+      daily_limit,
+      tokens: hourly_limit as f64,
+      last_refill: now_secs_f64(),
+      day: current_timestamp() / 86_400,
+      daily_used: 0,
+      blocked_until: None,
+      requests: 0,
+      cache_hits: 0,
+      errors: BTreeMap::new(),
+      last_error: None,
+    }
+  }
+
+  fn refill(&mut self) {
+    let now = now_secs_f64();
+    let elapsed = (now - self.last_refill).max(0f64);
+    self.tokens = (self.tokens + elapsed * self.hourly_limit as f64 / 3600f64).min(self.hourly_limit as f64);
+    self.last_refill = now;
+    let today = current_timestamp() / 86_400;
+    if today != self.day {
+      self.day = today;
+      self.daily_used = 0;
+    }
+    if let Some(until) = self.blocked_until {
+      if until <= current_timestamp() {
+        self.blocked_until = None;
+      }
+    }
+  }
+
+  /*
+  * Seconds until a request may be sent with these credentials, 0 if available now
+  */
+  pub fn retry_after(&mut self) -> i64 {
+    self.refill();
+    let now = current_timestamp();
+    if let Some(until) = self.blocked_until {
+      (until - now).max(1)
+    } else if self.daily_limit > 0 && self.daily_used >= self.daily_limit {
+      (now / 86_400 + 1) * 86_400 - now
+    } else if self.tokens < 1f64 {
+      let rate = self.hourly_limit.max(1) as f64 / 3600f64;
+      ((1f64 - self.tokens) / rate).ceil() as i64
+    } else {
+      0
+    }
+  }
+
+  pub fn acquire(&mut self) -> Result<(), GeoNamesError> {
+    let wait = self.retry_after();
+    if wait > 0 {
+      Err(GeoNamesError::RateLimited(wait))
+    } else {
+      self.tokens -= 1f64;
+      self.daily_used += 1;
+      self.requests += 1;
+      Ok(())
+    }
+  }
+
+  /*
+  * Refund a token for responses served from the HTTP cache without reaching GeoNames
+  */
+  pub fn release(&mut self) {
+    self.tokens = (self.tokens + 1f64).min(self.hourly_limit as f64);
+    self.daily_used = self.daily_used.saturating_sub(1);
+    self.cache_hits += 1;
+  }
+
+  pub fn record_error(&mut self, error: &GeoNamesError) {
+    *self.errors.entry(error.code()).or_insert(0) += 1;
+    if let GeoNamesError::RateLimited(_) = error {
+      // client-side refusals do not extend the block
+    } else if let Some(until) = error.blocked_until(current_timestamp()) {
+      self.blocked_until = Some(until);
+      if *error == GeoNamesError::DailyLimitExceeded {
+        self.daily_used = self.daily_limit;
+      }
+    }
+    self.last_error = Some(error.clone());
+  }
+
+  pub fn status(&mut self) -> QuotaStatus {
+    let retry_after = self.retry_after();
+    QuotaStatus {
+      username: mask_username(&self.username),
+      hourly_limit: self.hourly_limit,
+      daily_limit: self.daily_limit,
+      remaining_hourly: self.tokens.floor().max(0f64) as u32,
+      remaining_daily: self.daily_limit.saturating_sub(self.daily_used),
+      blocked_until: self.blocked_until.map(unixtime_to_utc),
+      retry_after,
+      requests: self.requests,
+      cache_hits: self.cache_hits,
+      errors: self.errors.clone(),
+      last_error: self.last_error.clone(),
+    }
+  }
+}
+
+/*
+* Only show the first two characters of account names in public metrics
+*/
+fn mask_username(username: &str) -> String {
+  let prefix: String = username.chars().take(2).collect();
+  format!("{}***", prefix)
+}
+
+/*
+* Snapshot of the usage and remaining allowance of one GeoNames account
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaStatus {
+  pub username: String,
+  #[serde(rename="hourlyLimit")]
+  pub hourly_limit: u32,
+  #[serde(rename="dailyLimit")]
+  pub daily_limit: u32,
+  #[serde(rename="remainingHourly")]
+  pub remaining_hourly: u32,
+  #[serde(rename="remainingDaily")]
+  pub remaining_daily: u32,
+  #[serde(rename="blockedUntil",skip_serializing_if = "Option::is_none")]
+  pub blocked_until: Option<String>,
+  #[serde(rename="retryAfter")]
+  pub retry_after: i64,
+  pub requests: u64,
+  #[serde(rename="cacheHits")]
+  pub cache_hits: u64,
+  pub errors: BTreeMap<String, u64>,
+  #[serde(rename="lastError",skip_serializing_if = "Option::is_none")]
+  pub last_error: Option<GeoNamesError>,
+}
+
+#[derive(Default)]
+struct GeoNamesQuotas {
+  accounts: Vec<AccountQuota>,
+  rotation: usize,
+}
+
+/*
+* Apply a function to the quota of an account, registering it with the default limits on first use.
+* The global lock must not be held across await points.
+*/
+fn with_account_quota<T, F>(username: &str, func: F) -> T where F: FnOnce(&mut AccountQuota) -> T {
+  let mut quotas = globals::get::<GeoNamesQuotas>();
+  let index = match quotas.accounts.iter().position(|a| a.username == username) {
+    Some(index) => index,
+    None => {
+      quotas.accounts.push(AccountQuota::new(username, GEONAMES_HOURLY_LIMIT_DEFAULT, GEONAMES_DAILY_LIMIT_DEFAULT));
+      quotas.accounts.len() - 1
+    }
+  };
+  func(&mut quotas.accounts[index])
+}
+
+/*
+* Register an account with its own limits, keeping usage counters if already known
+*/
+pub fn register_geonames_account(username: &str, hourly_limit: u32, daily_limit: u32) {
+  with_account_quota(username, |quota| {
+    if quota.hourly_limit != hourly_limit {
+      quota.tokens = quota.tokens.min(hourly_limit as f64);
+      quota.hourly_limit = hourly_limit;
+    }
+    quota.daily_limit = daily_limit;
+  })
+}
+
+/*
+* Counter for round-robin account selection
+*/
+pub fn next_geonames_rotation() -> usize {
+  let mut quotas = globals::get::<GeoNamesQuotas>();
+  let index = quotas.rotation;
+  quotas.rotation = quotas.rotation.wrapping_add(1);
+  index
+}
+
+pub fn acquire_geonames_token(username: &str) -> Result<(), GeoNamesError> {
+  with_account_quota(username, |quota| quota.acquire())
+}
+
+pub fn release_geonames_token(username: &str) {
+  with_account_quota(username, |quota| quota.release())
+}
+
+pub fn record_geonames_error(username: &str, error: &GeoNamesError) {
+  with_account_quota(username, |quota| quota.record_error(error))
+}
+
+/*
+* Seconds until GeoNames can be queried again if every known account is exhausted
+*/
+pub fn geonames_retry_after() -> Option<i64> {
+  let mut quotas = globals::get::<GeoNamesQuotas>();
+  let waits: Vec<i64> = quotas.accounts.iter_mut().map(|quota| quota.retry_after()).collect();
+  if !waits.is_empty() && waits.iter().all(|w| *w > 0) {
+    waits.into_iter().min()
+  } else {
+    None
+  }
+}
+
+pub fn geonames_quota_status() -> Vec<QuotaStatus> {
+  let mut quotas = globals::get::<GeoNamesQuotas>();
+  quotas.accounts.iter_mut().map(|quota| quota.status()).collect()
+}
+
+/*
+* Run a request future while noting whether a GeoNames call failed because every account was exhausted
+*/
+pub async fn with_geonames_limit_trace<F: Future>(future: F) -> F::Output {
+  GEONAMES_LIMIT.scope(Cell::new(None), future).await
+}
+
+/*
+* Note a failed GeoNames call for the current request if no account could serve it
+*/
+pub fn record_geonames_limit(error: &GeoNamesError) {
+  if error.is_limit() {
+    let retry_after = match error {
+      GeoNamesError::RateLimited(secs) => *secs,
+      _ => geonames_retry_after().unwrap_or(1)
+    };
+    let _ = GEONAMES_LIMIT.try_with(|limit| limit.set(Some(retry_after.max(1))));
+  }
+}
+
+/*
+* Seconds until GeoNames can be queried again if a call made for the current request failed on quota limits
+*/
+pub fn geonames_limit_retry_after() -> Option<i64> {
+  GEONAMES_LIMIT.try_with(|limit| limit.get()).ok().flatten()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn status_objects_map_to_errors() {
+    assert_eq!(GeoNamesError::from_status(19, "limit"), GeoNamesError::HourlyLimitExceeded);
+    assert_eq!(GeoNamesError::from_status(10, "user does not exist"), GeoNamesError::InvalidUser("user does not exist".to_owned()));
+    assert_eq!(GeoNamesError::from_status(15, "no result found"), GeoNamesError::Status(15, "no result found".to_owned()));
+    assert_eq!(GeoNamesError::DailyLimitExceeded.code(), "18");
+    let data = serde_json::from_str::<Map<String, Value>>(r#"{"status": {"message": "the hourly limit has been exceeded", "value": 19}}"#).unwrap();
+    assert_eq!(GeoNamesError::from_response(&data), Some(GeoNamesError::HourlyLimitExceeded));
+    let data = serde_json::from_str::<Map<String, Value>>(r#"{"timezoneId": "Europe/London"}"#).unwrap();
+    assert_eq!(GeoNamesError::from_response(&data), None);
+  }
+
+  #[test]
+  fn limits_and_retryable_failures_are_distinct() {
+    assert!(GeoNamesError::WeeklyLimitExceeded.is_limit());
+    assert!(GeoNamesError::RateLimited(5).is_limit());
+    assert!(!GeoNamesError::ServerOverloaded.is_limit());
+    assert!(GeoNamesError::ServerOverloaded.is_retryable());
+    assert!(GeoNamesError::Transport("timeout".to_owned()).is_retryable());
+    assert!(!GeoNamesError::HourlyLimitExceeded.is_retryable());
+  }
+
+  #[test]
+  fn limits_reset_at_the_next_hour_day_or_monday() {
+    // Wednesday 2022-06-15 10:30:00 UTC
+    let now = 1_655_289_000;
+    assert_eq!(GeoNamesError::HourlyLimitExceeded.blocked_until(now), Some(1_655_290_800));
+    assert_eq!(GeoNamesError::DailyLimitExceeded.blocked_until(now), Some(1_655_337_600));
+    // Monday 2022-06-20 00:00:00 UTC
+    assert_eq!(GeoNamesError::WeeklyLimitExceeded.blocked_until(now), Some(1_655_683_200));
+    assert_eq!(GeoNamesError::RateLimited(30).blocked_until(now), Some(now + 30));
+    assert_eq!(GeoNamesError::DatabaseTimeout.blocked_until(now), None);
+  }
+
+  #[test]
+  fn tokens_run_out_and_cache_hits_are_refunded() {
+    let mut quota = AccountQuota::new("tester", 2, 1000);
+    assert!(quota.acquire().is_ok());
+    assert!(quota.acquire().is_ok());
+    match quota.acquire() {
+      Err(GeoNamesError::RateLimited(wait)) => assert!(wait > 0 && wait <= 1800),
+      other => panic!("expected a rate limit, got {:?}", other),
+    }
+    quota.release();
+    assert!(quota.acquire().is_ok());
+    assert_eq!(quota.requests, 3);
+    assert_eq!(quota.cache_hits, 1);
+  }
+
+  #[test]
+  fn the_daily_allowance_caps_requests() {
+    let mut quota = AccountQuota::new("tester", 100, 2);
+    assert!(quota.acquire().is_ok());
+    assert!(quota.acquire().is_ok());
+    assert!(matches!(quota.acquire(), Err(GeoNamesError::RateLimited(_))));
+    let status = quota.status();
+    assert_eq!(status.remaining_daily, 0);
+    assert!(status.retry_after > 0);
+  }
+
+  #[test]
+  fn upstream_limits_block_the_account() {
+    let mut quota = AccountQuota::new("tester", 100, 1000);
+    quota.record_error(&GeoNamesError::RateLimited(10));
+    assert_eq!(quota.blocked_until, None);
+    quota.record_error(&GeoNamesError::HourlyLimitExceeded);
+    assert!(quota.blocked_until.is_some());
+    assert!(quota.acquire().is_err());
+    assert_eq!(quota.errors.get("19"), Some(&1));
+    assert_eq!(quota.errors.get("rate_limited"), Some(&1));
+    assert_eq!(quota.last_error, Some(GeoNamesError::HourlyLimitExceeded));
+  }
+
+  #[test]
+  fn user_names_are_masked() {
+    assert_eq!(mask_username("geouser"), "ge***");
+    assert_eq!(mask_username("x"), "x***");
+    assert_eq!(AccountQuota::new("premium", 1, 1).status().username, "pr***");
+  }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use crate::data::historic_states::HISTORIC_SOVEREIGN_STATES;
+use super::alternate_names::{AlternateName, match_historic_names_for_all};
+use super::geonames::GeoNameRow;
+use super::local_geonames::source_country_code;
+
+/*
+* Name of a place in use on the given date among its dated historic entries in the alternate_names table.
+* English names are preferred where several apply.
+*/
+fn select_historic_name(historic_names: &[AlternateName], current_name: &str, date: &str) -> Option<String> {
+  let mut names = historic_names.iter().filter(|alt| alt.is_valid_at(date) && alt.name != current_name).collect::<Vec<_>>();
+  names.sort_by_key(|alt| if alt.lang == "en" { 0 } else if alt.lang.is_empty() { 1 } else { 2 });
+  names.into_iter().next().map(|alt| alt.name.clone())
+}
+
+/*
+* Sovereign state in place of the current country on the given date, checking region-specific entries first
+*/
+pub fn match_sovereign_state(cc: &str, regions: &[&str], date: &str) -> Option<(&'static str, &'static str)> {
+  let source_cc = source_country_code(cc);
+  let day = date.get(0..10).unwrap_or(date);
+  let in_period = |from: &str, to: &str| (from.is_empty() || day >= from) && (to.is_empty() || day < to);
+  let mut candidates = HISTORIC_SOVEREIGN_STATES.into_iter()
+    .filter(|(state_cc, region, _, _, from, to)| (*state_cc == cc || *state_cc == source_cc) && (region.is_empty() || regions.contains(region)) && in_period(from, to))
+    .collect::<Vec<_>>();
+  candidates.sort_by_key(|entry| entry.1.is_empty());
+  candidates.into_iter().next().map(|(_, _, name, code, _, _)| (name, code))
+}
+
+/*
+* Add the names in use on the given date to places and the sovereign state to the country row, where these differ.
+* Historic names of all places and regions are fetched in one lookup.
+*/
+pub fn apply_historic_names(rows: &mut [GeoNameRow], date: &str) {
+  let regions: Vec<String> = rows.iter().filter(|row| row.fcode.starts_with("ADM")).map(|row| row.name.clone()).collect();
+  let region_refs: Vec<&str> = regions.iter().map(|r| r.as_str()).collect();
+  let place_names: Vec<String> = rows.iter().filter(|row| !row.fcode.starts_with("PCL") && (row.fcode.starts_with('P') || row.fcode.starts_with("ADM"))).map(|row| row.name.clone()).collect();
+  let historic_names = if place_names.is_empty() { HashMap::new() } else { match_historic_names_for_all(&place_names.iter().map(|n| n.as_str()).collect::<Vec<&str>>()) };
+  for row in rows.iter_mut() {
+    if row.fcode.starts_with("PCL") {
+      if let Some(cc) = row.country_code.clone() {
+        if let Some((name, _code)) = match_sovereign_state(&cc, &region_refs, date) {
+          row.historic_name = Some(name.to_owned());
+        }
+      }
+    } else if row.fcode.starts_with('P') || row.fcode.starts_with("ADM") {
+      row.historic_name = historic_names.get(&row.name).and_then(|names| select_historic_name(names, &row.name, date));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn dated(name: &str, lang: &str, from: &str, to: &str) -> AlternateName {
+    AlternateName { valid_from: from.to_owned(), valid_to: to.to_owned(), lang: lang.to_owned(), historic: true, ..AlternateName::new_builtin(name, "Chennai") }
+  }
+
+  #[test]
+  fn year_end_dates_include_the_whole_year() {
+    let madras = dated("Madras", "en", "1639", "1996");
+    assert!(madras.is_valid_at("1996-12-31"));
+    assert!(!madras.is_valid_at("1997-01-01"));
+    assert!(madras.is_valid_at("1639-01-01"));
+    assert!(!madras.is_valid_at("1638-12-31"));
+    let madras = dated("Madras", "en", "", "1996-07-17");
+    assert!(madras.is_valid_at("1996-07-16T23:59:59"));
+    assert!(!madras.is_valid_at("1996-07-17"));
+    assert!(!dated("Madras", "en", "", "").is_valid_at("1900-01-01"));
+  }
+
+  #[test]
+  fn english_names_in_use_are_preferred() {
+    let names = [dated("Madraspatnam", "ta", "", "1996"), dated("Madras", "en", "", "1996"), dated("Fort St George", "en", "", "1700")];
+    assert_eq!(select_historic_name(&names, "Chennai", "1930-01-01").as_deref(), Some("Madras"));
+    assert_eq!(select_historic_name(&names, "Chennai", "2000-01-01"), None);
+  }
+
+  #[test]
+  fn region_entries_take_precedence_over_country_entries() {
+    assert_eq!(match_sovereign_state("RU", &["Kaliningrad"], "1930-06-01"), Some(("Germany", "DE")));
+    assert_eq!(match_sovereign_state("RU", &["Moscow"], "1930-06-01"), Some(("Soviet Union", "SU")));
+    assert_eq!(match_sovereign_state("RU", &["Kaliningrad"], "1950-06-01"), Some(("Soviet Union", "SU")));
+    assert_eq!(match_sovereign_state("FR", &["Bas-Rhin"], "1900-01-01"), Some(("German Empire", "DE")));
+    assert_eq!(match_sovereign_state("FR", &["Bas-Rhin"], "1920-01-01"), None);
+    assert_eq!(match_sovereign_state("DE", &["Saxony"], "1960-01-01"), Some(("East Germany", "DD")));
+    assert_eq!(match_sovereign_state("DE", &["Bavaria"], "1960-01-01"), Some(("West Germany", "DE")));
+  }
+}
@@ -0,0 +1,143 @@
+use std::sync::OnceLock;
+use mysql::prelude::Queryable;
+use crate::data::mysql::{connect_mysql, escape_sql, escape_like};
+use super::geonames::{GeoNameRow, correct_country_code};
+use super::geocoder::PlaceQuery;
+use super::countries::resolve_country_code;
+
+/*
+* Country code as stored in GeoNames dumps, reversing output conventions such as UK for GB or alpha-3 codes
+*/
+pub fn source_country_code(cc: &str) -> String {
+  resolve_country_code(cc).unwrap_or_else(|| cc.trim().to_uppercase())
+}
+
+/*
+* Condition matching a qualifier such as a region, region code or country after a comma in the search string
+*/
+fn build_qualifier_clause(qualifier: &str) -> String {
+  let q = escape_like(qualifier.trim());
+  format!("(a1.name LIKE '{q}%' OR a1.ascii_name LIKE '{q}%' OR g.admin1_code = '{code}' OR ci.name LIKE '{q}%' OR g.cc = '{cc}')", q = q, code = escape_sql(qualifier.trim()), cc = escape_sql(&source_country_code(qualifier)))
+}
+
+/*
+* Condition matching any name or common variant of recognised admin1 regions, e.g. Washington, D.C. for District of Columbia
+*/
+fn build_admin_regions_clause(names: &[String]) -> String {
+  let parts: Vec<String> = names.iter().map(|name| format!("a1.name LIKE '{n}%' OR a1.ascii_name LIKE '{n}%'", n = escape_like(name))).collect();
+  format!("({})", parts.join(" OR "))
+}
+
+/*
+* Whether the geonames table has an ngram FULLTEXT index on name and ascii_name for matching within names.
+* Created manually with: ALTER TABLE geonames ADD FULLTEXT name_fulltext (name, ascii_name) WITH PARSER ngram
+*/
+fn has_name_fulltext_index() -> bool {
+  static NAME_FULLTEXT: OnceLock<bool> = OnceLock::new();
+  if let Some(exists) = NAME_FULLTEXT.get() {
+    return *exists;
+  }
+  let sql = "SELECT COUNT(*) FROM information_schema.STATISTICS WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = 'geonames' AND INDEX_NAME = 'name_fulltext' AND INDEX_TYPE = 'FULLTEXT'";
+  match connect_mysql().and_then(|mut conn| conn.query_first::<u32, &str>(sql)) {
+    Ok(count) => *NAME_FULLTEXT.get_or_init(|| count.unwrap_or(0) > 0),
+    Err(_) => false
+  }
+}
+
+/*
+* Derived table of geoname ids whose name, ASCII name or any alternate name starts with the search name.
+* Each branch is a prefix lookup on its own index, which an OR across tables would not use.
+* GeoNames treats fuzzy 1 as exact and lower values also match within names, which is only done with an ngram FULLTEXT index.
+*/
+fn build_name_matches(name: &str, fuzzy: Option<f32>, infix_index: bool) -> String {
+  let p = escape_like(name.trim());
+  let mut branches = vec![
+    format!("SELECT geoname_id FROM geonames WHERE name LIKE '{}%'", p),
+    format!("SELECT geoname_id FROM geonames WHERE ascii_name LIKE '{}%'", p),
+    format!("SELECT geoname_id FROM alternate_names WHERE alternate_name LIKE '{}%'", p),
+  ];
+  if infix_index && fuzzy.unwrap_or(1f32) < 0.9 {
+    let phrase = escape_sql(&name.trim().replace('"', ""));
+    branches.push(format!("SELECT geoname_id FROM geonames WHERE MATCH(name, ascii_name) AGAINST ('\"{}\"' IN BOOLEAN MODE)", phrase));
+  }
+  format!("({})", branches.join(" UNION "))
+}
+
+/*
+* Place name search over the imported GeoNames tables with the same options as the GeoNames search service.
+* Text after commas, e.g. "Springfield, Illinois" or "Perth, AU", is matched against admin1 names and codes or countries.
+*/
+pub fn search_local_geonames(query: &PlaceQuery) -> Vec<GeoNameRow> {
+  let mut parts = query.search.split(',').map(|p| p.trim()).filter(|p| !p.is_empty());
+  let name = match parts.next() {
+    Some(name) => name.to_owned(),
+    None => return vec![]
+  };
+  let name_matches = build_name_matches(&name, query.fuzzy, has_name_fulltext_index());
+  let mut conditions: Vec<String> = parts.map(build_qualifier_clause).collect();
+  match (&query.admin_regions, &query.region) {
+    (Some(filter), _) => conditions.push(build_admin_regions_clause(&filter.region_names())),
+    (None, Some(region)) => conditions.push(build_qualifier_clause(region)),
+    _ => ()
+  }
+  if let Some(cc) = &query.cc {
+    conditions.push(format!("g.cc = '{}'", escape_sql(&source_country_code(cc))));
+  }
+  if !query.all_classes {
+    conditions.push("g.feature_class IN ('P', 'A')".to_owned());
+  }
+  let where_sql = if conditions.is_empty() { String::new() } else { format!("WHERE {}", conditions.join(" AND ")) };
+  let sql = format!("SELECT g.name, g.ascii_name, g.lat, g.lng, g.feature_code, g.cc, COALESCE(a1.name, ''), g.population
+    FROM {} AS m
+    INNER JOIN geonames AS g ON g.geoname_id = m.geoname_id
+    LEFT JOIN admin1_codes AS a1 ON a1.code = CONCAT(g.cc, '.', g.admin1_code)
+    LEFT JOIN country_info AS ci ON ci.cc = g.cc
+    {}
+    ORDER BY g.population DESC LIMIT {}", name_matches, where_sql, query.max_rows.max(1));
+  let mut rows = fetch_local_geoname_rows(sql);
+  rows.sort_by_key(|row| std::cmp::Reverse(row.weighted_pop()));
+  rows
+}
+
+pub fn fetch_local_geoname_rows(sql: String) -> Vec<GeoNameRow> {
+  if let Ok(mut conn) = connect_mysql() {
+    conn.query_map(sql, |(name, ascii_name, lat, lng, fcode, cc, admin_name, population): (String, String, f64, f64, String, String, String, i64)| {
+      let mut row = GeoNameRow::new_from_params(lat, lng, name, fcode, population.clamp(0, u32::MAX as i64) as u32);
+      row.toponym = ascii_name;
+      if !cc.is_empty() {
+        row.country_code = Some(correct_country_code(&cc));
+      }
+      if !admin_name.is_empty() {
+        row.admin_name = Some(admin_name);
+      }
+      row
+    }).unwrap_or_default()
+  } else {
+    vec![]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn like_wildcards_in_names_match_literally() {
+    assert_eq!(escape_like("50%_off"), "50\\\\%\\\\_off");
+    assert_eq!(escape_like("Val d'Isère"), "Val d''Isère");
+    assert!(build_qualifier_clause("a_b").contains("a1.name LIKE 'a\\\\_b%'"));
+    assert!(build_qualifier_clause("a_b").contains("g.admin1_code = 'a_b'"));
+  }
+
+  #[test]
+  fn names_are_matched_by_a_union_of_prefix_lookups() {
+    let sql = build_name_matches(" York ", Some(0.5), false);
+    assert_eq!(sql.matches(" UNION ").count(), 2);
+    assert!(sql.contains("WHERE name LIKE 'York%'"));
+    assert!(sql.contains("WHERE alternate_name LIKE 'York%'"));
+    assert!(!sql.contains("'%York") && !sql.contains(" OR "));
+    let sql = build_name_matches("York", Some(0.5), true);
+    assert!(sql.contains("MATCH(name, ascii_name) AGAINST ('\"York\"' IN BOOLEAN MODE)"));
+    assert!(!build_name_matches("York", Some(1.0), true).contains("MATCH"));
+  }
+}
@@ -0,0 +1,381 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+use diacritics::remove_diacritics;
+use super::geonames::{Locality, fetch_locality_rows};
+use super::countries::output_country_code;
+use super::admin_regions::RegionFilter;
+
+/*
+* How a search string matched a locality, in order of preference
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum MatchKind {
+  Typo = 1,
+  Infix = 2,
+  Prefix = 4,
+  Exact = 5,
+}
+
+struct IndexEntry {
+  locality: Locality,
+  cc: String,
+  // normalised name and ASCII name
+  keys: Vec<String>,
+}
+
+impl IndexEntry {
+  fn new(locality: Locality) -> IndexEntry {
+    let mut keys: Vec<String> = vec![];
+    for name in locality.search_names() {
+      let key = normalize_search_text(name);
+      if !key.is_empty() && !keys.contains(&key) {
+        keys.push(key);
+      }
+    }
+    IndexEntry { cc: locality.cc_code(), locality, keys }
+  }
+}
+
+/*
+* In-memory index over the cities table, built on first use.
+* Words are kept sorted for prefix lookups and trigrams map to entries for infix and typo-tolerant matches.
+*/
+#[derive(Default)]
+struct LocalityIndex {
+  entries: Vec<IndexEntry>,
+  words: Vec<(String, u32)>,
+  trigrams: HashMap<String, Vec<u32>>,
+}
+
+// published once built, so searches only clone the handle under a read lock
+static LOCALITY_INDEX: RwLock<Option<Arc<LocalityIndex>>> = RwLock::new(None);
+// held while reading the cities table, so concurrent first searches wait for a single build
+static LOCALITY_INDEX_BUILD: Mutex<()> = Mutex::new(());
+
+/*
+* Lower case without diacritics, with punctuation such as hyphens and apostrophes treated as word breaks
+*/
+pub fn normalize_search_text(text: &str) -> String {
+  remove_diacritics(text).to_lowercase().chars()
+    .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+    .collect::<String>()
+    .split_whitespace()
+    .collect::<Vec<&str>>()
+    .join(" ")
+}
+
+fn to_trigrams(text: &str) -> Vec<String> {
+  let chars: Vec<char> = text.chars().collect();
+  if chars.len() < 3 {
+    return vec![];
+  }
+  chars.windows(3).map(|w| w.iter().collect::<String>()).collect()
+}
+
+/*
+* Edit distance counting swapped adjacent letters as one edit, giving up once it exceeds the limit
+*/
+pub fn edit_distance_within(a: &[char], b: &[char], limit: usize) -> Option<usize> {
+  if a.len().abs_diff(b.len()) > limit {
+    return None;
+  }
+  let mut before_prev: Vec<usize> = vec![];
+  let mut prev: Vec<usize> = (0..=b.len()).collect();
+  for (i, ca) in a.iter().enumerate() {
+    let mut curr = vec![i + 1; b.len() + 1];
+    for (j, cb) in b.iter().enumerate() {
+      let cost = if ca == cb { 0 } else { 1 };
+      curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+      if i > 0 && j > 0 && *ca == b[j - 1] && a[i - 1] == *cb {
+        curr[j + 1] = curr[j + 1].min(before_prev[j - 1] + 1);
+      }
+    }
+    if curr.iter().min().copied().unwrap_or(0) > limit {
+      return None;
+    }
+    before_prev = prev;
+    prev = curr;
+  }
+  prev.last().copied().filter(|d| *d <= limit)
+}
+
+/*
+* Typos tolerated for a search string of this length: none for short strings, then 1 and 2
+*/
+pub fn max_typos(num_chars: usize) -> usize {
+  if num_chars < 4 { 0 } else if num_chars < 8 { 1 } else { 2 }
+}
+
+/*
+* Match a search string against a key: the whole key, a word prefix, anywhere within it or within a few typos
+* of the start of a word or of the whole key
+*/
+fn match_key(key: &str, query: &str, infix: bool, typos: usize) -> Option<MatchKind> {
+  if key == query {
+    Some(MatchKind::Exact)
+  } else if key.starts_with(query) || key.contains(&format!(" {}", query)) {
+    Some(MatchKind::Prefix)
+  } else if infix && key.contains(query) {
+    Some(MatchKind::Infix)
+  } else if typos > 0 {
+    let q_chars: Vec<char> = query.chars().collect();
+    let key_chars: Vec<char> = key.chars().collect();
+    let word_starts = key_chars.iter().enumerate().filter(|(i, c)| *i == 0 || (**c != ' ' && key_chars[i - 1] == ' ')).map(|(i, _)| i);
+    for start in word_starts {
+      let rest = &key_chars[start..];
+      // compare with the same number of characters and one either side to allow for insertions and deletions
+      for len in q_chars.len().saturating_sub(typos)..=(q_chars.len() + typos).min(rest.len()) {
+        if len > 0 && edit_distance_within(&q_chars, &rest[..len], typos).is_some() {
+          return Some(MatchKind::Typo);
+        }
+      }
+    }
+    None
+  } else {
+    None
+  }
+}
+
+impl LocalityIndex {
+  fn load(&mut self, entries: Vec<IndexEntry>) {
+    self.entries = entries;
+    let mut words: Vec<(String, u32)> = vec![];
+    let mut trigrams: HashMap<String, Vec<u32>> = HashMap::new();
+    for (id, entry) in self.entries.iter().enumerate() {
+      let id = id as u32;
+      let mut entry_trigrams: HashSet<String> = HashSet::new();
+      for key in entry.keys.iter() {
+        for word in key.split(' ') {
+          words.push((word.to_owned(), id));
+        }
+        entry_trigrams.extend(to_trigrams(key));
+      }
+      for tri in entry_trigrams {
+        trigrams.entry(tri).or_default().push(id);
+      }
+    }
+    words.sort();
+    words.dedup();
+    self.words = words;
+    self.trigrams = trigrams;
+  }
+
+  /*
+  * Entries with a word starting with the query, via binary search over the sorted words
+  */
+  fn prefix_candidates(&self, query: &str) -> HashSet<u32> {
+    let first_word = query.split(' ').next().unwrap_or("");
+    let start = self.words.partition_point(|(word, _)| word.as_str() < first_word);
+    self.words[start..].iter().take_while(|(word, _)| word.starts_with(first_word)).map(|(_, id)| *id).collect()
+  }
+
+  /*
+  * Entries sharing enough trigrams with the query to match within it or within the allowed number of typos.
+  * Each typo can affect up to three trigrams.
+  */
+  fn trigram_candidates(&self, query: &str, typos: usize) -> HashSet<u32> {
+    let q_trigrams: HashSet<String> = to_trigrams(query).into_iter().collect();
+    let min_shared = q_trigrams.len().saturating_sub(typos * 3).max(1);
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for tri in q_trigrams.iter() {
+      if let Some(ids) = self.trigrams.get(tri) {
+        for id in ids {
+          *counts.entry(*id).or_default() += 1;
+        }
+      }
+    }
+    counts.into_iter().filter(|(_, count)| *count >= min_shared).map(|(id, _)| id).collect()
+  }
+
+  /*
+  * fuzzy on the same 0-100 scale as the lookup endpoints: 0 for word prefixes only, above 0 also within names
+  * and from 50 with tolerance of one typo in 4 to 7 letters or two in longer search strings
+  */
+  fn search(&self, text: &str, cc: &Option<String>, region: Option<&RegionFilter>, max: u8, fuzzy: u8) -> Vec<Locality> {
+    let query = normalize_search_text(text);
+    let num_chars = query.chars().count();
+    if num_chars < 1 {
+      return vec![];
+    }
+    let infix = fuzzy > 0 && num_chars > 2;
+    let typos = if fuzzy >= 50 { max_typos(num_chars) } else { 0 };
+    let mut candidates = self.prefix_candidates(&query);
+    if infix || typos > 0 {
+      candidates.extend(self.trigram_candidates(&query, typos));
+    }
+    // entries hold country codes in the output convention
+    let cc_ref = cc.as_deref().map(output_country_code).filter(|c| c != "ALL");
+    let mut matches: Vec<(u64, &Locality)> = candidates.into_iter().filter_map(|id| {
+      let entry = self.entries.get(id as usize)?;
+      if let Some(cc_str) = &cc_ref {
+        if &entry.cc != cc_str {
+          return None;
+        }
+      }
+      if !region.map(|filter| entry.locality.in_region(filter)).unwrap_or(true) {
+        return None;
+      }
+      let kind = entry.keys.iter().filter_map(|key| match_key(key, &query, infix, typos)).max()?;
+      // weight is zero for typo matches, so population alone ranks them
+      let weight = match entry.locality.weight(&query) {
+        0 => entry.locality.base_weight(),
+        w => w
+      } as u64;
+      Some((weight * kind as u64, &entry.locality))
+    }).collect();
+    matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    matches.into_iter().take(max as usize).map(|(_, locality)| locality.clone()).collect()
+  }
+}
+
+fn fetch_index_rows() -> Vec<IndexEntry> {
+  fetch_locality_rows("SELECT name, ascii_name, admin_name, lat, lng, cc, population, zone_name FROM cities".to_owned())
+    .into_iter().map(IndexEntry::new).collect()
+}
+
+fn published_index() -> Option<Arc<LocalityIndex>> {
+  LOCALITY_INDEX.read().ok().and_then(|index| index.clone())
+}
+
+/*
+* The published index, else one built from the cities table outside the index lock and then published.
+* Nothing is published if the table could not be read, so the next search tries again.
+*/
+fn locality_index() -> Option<Arc<LocalityIndex>> {
+  if let Some(index) = published_index() {
+    return Some(index);
+  }
+  let _build = LOCALITY_INDEX_BUILD.lock().unwrap_or_else(|e| e.into_inner());
+  if let Some(index) = published_index() {
+    return Some(index);
+  }
+  let mut index = LocalityIndex::default();
+  index.load(fetch_index_rows());
+  if index.entries.is_empty() {
+    return None;
+  }
+  let index = Arc::new(index);
+  if let Ok(mut published) = LOCALITY_INDEX.write() {
+    *published = Some(index.clone());
+  }
+  Some(index)
+}
+
+/*
+* Search the cities table via the in-memory index, building it on first use
+*/
+pub fn search_locality_index(text: &str, cc: &Option<String>, region: Option<&RegionFilter>, max: u8, fuzzy: u8) -> Vec<Locality> {
+  match locality_index() {
+    Some(index) => index.search(text, cc, region, max, fuzzy),
+    None => vec![]
+  }
+}
+
+/*
+* Build the index ahead of the first search
+*/
+pub fn warm_locality_index() -> usize {
+  locality_index().map(|index| index.entries.len()).unwrap_or(0)
+}
+
+/*
+* Drop the index so it is rebuilt from the cities table on the next search
+*/
+pub fn clear_locality_index() -> usize {
+  let index = LOCALITY_INDEX.write().ok().and_then(|mut published| published.take());
+  index.map(|index| index.entries.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn chars(text: &str) -> Vec<char> {
+    text.chars().collect()
+  }
+
+  fn locality(name: &str, admin_name: &str, cc: &str, population: u32) -> Locality {
+    let ascii_name = remove_diacritics(name);
+    Locality::new(name.to_owned(), ascii_name, admin_name.to_owned(), 0f64, 0f64, cc.to_owned(), population, "America/Chicago".to_owned())
+  }
+
+  fn build_index(localities: Vec<Locality>) -> LocalityIndex {
+    let mut index = LocalityIndex::default();
+    index.load(localities.into_iter().map(IndexEntry::new).collect());
+    index
+  }
+
+  fn springfield_index() -> LocalityIndex {
+    build_index(vec![
+      locality("Springfield", "Missouri", "US", 160000),
+      locality("West Springfield", "Massachusetts", "US", 28000),
+      locality("Spring", "Texas", "US", 62000),
+      locality("Frankfurt am Main", "Hesse", "DE", 750000),
+    ])
+  }
+
+  fn search_texts(index: &LocalityIndex, text: &str, fuzzy: u8) -> Vec<String> {
+    index.search(text, &None, None, 10, fuzzy).iter().map(|l| l.text()).collect()
+  }
+
+  #[test]
+  fn swapped_letters_count_as_one_edit() {
+    assert_eq!(edit_distance_within(&chars("paris"), &chars("pairs"), 1), Some(1));
+    assert_eq!(edit_distance_within(&chars("paris"), &chars("piras"), 1), None);
+    assert_eq!(edit_distance_within(&chars("kitten"), &chars("sitting"), 3), Some(3));
+    assert_eq!(edit_distance_within(&chars("kitten"), &chars("sitting"), 2), None);
+    assert_eq!(edit_distance_within(&chars("lyon"), &chars("lyonnais"), 2), None);
+    assert_eq!(edit_distance_within(&chars("oslo"), &chars("oslo"), 0), Some(0));
+  }
+
+  #[test]
+  fn typos_allowed_by_search_length() {
+    assert_eq!([1, 3, 4, 7, 8, 20].map(max_typos), [0, 0, 1, 1, 2, 2]);
+  }
+
+  #[test]
+  fn match_kinds_by_position_and_typos() {
+    assert_eq!(match_key("springfield", "springfield", true, 2), Some(MatchKind::Exact));
+    assert_eq!(match_key("west springfield", "spring", false, 0), Some(MatchKind::Prefix));
+    assert_eq!(match_key("springfield", "field", true, 0), Some(MatchKind::Infix));
+    assert_eq!(match_key("springfield", "field", false, 0), None);
+    assert_eq!(match_key("frankfurt am main", "frnakfurt", false, 2), Some(MatchKind::Typo));
+    assert_eq!(match_key("frankfurt am main", "frnakfurt", false, 0), None);
+    // typos are only matched from the start of a word
+    assert_eq!(match_key("springfield", "fielt", false, 1), None);
+    assert!(MatchKind::Exact > MatchKind::Prefix && MatchKind::Prefix > MatchKind::Infix && MatchKind::Infix > MatchKind::Typo);
+  }
+
+  #[test]
+  fn trigram_candidates_allow_for_typos() {
+    let index = springfield_index();
+    let ids = |query: &str, typos: usize| -> Vec<String> {
+      let mut texts: Vec<String> = index.trigram_candidates(query, typos).into_iter().map(|id| index.entries[id as usize].locality.text()).collect();
+      texts.sort();
+      texts
+    };
+    assert_eq!(ids("field", 0), vec!["Springfield, Missouri (US)", "West Springfield, Massachusetts (US)"]);
+    assert!(ids("sprignfield", 0).is_empty());
+    assert_eq!(ids("sprignfield", 2), vec!["Springfield, Missouri (US)", "West Springfield, Massachusetts (US)"]);
+  }
+
+  #[test]
+  fn exact_and_prefix_matches_rank_above_infix_matches() {
+    let index = springfield_index();
+    assert_eq!(search_texts(&index, "spring", 0), vec!["Spring, Texas (US)", "Springfield, Missouri (US)", "West Springfield, Massachusetts (US)"]);
+    assert!(search_texts(&index, "field", 0).is_empty());
+    let equal = build_index(vec![
+      locality("Springfield", "Illinois", "US", 100000),
+      locality("Fieldbrook", "California", "US", 100000),
+    ]);
+    assert_eq!(search_texts(&equal, "field", 10), vec!["Fieldbrook, California (US)", "Springfield, Illinois (US)"]);
+  }
+
+  #[test]
+  fn typo_matches_need_a_fuzzy_level_of_50() {
+    let index = springfield_index();
+    assert!(search_texts(&index, "sprignfield", 10).is_empty());
+    assert_eq!(search_texts(&index, "sprignfield", 50), vec!["Springfield, Missouri (US)", "West Springfield, Massachusetts (US)"]);
+    assert_eq!(search_texts(&index, "Frankfrut", 50), vec!["Frankfurt am Main, Hesse (DE)"]);
+  }
+}
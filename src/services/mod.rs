@@ -1,2 +1,16 @@
 pub mod timezonedb;
-pub mod geonames;
\ No newline at end of file
+pub mod geonames;
+pub mod geonames_client;
+pub mod geonames_quota;
+pub mod zone_memo;
+pub mod geocoder;
+pub mod local_geonames;
+pub mod reverse_geocoder;
+pub mod locality_index;
+pub mod alternate_names;
+pub mod historic_names;
+pub mod zone_overrides;
+pub mod countries;
+pub mod zone_catalogue;
+pub mod zone_abbreviations;
+pub mod admin_regions;
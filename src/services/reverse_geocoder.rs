@@ -0,0 +1,355 @@
+use std::fs;
+use mysql::prelude::Queryable;
+use serde_json::Value;
+use crate::constants::*;
+use crate::app::coords::{Coords, ring_contains_coords};
+use crate::data::mysql::{connect_mysql, escape_sql};
+use super::geonames::{GeoNameRow, Locality, correct_country_code};
+use super::local_geonames::source_country_code;
+
+/*
+* Row from the cities table with its distance in km and initial bearing in degrees from a reference point
+*/
+#[derive(Debug, Clone)]
+pub struct NearbyCity {
+  pub name: String,
+  pub ascii_name: String,
+  pub admin_name: String,
+  pub lat: f64,
+  pub lng: f64,
+  // country code as stored, before output corrections such as GB => UK
+  pub cc: String,
+  pub population: u32,
+  pub zone_name: String,
+  pub distance: f64,
+  pub bearing: f64,
+}
+
+impl NearbyCity {
+  pub fn to_locality(&self) -> Locality {
+    Locality::new(self.name.clone(), self.ascii_name.clone(), self.admin_name.clone(), self.lat, self.lng, self.cc.clone(), self.population, self.zone_name.clone())
+      .with_distance(round_to(self.distance, 3), round_to(self.bearing, 1))
+  }
+}
+
+fn round_to(value: f64, places: i32) -> f64 {
+  let factor = 10f64.powi(places);
+  (value * factor).round() / factor
+}
+
+/*
+* Longitude condition for a bounding box that may cross the antimeridian
+*/
+fn build_lng_clause(min_lng: f64, max_lng: f64) -> String {
+  if max_lng - min_lng >= 360f64 {
+    "1".to_owned()
+  } else if min_lng < -180f64 {
+    format!("(lng >= {} OR lng <= {})", min_lng + 360f64, max_lng)
+  } else if max_lng > 180f64 {
+    format!("(lng >= {} OR lng <= {})", min_lng, max_lng - 360f64)
+  } else {
+    format!("lng BETWEEN {} AND {}", min_lng, max_lng)
+  }
+}
+
+/*
+* Country codes in any accepted form, e.g. GBR, 826 or UK, are matched as stored in the cities table
+*/
+fn build_cities_near_sql(coords: &Coords, radius_km: f64, min_pop: u32, cc: Option<&str>) -> String {
+  let ((min_lat, max_lat), (min_lng, max_lng)) = coords.bounding_box(radius_km);
+  let mut conditions = vec![format!("lat BETWEEN {} AND {}", min_lat, max_lat), build_lng_clause(min_lng, max_lng)];
+  if min_pop > 0 {
+    conditions.push(format!("population >= {}", min_pop));
+  }
+  if let Some(cc_str) = cc.map(|c| c.trim()).filter(|c| !c.is_empty()) {
+    conditions.push(format!("cc = '{}'", escape_sql(&source_country_code(cc_str))));
+  }
+  format!("SELECT name, ascii_name, admin_name, lat, lng, cc, population, zone_name FROM cities WHERE {}", conditions.join(" AND "))
+}
+
+/*
+* Cities within a radius of the given point, nearest first, prefiltered by bounding box and then by haversine distance.
+* Optional minimum population and country code filters.
+*/
+pub fn fetch_cities_near(coords: &Coords, radius_km: f64, min_pop: u32, cc: Option<&str>, limit: usize) -> Vec<NearbyCity> {
+  let sql = build_cities_near_sql(coords, radius_km, min_pop, cc);
+  let mut rows: Vec<NearbyCity> = if let Ok(mut conn) = connect_mysql() {
+    conn.query_map(sql, |(name, ascii_name, admin_name, lat, lng, cc, population, zone_name): (String, String, String, f64, f64, String, u32, String)| {
+      let target = Coords::new(lat, lng);
+      NearbyCity {
+        name,
+        ascii_name,
+        admin_name,
+        lat,
+        lng,
+        cc,
+        population,
+        zone_name,
+        distance: coords.distance_km(&target),
+        bearing: coords.bearing_to(&target),
+      }
+    }).unwrap_or_default()
+  } else {
+    vec![]
+  };
+  rows.retain(|row| row.distance <= radius_km);
+  rows.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+  rows.truncate(limit);
+  rows
+}
+
+/*
+* Localities within a radius for the nearby endpoint, nearest first
+*/
+pub fn match_nearby_localities(coords: &Coords, radius_km: f64, min_pop: u32, cc: Option<&str>, max: u8) -> Vec<Locality> {
+  let radius = if radius_km > 0f64 { radius_km.min(NEARBY_RADIUS_MAX_KM) } else { NEARBY_RADIUS_DEFAULT_KM };
+  fetch_cities_near(coords, radius, min_pop, cc, max as usize).iter().map(|city| city.to_locality()).collect()
+}
+
+/*
+* Nearest city searching progressively wider radii, so sparse regions do not scan the whole table
+*/
+pub fn fetch_nearest_city(coords: &Coords) -> Option<NearbyCity> {
+  REVERSE_GEOCODE_RADII_KM.into_iter().find_map(|radius| fetch_cities_near(coords, radius, 0, None, 1).into_iter().next())
+}
+
+fn fetch_single_string(sql: String) -> Option<String> {
+  if let Ok(mut conn) = connect_mysql() {
+    conn.query_first::<String, String>(sql).ok().flatten().filter(|s| !s.is_empty())
+  } else {
+    None
+  }
+}
+
+/*
+* Country name from the imported country_info table, if available
+*/
+fn fetch_country_name(cc: &str) -> Option<String> {
+  fetch_single_string(format!("SELECT name FROM country_info WHERE cc = '{}'", escape_sql(cc)))
+}
+
+/*
+* Second-level division of the nearest imported GeoNames place that has one, if the GeoNames tables are available
+*/
+fn fetch_admin2_name(coords: &Coords, cc: &str) -> Option<String> {
+  let ((min_lat, max_lat), (min_lng, max_lng)) = coords.bounding_box(REVERSE_GEOCODE_ADMIN2_RADIUS_KM);
+  let lng_scale = coords.lat.to_radians().cos();
+  let sql = format!("SELECT a2.name FROM geonames AS g
+    INNER JOIN admin2_codes AS a2 ON a2.code = CONCAT(g.cc, '.', g.admin1_code, '.', g.admin2_code)
+    WHERE g.cc = '{cc}' AND g.admin2_code != '' AND g.lat BETWEEN {min_lat} AND {max_lat} AND g.lng BETWEEN {min_lng} AND {max_lng}
+    ORDER BY POW(g.lat - {lat}, 2) + POW((g.lng - {lng}) * {scale}, 2) LIMIT 1",
+    cc = escape_sql(cc), min_lat = min_lat, max_lat = max_lat, min_lng = min_lng, max_lng = max_lng, lat = coords.lat, lng = coords.lng, scale = lng_scale);
+  fetch_single_string(sql)
+}
+
+/*
+* Ray-casting test for a single GeoJSON linear ring of [lng, lat] positions
+*/
+fn ring_contains(ring: &[Value], coords: &Coords) -> bool {
+  let points: Vec<(f64, f64)> = ring.iter().filter_map(|pos| match pos {
+    Value::Array(pair) if pair.len() > 1 => Some((pair[0].as_f64()?, pair[1].as_f64()?)),
+    _ => None
+  }).collect();
+  ring_contains_coords(&points, coords)
+}
+
+/*
+* A polygon contains the point if its outer ring does and none of its holes do
+*/
+fn polygon_contains(rings: &Value, coords: &Coords) -> bool {
+  if let Value::Array(rings) = rings {
+    if let Some(Value::Array(outer)) = rings.first() {
+      return ring_contains(outer, coords) && !rings.iter().skip(1).any(|hole| match hole {
+        Value::Array(hole) => ring_contains(hole, coords),
+        _ => false
+      });
+    }
+  }
+  false
+}
+
+fn geometry_contains(geometry: &Value, coords: &Coords) -> bool {
+  match (geometry.get("type").and_then(|t| t.as_str()), geometry.get("coordinates")) {
+    (Some("Polygon"), Some(rings)) => polygon_contains(rings, coords),
+    (Some("MultiPolygon"), Some(Value::Array(polygons))) => polygons.iter().any(|rings| polygon_contains(rings, coords)),
+    _ => false
+  }
+}
+
+/*
+* Admin boundary polygons loaded once from the GeoJSON file in admin_boundaries
+*/
+#[derive(Default)]
+struct AdminBoundaries {
+  path: String,
+  features: Vec<Value>,
+}
+
+/*
+* Names of the level 1 and level 2 boundaries containing the point.
+* Features need a name property and an admin_level of 1 or 2.
+*/
+fn match_admin_boundaries(coords: &Coords) -> (Option<String>, Option<String>) {
+  let path = dotenv::var("admin_boundaries").unwrap_or_default();
+  if path.is_empty() {
+    return (None, None);
+  }
+  let mut boundaries = globals::get::<AdminBoundaries>();
+  if boundaries.path != path {
+    boundaries.features = fs::read_to_string(&path).ok()
+      .and_then(|contents| serde_json::from_str::<Value>(&contents).ok())
+      .and_then(|data| data.get("features").and_then(|f| f.as_array()).cloned())
+      .unwrap_or_default();
+    boundaries.path = path;
+  }
+  let mut admin1: Option<String> = None;
+  let mut admin2: Option<String> = None;
+  for feature in boundaries.features.iter() {
+    let props = match feature.get("properties") {
+      Some(props) => props,
+      None => continue
+    };
+    let level = props.get("admin_level").and_then(|l| l.as_u64().or_else(|| l.as_str().and_then(|s| s.parse::<u64>().ok())));
+    let name = props.get("name").and_then(|n| n.as_str()).map(|n| n.to_owned());
+    let slot = match level {
+      Some(1) => &mut admin1,
+      Some(2) => &mut admin2,
+      _ => continue
+    };
+    if slot.is_none() && feature.get("geometry").map(|g| geometry_contains(g, coords)).unwrap_or(false) {
+      *slot = name;
+    }
+  }
+  (admin1, admin2)
+}
+
+/*
+* Offline equivalent of GeoNames extendedFindNearby from the cities table.
+* Returns country, first and second-level divisions and the nearest populated place in that order,
+* with the place's distance and bearing from the query point.
+*/
+pub fn reverse_geocode_local(lat: f64, lng: f64) -> Vec<GeoNameRow> {
+  let coords = Coords::new(lat, lng);
+  let city = match fetch_nearest_city(&coords) {
+    Some(city) => city,
+    None => return vec![]
+  };
+  let cc = correct_country_code(&city.cc);
+  let (boundary_admin1, boundary_admin2) = match_admin_boundaries(&coords);
+  let admin1 = boundary_admin1.or(Some(city.admin_name.clone()).filter(|a| !a.is_empty() && *a != city.cc));
+  let admin2 = boundary_admin2.or_else(|| fetch_admin2_name(&coords, &city.cc));
+  let country_name = fetch_country_name(&city.cc).unwrap_or(cc.clone());
+
+  let mut rows: Vec<GeoNameRow> = vec![];
+  let mut push_row = |name: String, fcode: &str, pop: u32| {
+    let mut row = GeoNameRow::new_from_params(city.lat, city.lng, name, fcode.to_owned(), pop);
+    row.country_code = Some(cc.clone());
+    rows.push(row);
+  };
+  push_row(country_name, "PCLI", 0);
+  if let Some(name) = admin1.clone() {
+    push_row(name, "ADM1", 0);
+  }
+  if let Some(name) = admin2 {
+    push_row(name, "ADM2", 0);
+  }
+  push_row(city.name.clone(), "PPL", city.population);
+  if let Some(place) = rows.last_mut() {
+    place.toponym = city.ascii_name.clone();
+    place.admin_name = admin1;
+    place.distance = Some(round_to(city.distance, 3));
+    place.bearing = Some(round_to(city.bearing, 1));
+  }
+  rows
+}
+
+/*
+* Use the offline reverse geocoder for placenames when reverse_geocoder is local or offline
+*/
+pub fn use_local_reverse_geocoder() -> bool {
+  matches!(dotenv::var("reverse_geocoder").unwrap_or_default().to_lowercase().as_str(), "local" | "offline")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn longitude_ranges_wrap_around_the_antimeridian() {
+    assert_eq!(build_lng_clause(-1.5, 1.5), "lng BETWEEN -1.5 AND 1.5");
+    assert_eq!(build_lng_clause(179.0, 181.0), "(lng >= 179 OR lng <= -179)");
+    assert_eq!(build_lng_clause(-181.0, -179.0), "(lng >= 179 OR lng <= -179)");
+    assert_eq!(build_lng_clause(-180.0, 180.0), "1");
+  }
+
+  #[test]
+  fn country_codes_are_normalised_before_filtering_cities() {
+    let coords = Coords::new(51.5074, -0.1278);
+    for cc in ["GB", "gb", "UK", "GBR", "826", "United Kingdom"] {
+      assert!(build_cities_near_sql(&coords, 50.0, 0, Some(cc)).ends_with("AND cc = 'GB'"), "{}", cc);
+    }
+    assert!(build_cities_near_sql(&coords, 50.0, 1000, Some("DEU")).ends_with("AND population >= 1000 AND cc = 'DE'"));
+    assert!(!build_cities_near_sql(&coords, 50.0, 0, Some(" ")).contains("cc ="));
+    assert!(!build_cities_near_sql(&coords, 50.0, 0, None).contains("cc ="));
+  }
+
+  #[test]
+  fn nearby_cities_are_rounded_for_output() {
+    assert_eq!(round_to(343.53678, 3), 343.537);
+    assert_eq!(round_to(148.06, 1), 148.1);
+  }
+
+  #[test]
+  fn polygons_exclude_their_holes() {
+    let square = serde_json::json!({
+      "type": "Polygon",
+      "coordinates": [
+        [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0], [0.0, 0.0]],
+        [[1.0, 1.0], [3.0, 1.0], [3.0, 3.0], [1.0, 3.0], [1.0, 1.0]]
+      ]
+    });
+    assert!(geometry_contains(&square, &Coords::new(0.5, 0.5)));
+    assert!(!geometry_contains(&square, &Coords::new(2.0, 2.0)));
+    assert!(!geometry_contains(&square, &Coords::new(5.0, 2.0)));
+  }
+
+  #[test]
+  fn multipolygons_contain_points_in_any_part() {
+    let islands = serde_json::json!({
+      "type": "MultiPolygon",
+      "coordinates": [
+        [[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [0.0, 0.0]]],
+        [[[10.0, 10.0], [11.0, 10.0], [11.0, 11.0], [10.0, 11.0], [10.0, 10.0]]]
+      ]
+    });
+    assert!(geometry_contains(&islands, &Coords::new(10.5, 10.5)));
+    assert!(!geometry_contains(&islands, &Coords::new(5.0, 5.0)));
+    let point = serde_json::json!({"type": "Point", "coordinates": [0.5, 0.5]});
+    assert!(!geometry_contains(&point, &Coords::new(0.5, 0.5)));
+  }
+
+  #[test]
+  fn nearby_cities_become_localities_with_distance_and_bearing() {
+    let london = Coords::new(51.5074, -0.1278);
+    let target = Coords::new(51.7520, -1.2577);
+    let city = NearbyCity {
+      name: "Oxford".to_owned(),
+      ascii_name: "Oxford".to_owned(),
+      admin_name: "England".to_owned(),
+      lat: target.lat,
+      lng: target.lng,
+      cc: "GB".to_owned(),
+      population: 171380,
+      zone_name: "Europe/London".to_owned(),
+      distance: london.distance_km(&target),
+      bearing: london.bearing_to(&target),
+    };
+    let json = serde_json::to_value(city.to_locality()).unwrap();
+    assert_eq!(json["cc"], crate::services::countries::output_country_code("GB"));
+    assert_eq!(json["zoneName"], "Europe/London");
+    assert_eq!(json["distance"].as_f64(), Some(round_to(city.distance, 3)));
+    assert_eq!(json["bearing"].as_f64(), Some(round_to(city.bearing, 1)));
+    assert!(json["distance"].as_f64().unwrap() > 80.0 && json["distance"].as_f64().unwrap() < 85.0);
+    assert!(json["bearing"].as_f64().unwrap() > 280.0 && json["bearing"].as_f64().unwrap() < 300.0);
+  }
+}
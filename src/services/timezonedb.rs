@@ -1,6 +1,10 @@
 use serde::{Serialize, Deserialize};
 use mysql::prelude::*;
 use crate::app::weekday_code::WeekdayCode;
+use crate::app::epoch_times::EpochTimes;
+use crate::app::relative_dates::{RelativeDateTime, RelativeInterpretation};
+use crate::app::recurrence::RecurrenceRule;
+use crate::app::transition_rules::ZoneTransition;
 use crate::data::mysql::*;
 use crate::app::date_conv::*;
 use chrono::Datelike;
@@ -62,6 +66,10 @@ pub struct TimeZone {
     pub ref_jd: Option<f64>,
     #[serde(rename="solarUtcOffset",skip_serializing_if = "Option::is_none")]
     pub solar_utc_offset: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub epochs: Option<EpochTimes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relative: Option<RelativeInterpretation>,
 }
 
 impl TimeZone {
@@ -69,7 +77,7 @@ impl TimeZone {
     let period = TimeZonePeriod::new(time_start, None, None);
     let ov = globals::get::<OffsetOverride>();
     let gmt_offset = ov.get().unwrap_or(gmt_offset);
-    TimeZone { zone_name, country_code, abbreviation, gmt_offset, dst, local_dt: None, utc: None, period, week_day: None, ref_unix: None, ref_jd: None, solar_utc_offset: None }
+    TimeZone { zone_name, country_code, abbreviation, gmt_offset, dst, local_dt: None, utc: None, period, week_day: None, ref_unix: None, ref_jd: None, solar_utc_offset: None, epochs: None, relative: None }
   }
 
   pub fn new_ocean(name: &str, lng: f64, date_str: &str) -> TimeZone {
@@ -97,7 +105,9 @@ impl TimeZone {
       week_day,
       ref_unix,
       ref_jd: None,
-      solar_utc_offset
+      solar_utc_offset,
+      epochs: None,
+      relative: None,
     }
   }
 
@@ -125,6 +135,16 @@ impl TimeZone {
     self.week_day = Some(unixtime_to_weekday(local_unix_ts));
   }
 
+  pub fn add_epochs(&mut self) {
+    if let Some(ref_ts) = self.ref_unix {
+      self.epochs = Some(EpochTimes::new(ref_ts));
+    }
+  }
+
+  pub fn set_relative(&mut self, relative: RelativeInterpretation) {
+    self.relative = Some(relative);
+  }
+
   pub fn set_natural_offset(&mut self, lng: f64) {
     self.solar_utc_offset = Some(natural_tz_offset_from_utc(lng));
   }
@@ -237,13 +257,19 @@ fn build_natural_timezone(zn: &str, date_str: &str, lng: f64, cc: String) -> Opt
   Some(tz_info)
 }
 
-fn match_nextprev_time_zone(zn: &str, ts: i64, next: bool) -> Option<TimeZone> {
+fn build_nextprev_time_zone_sql(zn: &str, ts: i64, next: bool) -> String {
   let comparator = if next { ">"} else { "<=" };
   let direction = if next { "ASC" } else { "DESC" };
-  let sql = format!("SELECT zone_name, country_code, abbreviation, time_start, gmt_offset, IF (dst = '1', true, false) AS dst from time_zone 
+  format!("SELECT zone_name, country_code, abbreviation, time_start, gmt_offset, IF (dst = '1', true, false) AS dst from time_zone 
   WHERE zone_name = '{}' AND time_start {} {}
-  ORDER BY time_start {} LIMIT 0, 1", zn, comparator, ts, direction);
-  fetch_time_zone_row(sql)
+  ORDER BY time_start {} LIMIT 0, 1", escape_sql(zn), comparator, ts, direction)
+}
+
+/*
+* Period of a zone in force at a timestamp or the next one after it. The zone name is escaped here, so pass it unescaped.
+*/
+pub fn match_nextprev_time_zone(zn: &str, ts: i64, next: bool) -> Option<TimeZone> {
+  fetch_time_zone_row(build_nextprev_time_zone_sql(zn, ts, next))
 }
 
 pub fn match_current_time_zone(zn: &str, date_str: &str, lng_opt: Option<f64>, enforce_dst: bool) -> Option<TimeZone> {
@@ -279,6 +305,142 @@ pub fn match_current_time_zone(zn: &str, date_str: &str, lng_opt: Option<f64>, e
   }
 }
 
+pub fn fetch_time_zone_rows(sql: String) -> Vec<TimeZone> {
+  if let Ok(mut conn) = connect_mysql() {
+    conn.query_map( sql,
+      |(zone_name, country_code, abbreviation, time_start, gmt_offset, dst)| {
+        TimeZone::new(zone_name, country_code, abbreviation, time_start, gmt_offset, dst)
+      },
+    ).unwrap_or_default()
+  } else {
+    vec![]
+  }
+}
+
+/*
+* All periods of a zone overlapping the given time range in ascending order, including the period
+* in force at the start, with the end and next offset of each period set from its successor
+*/
+pub fn match_time_zone_periods(zn: &str, start_ts: i64, end_ts: i64) -> Vec<TimeZone> {
+  let sql = format!("SELECT zone_name, country_code, abbreviation, time_start, gmt_offset, IF (dst = '1', true, false) AS dst from time_zone 
+  WHERE zone_name = '{}' AND time_start > {} AND time_start <= {}
+  ORDER BY time_start ASC", escape_sql(zn), start_ts, end_ts);
+  let mut rows: Vec<TimeZone> = match_nextprev_time_zone(zn, start_ts, false).into_iter().collect();
+  rows.extend(fetch_time_zone_rows(sql));
+  if let Some(next) = match_nextprev_time_zone(zn, end_ts, true) {
+    rows.push(next);
+  }
+  let num_rows = rows.len();
+  for index in 1..num_rows {
+    let (end, next_offset) = (rows[index].time_start(), rows[index].gmt_offset);
+    rows[index - 1].add_end(end, next_offset);
+  }
+  rows
+}
+
+/*
+* Offset in force at a UTC timestamp within a set of periods in ascending order
+*/
+pub fn match_offset_in_periods(periods: &[TimeZone], ts: i64) -> Option<i64> {
+  periods.iter().rev().find(|p| p.time_start() <= ts).map(|p| p.offset())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecurrenceOccurrence {
+  pub local: String,
+  pub utc: String,
+  #[serde(rename="refUnix")]
+  pub ref_unix: i64,
+  #[serde(rename="gmtOffset")]
+  pub gmt_offset: i64,
+  pub abbreviation: String,
+  pub dst: bool,
+  // the local time does not exist as the clocks go forward
+  pub gap: bool,
+  // the local time occurs twice as the clocks go back
+  pub overlap: bool,
+}
+
+/*
+* Expand a recurrence rule at a local wall-clock time in a zone and resolve every occurrence to UTC,
+* flagging occurrences in DST gaps and overlaps
+*/
+pub fn expand_recurrence_in_zone(zn: &str, rule: &RecurrenceRule, start_local: &str, max: usize, enforce_dst: bool) -> Vec<RecurrenceOccurrence> {
+  let start = iso_string_to_datetime(start_local);
+  let wall_times = rule.expand(start, max);
+  let (first_ts, last_ts) = match (wall_times.first(), wall_times.last()) {
+    (Some(first), Some(last)) => (first.timestamp(), last.timestamp()),
+    _ => return vec![]
+  };
+  let periods = match_time_zone_periods(zn, first_ts - 2 * 86_400, last_ts + 2 * 86_400);
+  if periods.is_empty() {
+    return vec![];
+  }
+  let mut occurrences: Vec<RecurrenceOccurrence> = Vec::new();
+  for wall in wall_times {
+    let wall_ts = wall.timestamp();
+    if let Some(res) = resolve_local_time_with_offsets(wall_ts, enforce_dst, |ts| match_offset_in_periods(&periods, ts)) {
+      if rule.is_after_utc_until(res.utc_ts) {
+        break;
+      }
+      if let Some(period) = periods.iter().rev().find(|p| p.time_start() <= res.utc_ts) {
+        let mut current = period.clone();
+        current.set_ref_time(res.utc_ts);
+        occurrences.push(RecurrenceOccurrence {
+          local: unixtime_to_utc(wall_ts),
+          utc: unixtime_to_utc(res.utc_ts),
+          ref_unix: res.utc_ts,
+          gmt_offset: current.offset(),
+          abbreviation: current.abbreviation.clone(),
+          dst: current.dst,
+          gap: res.gap,
+          overlap: !res.gap && (res.ambiguous || current.is_overlap_period()),
+        });
+      }
+    }
+  }
+  occurrences
+}
+
+/*
+* Latest timestamp considered when exporting transitions, beyond the last year covered by the time_zone table
+*/
+pub const MAX_TRANSITION_TS: i64 = 4_102_444_800; // 2100-01-01 00:00:00 UTC
+pub const MIN_TRANSITION_TS: i64 = -5_000_000_000; // before the earliest local mean times in the time_zone table
+
+/*
+* Last transition in the time_zone table up to MAX_TRANSITION_TS. Annual rules still followed in the final year
+* of the data are current, while rules that stop earlier have been abolished.
+*/
+pub fn match_transition_horizon() -> i64 {
+  let sql = format!("SELECT MAX(time_start) FROM time_zone WHERE time_start <= {}", MAX_TRANSITION_TS);
+  match connect_mysql() {
+    Ok(mut conn) => conn.query_first::<Option<i64>, String>(sql).ok().flatten().flatten().unwrap_or(MAX_TRANSITION_TS),
+    Err(_) => MAX_TRANSITION_TS
+  }
+}
+
+/*
+* Transitions of a zone from the given timestamp onwards. The first item describes the offset in force
+* at the start, with the same offset before and after.
+*/
+pub fn match_zone_transitions(zn: &str, start_ts: i64) -> Vec<ZoneTransition> {
+  let periods = match_time_zone_periods(zn, start_ts, MAX_TRANSITION_TS);
+  let mut transitions: Vec<ZoneTransition> = Vec::new();
+  for (index, period) in periods.iter().enumerate() {
+    let offset_from = if index > 0 { periods[index - 1].offset() } else { period.offset() };
+    let ts = if index > 0 { period.time_start() } else { period.time_start().max(start_ts) };
+    transitions.push(ZoneTransition {
+      ts,
+      offset_from,
+      offset_to: period.offset(),
+      abbreviation: period.abbreviation.clone(),
+      dst: period.dst,
+    });
+  }
+  transitions
+}
+
 pub fn fetch_time_zone_row(sql: String) -> Option<TimeZone> {
     if let Ok(mut conn) = connect_mysql() {
         let zone_results = conn
@@ -305,4 +467,128 @@ pub fn fetch_time_zone_row(sql: String) -> Option<TimeZone> {
     }
 }
 
+/*
+* Offset from UTC in seconds valid for the zone at the given unix timestamp
+*/
+pub fn match_zone_offset(zn: &str, ts: i64) -> Option<i64> {
+  match_nextprev_time_zone(zn, ts, false).map(|tz| tz.offset())
+}
+
+/*
+* Resolve a local wall-clock time, expressed as if it were a unix timestamp, to UTC via a function
+* returning the offset valid at any UTC timestamp. In the overlap when the clocks go back, summer time
+* applies unless enforce_dst is false. In the gap when the clocks go forward, the offset before the transition applies.
+*/
+pub fn local_to_utc_with_offsets<F>(local_ts: i64, enforce_dst: bool, offset_at: F) -> Option<i64> where F: Fn(i64) -> Option<i64> {
+  resolve_local_time_with_offsets(local_ts, enforce_dst, offset_at).map(|res| res.utc_ts)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LocalTimeResolution {
+  pub utc_ts: i64,
+  // the local time is skipped when the clocks go forward
+  pub gap: bool,
+  // the local time occurs twice when the clocks go back
+  pub ambiguous: bool,
+}
+
+pub fn resolve_local_time_with_offsets<F>(local_ts: i64, enforce_dst: bool, offset_at: F) -> Option<LocalTimeResolution> where F: Fn(i64) -> Option<i64> {
+  let before = offset_at(local_ts - 86_400)?;
+  let after = offset_at(local_ts + 86_400).unwrap_or(before);
+  let mut candidates: Vec<i64> = vec![before, after].into_iter().filter(|off| offset_at(local_ts - off) == Some(*off)).collect();
+  candidates.dedup();
+  let offset = match candidates.len() {
+    0 => before.min(after),
+    1 => candidates[0],
+    _ => if enforce_dst { before.max(after) } else { before.min(after) }
+  };
+  Some(LocalTimeResolution { utc_ts: local_ts - offset, gap: candidates.is_empty(), ambiguous: candidates.len() > 1 })
+}
 
+/*
+* Evaluate a relative date expression either in UTC or in the local time of a zone.
+* Without a matched zone, local time falls back to the natural hourly offset of the longitude.
+*/
+pub fn resolve_relative_datetime(rel: &RelativeDateTime, local: bool, zone_name: Option<&str>, lng: f64, enforce_dst: bool) -> (String, RelativeInterpretation) {
+  let now_ts = current_timestamp();
+  let extra = rel.extra_seconds();
+  let (utc_ts, frame) = if rel.is_instant() {
+    (now_ts + extra, "UTC".to_owned())
+  } else if local {
+    match zone_name {
+      Some(zn) => {
+        let offset = match_zone_offset(zn, now_ts).unwrap_or(0);
+        let wall_ts = rel.wall_time(now_ts, offset).timestamp();
+        let utc_ts = local_to_utc_with_offsets(wall_ts, enforce_dst, |ts| match_zone_offset(zn, ts)).unwrap_or(wall_ts - offset);
+        (utc_ts + extra, format!("local time in {}", zn))
+      },
+      _ => {
+        let offset = natural_hours_offset_from_utc(lng) as i64 * 3600;
+        let wall_ts = rel.wall_time(now_ts, offset).timestamp();
+        (wall_ts - offset + extra, format!("local time at UTC{:+03}:00", offset / 3600))
+      }
+    }
+  } else {
+    (rel.wall_time(now_ts, 0).timestamp() + extra, "UTC".to_owned())
+  };
+  (unixtime_to_utc(utc_ts), rel.to_interpretation(&frame, local))
+}
+
+/*
+* Fingerprint of the time_zone table that changes whenever zone data is reloaded
+*/
+pub fn zone_data_version() -> Option<String> {
+  let sql = "SELECT COUNT(*), COALESCE(MAX(time_start), 0) FROM time_zone";
+  let mut conn = connect_mysql().ok()?;
+  let rows: Vec<(i64, i64)> = conn.query_map(sql, |(count, max_start)| (count, max_start)).ok()?;
+  rows.first().map(|(count, max_start)| format!("{}:{}", count, max_start))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::app::transition_rules::tests::utc_ts;
+
+  // Europe/London in 2022: BST from 01:00 UTC on 27 March to 01:00 UTC on 30 October
+  fn london_offset_at(ts: i64) -> Option<i64> {
+    Some(if ts >= utc_ts("2022-03-27 01:00") && ts < utc_ts("2022-10-30 01:00") { 3600 } else { 0 })
+  }
+
+  #[test]
+  fn local_times_skipped_when_the_clocks_go_forward_are_gaps() {
+    let res = resolve_local_time_with_offsets(utc_ts("2022-03-27 01:30"), true, london_offset_at).unwrap();
+    assert!(res.gap && !res.ambiguous);
+    // read with the offset before the transition, i.e. as 02:30 BST
+    assert_eq!(res.utc_ts, utc_ts("2022-03-27 01:30"));
+    let res = resolve_local_time_with_offsets(utc_ts("2022-03-27 02:00"), true, london_offset_at).unwrap();
+    assert!(!res.gap);
+    assert_eq!(res.utc_ts, utc_ts("2022-03-27 01:00"));
+  }
+
+  #[test]
+  fn local_times_repeated_when_the_clocks_go_back_are_ambiguous() {
+    let res = resolve_local_time_with_offsets(utc_ts("2022-10-30 01:30"), true, london_offset_at).unwrap();
+    assert!(res.ambiguous && !res.gap);
+    assert_eq!(res.utc_ts, utc_ts("2022-10-30 00:30"));
+    let res = resolve_local_time_with_offsets(utc_ts("2022-10-30 01:30"), false, london_offset_at).unwrap();
+    assert_eq!(res.utc_ts, utc_ts("2022-10-30 01:30"));
+    let res = resolve_local_time_with_offsets(utc_ts("2022-10-30 02:00"), true, london_offset_at).unwrap();
+    assert!(!res.ambiguous);
+    assert_eq!(res.utc_ts, utc_ts("2022-10-30 02:00"));
+  }
+
+  #[test]
+  fn local_times_outside_transitions_use_the_offset_in_force() {
+    let res = resolve_local_time_with_offsets(utc_ts("2022-07-01 12:00"), true, london_offset_at).unwrap();
+    assert!(!res.gap && !res.ambiguous);
+    assert_eq!(res.utc_ts, utc_ts("2022-07-01 11:00"));
+    assert!(resolve_local_time_with_offsets(0, true, |_| None).is_none());
+  }
+
+  #[test]
+  fn zone_names_are_escaped_once() {
+    let sql = build_nextprev_time_zone_sql("Bad'Zone", 0, true);
+    assert!(sql.contains("WHERE zone_name = 'Bad''Zone' AND time_start > 0"));
+    assert!(sql.contains("ORDER BY time_start ASC"));
+  }
+}
@@ -0,0 +1,191 @@
+use mysql::prelude::Queryable;
+use serde::{Serialize, Deserialize};
+use crate::data::mysql::{connect_mysql, escape_sql};
+use super::countries::{output_country_code, find_country};
+use crate::app::date_conv::{match_unix_ts_from_fuzzy_datetime, unixtime_to_utc};
+
+/*
+* Zone that used an abbreviation with the offset it stood for and the period of use
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbbreviationZone {
+  #[serde(rename="zoneName")]
+  pub zone_name: String,
+  #[serde(rename="countryCode")]
+  pub country_code: String,
+  #[serde(rename="gmtOffset")]
+  pub gmt_offset: i32,
+  pub dst: bool,
+  // start of the first period with the abbreviation and offset
+  pub from: String,
+  // end of the last such period, if it has ended or is scheduled to end
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub until: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbbreviationInfo {
+  pub abbreviation: String,
+  // the abbreviation stands for more than one UTC offset
+  pub ambiguous: bool,
+  pub offsets: Vec<i32>,
+  pub count: usize,
+  pub zones: Vec<AbbreviationZone>,
+}
+
+impl AbbreviationInfo {
+  pub fn new(abbreviation: &str, zones: Vec<AbbreviationZone>) -> AbbreviationInfo {
+    let mut offsets: Vec<i32> = zones.iter().map(|zone| zone.gmt_offset).collect();
+    offsets.sort();
+    offsets.dedup();
+    AbbreviationInfo { abbreviation: abbreviation.to_owned(), ambiguous: offsets.len() > 1, offsets, count: zones.len(), zones }
+  }
+}
+
+/*
+* Letters as in CEST or numeric offsets as in +0545, as used in the abbreviation column
+*/
+pub fn is_zone_abbreviation(text: &str) -> bool {
+  let abbr = text.trim();
+  let (sign, rest) = abbr.split_at(abbr.chars().next().filter(|c| *c == '+' || *c == '-').map(|_| 1).unwrap_or(0));
+  (2..=6).contains(&abbr.len()) && if sign.is_empty() { rest.chars().all(|c| c.is_ascii_alphabetic()) } else { rest.chars().all(|c| c.is_ascii_digit()) }
+}
+
+fn normalize_abbreviation(text: &str) -> String {
+  text.trim().to_uppercase()
+}
+
+/*
+* Periods with an abbreviation and the start of the following period, read with a correlated MIN rather than per row.
+* With a timestamp only the period in force at that time is read per zone, otherwise the latest per zone and offset
+* along with the first start.
+*/
+fn build_abbreviation_zones_sql(abbr: &str, ts: Option<i64>, cc: Option<&str>) -> String {
+  let abbreviation = escape_sql(&normalize_abbreviation(abbr));
+  let cc_clause = cc.map(|c| format!(" AND tz.country_code = '{}'", escape_sql(c))).unwrap_or_default();
+  let until = |alias: &str| format!("(SELECT MIN(nx.time_start) FROM time_zone AS nx WHERE nx.zone_name = {a}.zone_name AND nx.time_start > {a}.time_start) AS until_ts", a = alias);
+  match ts {
+    Some(ts) => format!("SELECT tz.zone_name, tz.country_code, tz.gmt_offset, IF (tz.dst = '1', true, false) AS dst, tz.time_start AS first_start, {until} FROM time_zone AS tz
+      INNER JOIN (SELECT zone_name, MAX(time_start) AS time_start FROM time_zone WHERE time_start <= {ts} GROUP BY zone_name) AS cur
+      ON cur.zone_name = tz.zone_name AND cur.time_start = tz.time_start
+      WHERE tz.abbreviation = '{abbr}'{cc} ORDER BY tz.zone_name", until = until("tz"), ts = ts, abbr = abbreviation, cc = cc_clause),
+    None => format!("SELECT p.zone_name, p.country_code, p.gmt_offset, IF (p.dst = '1', true, false) AS dst, p.first_start, {until} FROM (
+      SELECT tz.zone_name, tz.country_code, tz.gmt_offset, tz.dst, MIN(tz.time_start) AS first_start, MAX(tz.time_start) AS time_start FROM time_zone AS tz
+      WHERE tz.abbreviation = '{abbr}'{cc}
+      GROUP BY tz.zone_name, tz.country_code, tz.gmt_offset, tz.dst
+    ) AS p ORDER BY p.time_start DESC, p.zone_name", until = until("p"), abbr = abbreviation, cc = cc_clause),
+  }
+}
+
+/*
+* Zones that used an abbreviation, with one entry per zone and offset, most recent first.
+* With a date only zones whose period in force at that time used the abbreviation are included.
+* Country codes are ISO alpha-2.
+*/
+pub fn match_abbreviation_zones(abbr: &str, date_str: Option<&str>, cc: Option<&str>) -> Vec<AbbreviationZone> {
+  let sql = build_abbreviation_zones_sql(abbr, date_str.map(match_unix_ts_from_fuzzy_datetime), cc);
+  if let Ok(mut conn) = connect_mysql() {
+    conn.query_map(sql, |(zone_name, country_code, gmt_offset, dst, first_start, until_ts): (String, String, i32, bool, i64, Option<i64>)| {
+      AbbreviationZone {
+        zone_name,
+        country_code: output_country_code(&country_code),
+        gmt_offset,
+        dst,
+        from: unixtime_to_utc(first_start),
+        until: until_ts.map(unixtime_to_utc),
+      }
+    }).unwrap_or_default()
+  } else {
+    vec![]
+  }
+}
+
+/*
+* Zone for matches of an abbreviation: the only matching zone, or else the given default zone if among the matches.
+* If all matches have the same offset, as with CEST in Europe, the representative zone is the first in alphabetical order
+* that is the main zone of its country, e.g. Europe/Amsterdam rather than Africa/Ceuta, so the UTC time is the same
+* whichever zone was meant.
+*/
+pub fn pick_abbreviation_zone(zones: &[AbbreviationZone], default_zone: Option<&str>) -> Option<String> {
+  if zones.len() == 1 {
+    return Some(zones[0].zone_name.clone());
+  }
+  if let Some(zn) = default_zone.filter(|zn| zones.iter().any(|zone| zone.zone_name == *zn)) {
+    return Some(zn.to_owned());
+  }
+  let first = zones.first()?;
+  if zones.iter().any(|zone| zone.gmt_offset != first.gmt_offset) {
+    return None;
+  }
+  let mut names: Vec<&AbbreviationZone> = zones.iter().collect();
+  names.sort_by(|a, b| a.zone_name.cmp(&b.zone_name));
+  let is_main_zone = |zone: &AbbreviationZone| find_country(&zone.country_code).and_then(|country| country.zones.first().cloned()) == Some(zone.zone_name.clone());
+  names.iter().find(|zone| is_main_zone(zone)).or(names.first()).map(|zone| zone.zone_name.clone())
+}
+
+/*
+* Zone for an abbreviation given as a zone name at a date, as picked from the matches above.
+* Otherwise all matches are returned to report the ambiguity, none if the abbreviation is unknown.
+*/
+pub fn select_abbreviation_zone(abbr: &str, date_str: &str, cc: Option<&str>) -> Result<String, Vec<AbbreviationZone>> {
+  let zones = match_abbreviation_zones(abbr, Some(date_str), cc);
+  let default_zone = cc.and_then(find_country).and_then(|country| country.zones.first().cloned());
+  match pick_abbreviation_zone(&zones, default_zone.as_deref()) {
+    Some(zn) => Ok(zn),
+    None => Err(zones)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn zone(zone_name: &str, cc: &str, gmt_offset: i32) -> AbbreviationZone {
+    AbbreviationZone { zone_name: zone_name.to_owned(), country_code: cc.to_owned(), gmt_offset, dst: false, from: "2000-01-01T00:00:00".to_owned(), until: None }
+  }
+
+  #[test]
+  fn abbreviations_are_letters_or_signed_digits() {
+    assert!(is_zone_abbreviation("CEST"));
+    assert!(is_zone_abbreviation(" ist "));
+    assert!(is_zone_abbreviation("+0545"));
+    assert!(is_zone_abbreviation("-03"));
+    assert!(!is_zone_abbreviation("Z"));
+    assert!(!is_zone_abbreviation("Europe/Paris"));
+    assert!(!is_zone_abbreviation("+05:45"));
+    assert!(!is_zone_abbreviation("UTC+1"));
+    assert!(!is_zone_abbreviation("ABCDEFG"));
+  }
+
+  #[test]
+  fn abbreviations_with_several_offsets_are_ambiguous() {
+    let info = AbbreviationInfo::new("IST", vec![zone("Asia/Kolkata", "IN", 19800), zone("Europe/Dublin", "IE", 3600), zone("Asia/Jerusalem", "IL", 7200), zone("Asia/Calcutta", "IN", 19800)]);
+    assert!(info.ambiguous);
+    assert_eq!(info.offsets, vec![3600, 7200, 19800]);
+    assert_eq!(info.count, 4);
+    let info = AbbreviationInfo::new("JST", vec![zone("Asia/Tokyo", "JP", 32400)]);
+    assert!(!info.ambiguous);
+    assert_eq!(info.offsets, vec![32400]);
+  }
+
+  #[test]
+  fn abbreviations_with_one_offset_pick_a_representative_zone() {
+    let cest = [zone("Africa/Ceuta", "ES", 7200), zone("Europe/Berlin", "DE", 7200), zone("Europe/Busingen", "DE", 7200), zone("Europe/Amsterdam", "NL", 7200)];
+    assert_eq!(pick_abbreviation_zone(&cest, None).as_deref(), Some("Europe/Amsterdam"));
+    assert_eq!(pick_abbreviation_zone(&cest, Some("Europe/Berlin")).as_deref(), Some("Europe/Berlin"));
+    let ist = [zone("Asia/Kolkata", "IN", 19800), zone("Europe/Dublin", "IE", 3600)];
+    assert_eq!(pick_abbreviation_zone(&ist, None), None);
+    assert_eq!(pick_abbreviation_zone(&ist, Some("Europe/Dublin")).as_deref(), Some("Europe/Dublin"));
+    assert_eq!(pick_abbreviation_zone(&[], None), None);
+  }
+
+  #[test]
+  fn following_periods_are_read_in_the_main_query() {
+    let sql = build_abbreviation_zones_sql("cest", Some(1_656_633_600), Some("DE"));
+    assert!(sql.contains("(SELECT MIN(nx.time_start) FROM time_zone AS nx WHERE nx.zone_name = tz.zone_name AND nx.time_start > tz.time_start) AS until_ts"));
+    assert!(sql.contains("WHERE tz.abbreviation = 'CEST' AND tz.country_code = 'DE'"));
+    let sql = build_abbreviation_zones_sql("IST", None, None);
+    assert!(sql.contains("nx.zone_name = p.zone_name AND nx.time_start > p.time_start"));
+    assert!(sql.contains("MIN(tz.time_start) AS first_start, MAX(tz.time_start) AS time_start"));
+  }
+}
@@ -0,0 +1,272 @@
+use mysql::prelude::Queryable;
+use serde::{Serialize, Deserialize};
+use crate::data::mysql::{connect_mysql, escape_sql, escape_like};
+use crate::data::zone_aliases::ZONE_ALIASES;
+use super::countries::output_country_code;
+use super::geonames::is_valid_zone_name;
+use super::locality_index::{edit_distance_within, max_typos};
+use crate::app::date_conv::unixtime_to_utc;
+use super::timezonedb::{TimeZone, fetch_time_zone_rows, match_current_time_zone};
+
+const ZONE_SUGGESTIONS_MAX: usize = 5;
+
+/*
+* Known zone with the period in force at the reference time and its former or alternative names
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneEntry {
+  #[serde(rename="zoneName")]
+  pub zone_name: String,
+  #[serde(rename="countryCode")]
+  pub country_code: String,
+  pub abbreviation: String,
+  #[serde(rename="gmtOffset")]
+  pub gmt_offset: i32,
+  pub dst: bool,
+  pub aliases: Vec<String>,
+}
+
+impl ZoneEntry {
+  fn from_time_zone(tz: TimeZone) -> ZoneEntry {
+    ZoneEntry {
+      aliases: list_zone_aliases(&tz.zone_name),
+      country_code: output_country_code(&tz.country_code),
+      abbreviation: tz.abbreviation,
+      gmt_offset: tz.gmt_offset,
+      dst: tz.dst,
+      zone_name: tz.zone_name,
+    }
+  }
+}
+
+/*
+* Zone names in the time_zone table, loaded on first use
+*/
+#[derive(Default)]
+struct ZoneCatalogue {
+  names: Vec<String>,
+  loaded: bool,
+}
+
+fn with_zone_names<T>(callback: impl FnOnce(&[String]) -> T) -> T {
+  let mut catalogue = globals::get::<ZoneCatalogue>();
+  if !catalogue.loaded {
+    catalogue.names = if let Ok(mut conn) = connect_mysql() {
+      conn.query::<String, &str>("SELECT DISTINCT zone_name FROM time_zone ORDER BY zone_name").unwrap_or_default()
+    } else {
+      vec![]
+    };
+    // retry on the next request if the table could not be read
+    catalogue.loaded = !catalogue.names.is_empty();
+  }
+  callback(&catalogue.names)
+}
+
+/*
+* Former and alternative names linked to a zone in either direction, so old names still in the table list the new ones
+*/
+pub fn list_zone_aliases(zn: &str) -> Vec<String> {
+  ZONE_ALIASES.into_iter().filter_map(|(alias, canonical)| {
+    if canonical == zn {
+      Some(alias.to_owned())
+    } else if alias == zn {
+      Some(canonical.to_owned())
+    } else {
+      None
+    }
+  }).collect()
+}
+
+/*
+* Zone name as held in the time_zone table for a zone name in any letter case or a linked name such as Asia/Calcutta.
+* Without access to the table, linked names are resolved and other names with a region prefix accepted as they are.
+*/
+fn select_zone_name(names: &[String], name: &str) -> Option<String> {
+  if names.is_empty() {
+    return ZONE_ALIASES.into_iter().find(|(alias, _)| alias.eq_ignore_ascii_case(name)).map(|(_, canonical)| canonical.to_owned())
+      .or_else(|| Some(name.to_owned()).filter(|n| is_valid_zone_name(n)));
+  }
+  let find_name = |candidate: &str| names.iter().find(|n| n.eq_ignore_ascii_case(candidate)).cloned();
+  find_name(name)
+    .or_else(|| ZONE_ALIASES.into_iter().find(|(alias, _)| alias.eq_ignore_ascii_case(name)).and_then(|(_, canonical)| find_name(canonical)))
+    .or_else(|| ZONE_ALIASES.into_iter().filter(|(_, canonical)| canonical.eq_ignore_ascii_case(name)).find_map(|(alias, _)| find_name(alias)))
+}
+
+pub fn resolve_zone_name(zn: &str) -> Option<String> {
+  let name = zn.trim();
+  if name.is_empty() {
+    return None;
+  }
+  with_zone_names(|names| select_zone_name(names, name))
+}
+
+/*
+* Known zone names resembling an unrecognised one, by edit distance over the whole name or the city part,
+* e.g. Europe/Amsterdam for Europe/Amsterdm or Amsterdam
+*/
+fn rank_zone_names(names: &[String], zn: &str) -> Vec<String> {
+  let query = zn.trim().to_lowercase().replace(' ', "_");
+  let q_chars: Vec<char> = query.chars().collect();
+  let q_city: Vec<char> = query.rsplit('/').next().unwrap_or("").chars().collect();
+  let (full_limit, city_limit) = (max_typos(q_chars.len()), max_typos(q_city.len()));
+  let mut matches: Vec<(usize, &String)> = names.iter().filter_map(|name| {
+    let lower = name.to_lowercase();
+    let chars: Vec<char> = lower.chars().collect();
+    let city: Vec<char> = lower.rsplit('/').next().unwrap_or("").chars().collect();
+    // rank matches on the city part alone after those on the whole name
+    edit_distance_within(&q_chars, &chars, full_limit)
+      .or_else(|| edit_distance_within(&q_city, &city, city_limit).map(|d| d + 1))
+      .map(|d| (d, name))
+  }).collect();
+  matches.sort();
+  matches.into_iter().take(ZONE_SUGGESTIONS_MAX).map(|(_, name)| name.clone()).collect()
+}
+
+pub fn suggest_zone_names(zn: &str) -> Vec<String> {
+  with_zone_names(|names| rank_zone_names(names, zn))
+}
+
+/*
+* All zones with the period in force at a UTC timestamp, optionally for one country (ISO alpha-2)
+*/
+pub fn list_zones(cc: Option<&str>, ts: i64) -> Vec<ZoneEntry> {
+  let cc_clause = cc.map(|c| format!(" AND country_code = '{}'", escape_sql(c))).unwrap_or_default();
+  let sql = format!("SELECT tz.zone_name, tz.country_code, tz.abbreviation, tz.time_start, tz.gmt_offset, IF (tz.dst = '1', true, false) AS dst FROM time_zone AS tz
+    INNER JOIN (SELECT zone_name, MAX(time_start) AS time_start FROM time_zone WHERE time_start <= {ts}{cc} GROUP BY zone_name) AS cur
+    ON cur.zone_name = tz.zone_name AND cur.time_start = tz.time_start
+    ORDER BY tz.zone_name", ts = ts, cc = cc_clause);
+  fetch_time_zone_rows(sql).into_iter().map(ZoneEntry::from_time_zone).collect()
+}
+
+/*
+* Zone whose period in force at a given time had a given offset, with the start and end of that period
+*/
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OffsetZone {
+  #[serde(rename="zoneName")]
+  pub zone_name: String,
+  #[serde(rename="countryCode")]
+  pub country_code: String,
+  pub abbreviation: String,
+  #[serde(rename="gmtOffset")]
+  pub gmt_offset: i32,
+  pub dst: bool,
+  pub from: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub until: Option<String>,
+}
+
+/*
+* Periods in force at a UTC timestamp with an offset, with the start of the following period read by a correlated MIN
+*/
+fn build_zones_by_offset_sql(offset: i32, ts: i64, dst: Option<bool>, cc: Option<&str>, region: Option<&str>) -> String {
+  let mut conditions = vec![format!("tz.gmt_offset = {}", offset)];
+  if let Some(is_dst) = dst {
+    conditions.push(format!("tz.dst = '{}'", is_dst as u8));
+  }
+  if let Some(c) = cc {
+    conditions.push(format!("tz.country_code = '{}'", escape_sql(c)));
+  }
+  if let Some(reg) = region.map(|r| r.trim()).filter(|r| !r.is_empty()) {
+    conditions.push(format!("(tz.zone_name LIKE '{p}/%' OR tz.zone_name IN (SELECT DISTINCT zone_name FROM cities WHERE admin_name = '{r}'))", p = escape_like(reg), r = escape_sql(reg)));
+  }
+  format!("SELECT tz.zone_name, tz.country_code, tz.abbreviation, tz.time_start, tz.gmt_offset, IF (tz.dst = '1', true, false) AS dst,
+    (SELECT MIN(nx.time_start) FROM time_zone AS nx WHERE nx.zone_name = tz.zone_name AND nx.time_start > tz.time_start) AS until_ts
+    FROM time_zone AS tz
+    INNER JOIN (SELECT zone_name, MAX(time_start) AS time_start FROM time_zone WHERE time_start <= {ts} GROUP BY zone_name) AS cur
+    ON cur.zone_name = tz.zone_name AND cur.time_start = tz.time_start
+    WHERE {conditions} ORDER BY tz.zone_name", ts = ts, conditions = conditions.join(" AND "))
+}
+
+/*
+* Zones that had a UTC offset in seconds at a UTC timestamp, optionally only in daylight saving or standard time,
+* for one country (ISO alpha-2) or for a region given as a zone name prefix such as America or a region name in the cities table
+*/
+pub fn match_zones_by_offset(offset: i32, ts: i64, dst: Option<bool>, cc: Option<&str>, region: Option<&str>) -> Vec<OffsetZone> {
+  let sql = build_zones_by_offset_sql(offset, ts, dst, cc, region);
+  if let Ok(mut conn) = connect_mysql() {
+    conn.query_map(sql, |(zone_name, country_code, abbreviation, time_start, gmt_offset, dst, until_ts): (String, String, String, i64, i32, bool, Option<i64>)| {
+      OffsetZone {
+        zone_name,
+        country_code: output_country_code(&country_code),
+        abbreviation,
+        gmt_offset,
+        dst,
+        from: unixtime_to_utc(time_start),
+        until: until_ts.map(unixtime_to_utc),
+      }
+    }).unwrap_or_default()
+  } else {
+    vec![]
+  }
+}
+
+/*
+* Metadata of a single zone as in force at a UTC date-time
+*/
+pub fn match_zone_entry(zn: &str, date_str: &str) -> Option<ZoneEntry> {
+  match_current_time_zone(zn, date_str, None, true).map(ZoneEntry::from_time_zone)
+}
+
+/*
+* Drop the cached zone names so they are reloaded from the time_zone table
+*/
+pub fn clear_zone_catalogue() -> usize {
+  let mut catalogue = globals::get::<ZoneCatalogue>();
+  let count = catalogue.names.len();
+  *catalogue = ZoneCatalogue::default();
+  count
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn offset_zones_read_following_periods_in_the_main_query() {
+    let sql = build_zones_by_offset_sql(20700, 1_656_633_600, Some(false), Some("NP"), Some("Asia"));
+    assert!(sql.contains("(SELECT MIN(nx.time_start) FROM time_zone AS nx WHERE nx.zone_name = tz.zone_name AND nx.time_start > tz.time_start) AS until_ts"));
+    assert!(sql.contains("WHERE tz.gmt_offset = 20700 AND tz.dst = '0' AND tz.country_code = 'NP' AND (tz.zone_name LIKE 'Asia/%'"));
+    assert!(build_zones_by_offset_sql(0, 0, None, None, Some("a_b")).contains("LIKE 'a\\\\_b/%' OR tz.zone_name IN (SELECT DISTINCT zone_name FROM cities WHERE admin_name = 'a_b')"));
+  }
+
+  fn catalogue() -> Vec<String> {
+    ["America/New_York", "Asia/Calcutta", "Asia/Kolkata", "Europe/Amsterdam", "Europe/Andorra", "Europe/Athens", "Europe/Kiev", "Europe/London", "Europe/Paris"]
+      .iter().map(|name| name.to_string()).collect()
+  }
+
+  #[test]
+  fn zone_names_are_matched_in_any_case_or_by_alias() {
+    let names = catalogue();
+    assert_eq!(select_zone_name(&names, "europe/london").as_deref(), Some("Europe/London"));
+    assert_eq!(select_zone_name(&names, "Asia/Calcutta").as_deref(), Some("Asia/Calcutta"));
+    assert_eq!(select_zone_name(&names, "Europe/Nowhere"), None);
+    // only the older name is in the table
+    assert_eq!(select_zone_name(&names, "Europe/Kyiv").as_deref(), Some("Europe/Kiev"));
+    let names: Vec<String> = vec!["Asia/Kolkata".to_owned()];
+    assert_eq!(select_zone_name(&names, "asia/calcutta").as_deref(), Some("Asia/Kolkata"));
+  }
+
+  #[test]
+  fn zone_names_resolve_without_the_table() {
+    assert_eq!(select_zone_name(&[], "Asia/Calcutta").as_deref(), Some("Asia/Kolkata"));
+    assert_eq!(select_zone_name(&[], "Europe/Amsterdam").as_deref(), Some("Europe/Amsterdam"));
+    assert_eq!(select_zone_name(&[], "Amsterdam"), None);
+  }
+
+  #[test]
+  fn aliases_are_listed_in_both_directions() {
+    assert!(list_zone_aliases("Asia/Kolkata").contains(&"Asia/Calcutta".to_owned()));
+    assert_eq!(list_zone_aliases("Europe/Kiev"), vec!["Europe/Kyiv"]);
+    assert!(list_zone_aliases("Europe/Paris").is_empty());
+  }
+
+  #[test]
+  fn misspelt_zone_names_suggest_the_closest_names() {
+    let names = catalogue();
+    assert_eq!(rank_zone_names(&names, "Europe/Amsterdm").first().map(|n| n.as_str()), Some("Europe/Amsterdam"));
+    assert_eq!(rank_zone_names(&names, "amsterdam").first().map(|n| n.as_str()), Some("Europe/Amsterdam"));
+    assert_eq!(rank_zone_names(&names, "new york"), vec!["America/New_York"]);
+    assert!(rank_zone_names(&names, "Pacific/Tarawa").is_empty());
+  }
+}
@@ -0,0 +1,244 @@
+use std::collections::{HashMap, VecDeque};
+use crate::constants::*;
+use crate::app::geohash::encode_geohash;
+use crate::app::date_conv::current_timestamp;
+use super::geonames::{GeoNameRow, TimeZoneInfo};
+use super::timezonedb::zone_data_version;
+
+/*
+* Upstream results memoised per geohash cell
+*/
+#[derive(Debug, Clone)]
+pub enum MemoValue {
+  Zone(TimeZoneInfo),
+  Placenames(Vec<GeoNameRow>),
+}
+
+impl MemoValue {
+  /*
+  * Results for different points in a cell must agree on this signature before the cell is trusted
+  */
+  fn signature(&self) -> String {
+    match self {
+      MemoValue::Zone(tz_item) => tz_item.tz.clone(),
+      MemoValue::Placenames(rows) => rows.iter().map(|row| row.fcode.clone() + ":" + &row.name).collect::<Vec<String>>().join("|"),
+    }
+  }
+
+  /*
+  * Value as returned for another point in the cell. Ocean rows carry the query point, not a fixed place.
+  */
+  fn at_point(mut self, lat: f64, lng: f64) -> MemoValue {
+    if let MemoValue::Placenames(rows) = &mut self {
+      for row in rows.iter_mut().filter(|row| row.fcode == "OCEAN") {
+        row.lat = lat;
+        row.lng = lng;
+      }
+    }
+    self
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoKind {
+  Zone,
+  Placenames,
+}
+
+#[derive(Debug, Clone)]
+struct MemoCell {
+  value: MemoValue,
+  // distinct points that returned the same result, rounded to about a metre
+  samples: Vec<(i64, i64)>,
+  // points in the cell returned different results, e.g. on a zone boundary
+  mixed: bool,
+}
+
+#[derive(Default)]
+struct ZoneMemo {
+  cells: HashMap<(MemoKind, String), MemoCell>,
+  // cell keys in order of creation, so the oldest are evicted first when full
+  order: VecDeque<(MemoKind, String)>,
+  version: Option<String>,
+  last_checked: i64,
+  hits: u64,
+}
+
+impl ZoneMemo {
+  fn insert_cell(&mut self, key: (MemoKind, String), cell: MemoCell, max_cells: usize) {
+    while self.cells.len() >= max_cells.max(1) {
+      match self.order.pop_front() {
+        Some(oldest) => { self.cells.remove(&oldest); },
+        None => break
+      }
+    }
+    self.order.push_back(key.clone());
+    self.cells.insert(key, cell);
+  }
+
+  fn clear_cells(&mut self) -> usize {
+    let count = self.cells.len();
+    self.cells.clear();
+    self.order.clear();
+    count
+  }
+}
+
+/*
+* Key of the cell containing a point
+*/
+fn cell_key(kind: MemoKind, lat: f64, lng: f64, precision: usize) -> (MemoKind, String) {
+  (kind, encode_geohash(lat, lng, precision))
+}
+
+fn match_env_usize(key: &str, default_value: usize) -> usize {
+  dotenv::var(key).ok().and_then(|v| v.parse::<usize>().ok()).unwrap_or(default_value)
+}
+
+/*
+* Geohash length of memo cells from geo_memo_precision, where 0 disables the memo
+*/
+fn memo_precision() -> usize {
+  match_env_usize("geo_memo_precision", GEO_MEMO_PRECISION_DEFAULT).min(12)
+}
+
+/*
+* Clear all cells when the zone data has changed, checking the time_zone table at most every few minutes
+*/
+fn check_zone_data_version() {
+  let due = {
+    let memo = globals::get::<ZoneMemo>();
+    current_timestamp() - memo.last_checked >= GEO_MEMO_VERSION_CHECK_SECS
+  };
+  if due {
+    let version = zone_data_version();
+    let mut memo = globals::get::<ZoneMemo>();
+    memo.last_checked = current_timestamp();
+    if version.is_some() && version != memo.version {
+      if memo.version.is_some() {
+        memo.clear_cells();
+      }
+      memo.version = version;
+    }
+  }
+}
+
+/*
+* Result for a point if earlier lookups at other points in the same cell consistently returned it.
+* Place names are only reused if the cell also lies within a single zone.
+*/
+pub fn memo_lookup(kind: MemoKind, lat: f64, lng: f64) -> Option<MemoValue> {
+  let precision = memo_precision();
+  if precision < 1 {
+    return None;
+  }
+  check_zone_data_version();
+  let confirmations = match_env_usize("geo_memo_confirmations", GEO_MEMO_CONFIRMATIONS_DEFAULT as usize).max(1) as u32;
+  let mut memo = globals::get::<ZoneMemo>();
+  let is_trusted = |cell: Option<&MemoCell>| cell.map(|c| !c.mixed && c.samples.len() as u32 >= confirmations).unwrap_or(false);
+  let zone_cell = memo.cells.get(&cell_key(MemoKind::Zone, lat, lng, precision));
+  let zone_ok = match kind {
+    MemoKind::Zone => true,
+    MemoKind::Placenames => zone_cell.map(|c| !c.mixed).unwrap_or(true),
+  };
+  let cell = memo.cells.get(&cell_key(kind, lat, lng, precision));
+  if zone_ok && is_trusted(cell) {
+    let value = cell.map(|c| c.value.clone().at_point(lat, lng));
+    memo.hits += 1;
+    value
+  } else {
+    None
+  }
+}
+
+/*
+* Add an upstream result for a point to its cell
+*/
+pub fn memo_record(kind: MemoKind, lat: f64, lng: f64, value: MemoValue) {
+  let precision = memo_precision();
+  if precision < 1 {
+    return;
+  }
+  let key = cell_key(kind, lat, lng, precision);
+  let point = ((lat * 100_000f64).round() as i64, (lng * 100_000f64).round() as i64);
+  let max_cells = match_env_usize("geo_memo_max_cells", GEO_MEMO_MAX_CELLS_DEFAULT);
+  let mut memo = globals::get::<ZoneMemo>();
+  match memo.cells.get_mut(&key) {
+    Some(cell) => {
+      if cell.value.signature() == value.signature() {
+        if !cell.samples.contains(&point) {
+          cell.samples.push(point);
+        }
+      } else {
+        cell.mixed = true;
+      }
+    },
+    None => {
+      memo.insert_cell(key, MemoCell { value, samples: vec![point], mixed: false }, max_cells);
+    }
+  }
+}
+
+/*
+* Number of memoised cells and lookups answered from them
+*/
+pub fn memo_stats() -> (usize, u64) {
+  let memo = globals::get::<ZoneMemo>();
+  (memo.cells.len(), memo.hits)
+}
+
+pub fn clear_memo() -> usize {
+  globals::get::<ZoneMemo>().clear_cells()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ocean_rows(lat: f64, lng: f64) -> MemoValue {
+    MemoValue::Placenames(vec![GeoNameRow::new_from_params(lat, lng, "South Pacific Ocean".to_owned(), "OCEAN".to_owned(), 0)])
+  }
+
+  fn cell(value: MemoValue) -> MemoCell {
+    MemoCell { value, samples: vec![(0, 0)], mixed: false }
+  }
+
+  #[test]
+  fn nearby_points_share_a_cell() {
+    assert_eq!(cell_key(MemoKind::Zone, 57.64911, 10.40744, 6), (MemoKind::Zone, "u4pruy".to_owned()));
+    assert_eq!(cell_key(MemoKind::Zone, 57.6495, 10.4078, 6), cell_key(MemoKind::Zone, 57.64911, 10.40744, 6));
+    assert_ne!(cell_key(MemoKind::Zone, 57.66, 10.40744, 6), cell_key(MemoKind::Zone, 57.64911, 10.40744, 6));
+    assert_ne!(cell_key(MemoKind::Placenames, 57.64911, 10.40744, 6), cell_key(MemoKind::Zone, 57.64911, 10.40744, 6));
+  }
+
+  #[test]
+  fn the_oldest_cells_are_evicted_when_full() {
+    let mut memo = ZoneMemo::default();
+    for (i, lng) in [10.0, 20.0, 30.0, 40.0].iter().enumerate() {
+      memo.insert_cell(cell_key(MemoKind::Placenames, 0.0, *lng, 4), cell(ocean_rows(0.0, *lng)), 3);
+      assert_eq!(memo.cells.len(), (i + 1).min(3));
+    }
+    assert!(!memo.cells.contains_key(&cell_key(MemoKind::Placenames, 0.0, 10.0, 4)));
+    assert!(memo.cells.contains_key(&cell_key(MemoKind::Placenames, 0.0, 20.0, 4)));
+    assert!(memo.cells.contains_key(&cell_key(MemoKind::Placenames, 0.0, 40.0, 4)));
+    assert_eq!(memo.clear_cells(), 3);
+    assert!(memo.order.is_empty());
+  }
+
+  #[test]
+  fn confirmed_cells_answer_other_points_with_their_own_coordinates() {
+    let (lat, lng) = (-30.001, -140.001);
+    assert!(memo_lookup(MemoKind::Placenames, lat, lng).is_none());
+    memo_record(MemoKind::Placenames, lat, lng, ocean_rows(lat, lng));
+    // one sample is not yet trusted
+    assert!(memo_lookup(MemoKind::Placenames, lat + 0.0005, lng).is_none());
+    memo_record(MemoKind::Placenames, lat + 0.0005, lng, ocean_rows(lat + 0.0005, lng));
+    match memo_lookup(MemoKind::Placenames, lat + 0.001, lng + 0.001) {
+      Some(MemoValue::Placenames(rows)) => {
+        assert_eq!(rows[0].name, "South Pacific Ocean");
+        assert_eq!((rows[0].lat, rows[0].lng), (lat + 0.001, lng + 0.001));
+      },
+      other => panic!("expected a memo hit, got {:?}", other)
+    }
+  }
+}
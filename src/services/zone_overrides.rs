@@ -0,0 +1,154 @@
+use mysql::prelude::Queryable;
+use crate::app::coords::{Coords, ring_contains_coords};
+use crate::data::mysql::connect_mysql;
+use crate::data::zone_overrides::parse_polygon;
+
+#[derive(Debug, Clone)]
+pub struct ZoneOverride {
+  pub zone_name: String,
+  valid_from: String,
+  valid_to: String,
+  bounds: (f64, f64, f64, f64),
+  polygon: Vec<(f64, f64)>,
+}
+
+impl ZoneOverride {
+  fn new(zone_name: &str, valid_from: &str, valid_to: &str, polygon: Vec<(f64, f64)>) -> ZoneOverride {
+    let lats = polygon.iter().map(|p| p.1);
+    let lngs = polygon.iter().map(|p| p.0);
+    let bounds = (lats.clone().fold(f64::MAX, f64::min), lats.fold(f64::MIN, f64::max), lngs.clone().fold(f64::MAX, f64::min), lngs.fold(f64::MIN, f64::max));
+    ZoneOverride { zone_name: zone_name.to_owned(), valid_from: valid_from.to_owned(), valid_to: valid_to.to_owned(), bounds, polygon }
+  }
+
+  /*
+  * ISO date ranges with an inclusive start and exclusive end, either of which may be open
+  */
+  fn is_valid_at(&self, date: &str) -> bool {
+    let day = date.get(0..10).unwrap_or(date);
+    (self.valid_from.is_empty() || day >= self.valid_from.as_str()) && (self.valid_to.is_empty() || day < self.valid_to.as_str())
+  }
+
+  fn contains(&self, coords: &Coords) -> bool {
+    let (min_lat, max_lat, min_lng, max_lng) = self.bounds;
+    coords.lat >= min_lat && coords.lat <= max_lat && coords.lng >= min_lng && coords.lng <= max_lng
+      && ring_contains_coords(&self.polygon, coords)
+  }
+}
+
+/*
+* The zone_overrides table is small, so it is read once and kept in memory
+*/
+#[derive(Default)]
+struct ZoneOverrides {
+  rows: Vec<ZoneOverride>,
+  loaded: bool,
+}
+
+fn fetch_zone_overrides() -> Vec<ZoneOverride> {
+  if let Ok(mut conn) = connect_mysql() {
+    conn.query_map("SELECT zone_name, valid_from, valid_to, polygon FROM zone_overrides",
+      |(zone_name, valid_from, valid_to, polygon): (String, String, String, String)| {
+        ZoneOverride::new(&zone_name, &valid_from, &valid_to, parse_polygon(&polygon))
+      }).unwrap_or_default()
+  } else {
+    vec![]
+  }
+}
+
+/*
+* Override for a point on a date among the given rows, taking dated entries before undated ones
+*/
+fn select_zone_override(rows: &[ZoneOverride], coords: &Coords, date: &str) -> Option<ZoneOverride> {
+  let mut matches: Vec<&ZoneOverride> = rows.iter().filter(|row| row.is_valid_at(date) && row.contains(coords)).collect();
+  matches.sort_by_key(|row| row.valid_from.is_empty() && row.valid_to.is_empty());
+  matches.first().map(|row| (*row).clone())
+}
+
+/*
+* Zone assigned to the point on the given date by the zone_overrides table.
+* The table is read without holding the lock, so other lookups are not kept waiting on MySQL.
+*/
+pub fn match_zone_override(lat: f64, lng: f64, date: &str) -> Option<ZoneOverride> {
+  let coords = Coords::new(lat, lng);
+  {
+    let overrides = globals::get::<ZoneOverrides>();
+    if overrides.loaded {
+      return select_zone_override(&overrides.rows, &coords, date);
+    }
+  }
+  let rows = fetch_zone_overrides();
+  let matched = select_zone_override(&rows, &coords, date);
+  let mut overrides = globals::get::<ZoneOverrides>();
+  if !overrides.loaded {
+    overrides.rows = rows;
+    overrides.loaded = true;
+  }
+  matched
+}
+
+/*
+* Reload the overrides on the next lookup
+*/
+pub fn clear_zone_overrides() -> usize {
+  let mut overrides = globals::get::<ZoneOverrides>();
+  let count = overrides.rows.len();
+  *overrides = ZoneOverrides::default();
+  count
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SHIPPED_OVERRIDES: &str = include_str!("../../data-sources/zone_overrides.csv");
+
+  fn shipped_overrides() -> Vec<ZoneOverride> {
+    SHIPPED_OVERRIDES.lines().skip(1).map(crate::data::geonames_import::split_csv_line)
+      .map(|c| ZoneOverride::new(&c[1], &c[2], &c[3], parse_polygon(&c[4]))).collect()
+  }
+
+  fn square(zone_name: &str, valid_from: &str, valid_to: &str) -> ZoneOverride {
+    ZoneOverride::new(zone_name, valid_from, valid_to, vec![(7.0, 48.0), (8.0, 48.0), (8.0, 49.0), (7.0, 49.0)])
+  }
+
+  #[test]
+  fn periods_include_the_start_and_exclude_the_end() {
+    let row = square("Europe/Berlin", "1871-05-10", "1918-11-11");
+    assert!(row.is_valid_at("1871-05-10T00:00:00"));
+    assert!(row.is_valid_at("1918-11-10"));
+    assert!(!row.is_valid_at("1918-11-11T12:00:00"));
+    assert!(!row.is_valid_at("1871-05-09"));
+    assert!(square("Europe/Vienna", "", "1918-11-04").is_valid_at("1850-01-01"));
+    assert!(square("Europe/Paris", "", "").is_valid_at("2024-01-01"));
+  }
+
+  #[test]
+  fn points_must_lie_within_the_polygon() {
+    let row = ZoneOverride::new("Europe/Berlin", "", "", vec![(7.0, 48.0), (8.0, 48.0), (7.0, 49.0)]);
+    assert!(row.contains(&Coords::new(48.2, 7.2)));
+    // within the bounding box but outside the triangle
+    assert!(!row.contains(&Coords::new(48.9, 7.9)));
+    assert!(!row.contains(&Coords::new(50.0, 7.5)));
+  }
+
+  #[test]
+  fn dated_overrides_take_precedence_over_undated_ones() {
+    let rows = vec![square("Europe/Paris", "", ""), square("Europe/Berlin", "1871-05-10", "1918-11-11")];
+    let strasbourg = Coords::new(48.58, 7.75);
+    assert_eq!(select_zone_override(&rows, &strasbourg, "1900-01-01").map(|row| row.zone_name).as_deref(), Some("Europe/Berlin"));
+    assert_eq!(select_zone_override(&rows, &strasbourg, "1930-01-01").map(|row| row.zone_name).as_deref(), Some("Europe/Paris"));
+    assert!(select_zone_override(&rows, &Coords::new(45.0, 7.5), "1900-01-01").is_none());
+  }
+
+  #[test]
+  fn shipped_overrides_cover_former_german_and_austrian_territories() {
+    let rows = shipped_overrides();
+    let zone_at = |lat: f64, lng: f64, date: &str| select_zone_override(&rows, &Coords::new(lat, lng), date).map(|row| row.zone_name);
+    assert_eq!(zone_at(48.58, 7.75, "1900-06-01").as_deref(), Some("Europe/Berlin"));
+    assert_eq!(zone_at(48.58, 7.75, "1920-06-01"), None);
+    assert_eq!(zone_at(46.50, 11.35, "1916-06-01").as_deref(), Some("Europe/Vienna"));
+    assert_eq!(zone_at(45.65, 13.78, "1917-06-01").as_deref(), Some("Europe/Vienna"));
+    assert_eq!(zone_at(46.50, 11.35, "1925-06-01"), None);
+    assert_eq!(zone_at(45.44, 12.33, "1916-06-01"), None);
+  }
+}